@@ -0,0 +1,410 @@
+use crate::{
+    audio::{
+        effects::EffectChain,
+        encode::{self, OutputFormat},
+        loudness::Loudness,
+    },
+    cache::Cache,
+    models::{NaturalModelTrait, Prosody, SynthesizedAudio},
+    processing::Processing,
+    text::normalize::Normalizer,
+    Model, TtsError,
+};
+use derive_builder::Builder;
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "coqui")]
+use crate::models::coqui;
+#[cfg(feature = "gtts")]
+use crate::models::gtts;
+#[cfg(feature = "meta")]
+use crate::models::meta;
+#[cfg(feature = "msedge")]
+use crate::models::msedge;
+#[cfg(feature = "parler")]
+use crate::models::parler;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+pub type AsyncResult<T> = Result<T, TtsError>;
+
+/// A boxed engine as stored in the [`AsyncNaturalTts`] registry.
+pub type AsyncEngine = Box<dyn AsyncNaturalModelTrait>;
+
+/// Async counterpart of [`NaturalModelTrait`].
+///
+/// Network engines implement this with real async I/O, anything else can be
+/// wrapped in [`Blocking`] to run on tokio's blocking pool.
+pub trait AsyncNaturalModelTrait: Send + Sync {
+    fn save<'a>(&'a self, message: String, path: &'a Path) -> BoxFuture<'a, AsyncResult<()>>;
+
+    fn synthesize_to_memory(
+        &self,
+        message: String,
+    ) -> BoxFuture<'_, AsyncResult<SynthesizedAudio<f32>>>;
+
+    /// Async counterpart of [`NaturalModelTrait::language`].
+    fn language(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async { None })
+    }
+
+    /// Async counterpart of [`NaturalModelTrait::configuration`].
+    fn configuration(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async { None })
+    }
+
+    /// Async counterpart of [`NaturalModelTrait::set_prosody`]. By default
+    /// nothing is native.
+    fn set_prosody(&self, prosody: Prosody) -> BoxFuture<'_, AsyncResult<Prosody>> {
+        Box::pin(async move { Ok(prosody) })
+    }
+}
+
+/// Runs a blocking [`NaturalModelTrait`] on tokio's blocking pool.
+pub struct Blocking<M> {
+    model: Arc<Mutex<M>>,
+}
+
+impl<M> Blocking<M> {
+    pub fn new(model: M) -> Self {
+        return Self {
+            model: Arc::new(Mutex::new(model)),
+        };
+    }
+}
+
+impl<M> Clone for Blocking<M> {
+    fn clone(&self) -> Self {
+        return Self {
+            model: self.model.clone(),
+        };
+    }
+}
+
+impl<M> Blocking<M>
+where
+    M: NaturalModelTrait<SynthesizeType = f32> + Send + 'static,
+{
+    async fn run<T, F>(&self, f: F) -> AsyncResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut M) -> Result<T, TtsError> + Send + 'static,
+    {
+        let model = self.model.clone();
+        tokio::task::spawn_blocking(move || -> AsyncResult<T> {
+            let mut model = model
+                .lock()
+                .map_err(|_| TtsError::engine("engine poisoned by an earlier panic"))?;
+            f(&mut model)
+        })
+        .await?
+    }
+}
+
+impl<M> AsyncNaturalModelTrait for Blocking<M>
+where
+    M: NaturalModelTrait<SynthesizeType = f32> + Send + 'static,
+{
+    fn save<'a>(&'a self, message: String, path: &'a Path) -> BoxFuture<'a, AsyncResult<()>> {
+        let path = path.to_path_buf();
+        Box::pin(self.run(move |model| model.save(message, &path)))
+    }
+
+    fn synthesize_to_memory(
+        &self,
+        message: String,
+    ) -> BoxFuture<'_, AsyncResult<SynthesizedAudio<f32>>> {
+        Box::pin(self.run(move |model| model.synthesize_to_memory(message)))
+    }
+
+    fn language(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async { self.run(|model| Ok(model.language())).await.ok()? })
+    }
+
+    fn configuration(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async { self.run(|model| Ok(model.configuration())).await.ok()? })
+    }
+
+    fn set_prosody(&self, prosody: Prosody) -> BoxFuture<'_, AsyncResult<Prosody>> {
+        Box::pin(self.run(move |model| model.set_prosody(&prosody)))
+    }
+}
+
+/// Async counterpart of [`NaturalTts`](crate::NaturalTts), with the same text
+/// normalizer, cache and audio processing. Processing and the cache's disk
+/// I/O run on tokio's blocking pool.
+#[derive(Builder, Default)]
+#[builder(setter(into), pattern = "owned")]
+pub struct AsyncNaturalTts {
+    pub default_model: Option<Model>,
+
+    /// Expands numbers, dates and the like before text reaches an engine.
+    /// Off by default, which sends text as is.
+    #[builder(default = "None")]
+    pub normalizer: Option<Normalizer>,
+
+    /// Serves repeated messages without synthesizing them again. Off by
+    /// default. Shared by every call, which may run at the same time.
+    #[builder(setter(custom), default)]
+    cache: Option<Arc<Mutex<Cache>>>,
+
+    /// The format [`AsyncNaturalTts::save`] writes. `None` picks it from the
+    /// extension of the path, falling back to WAV.
+    #[builder(default = "None")]
+    pub output_format: Option<OutputFormat>,
+
+    #[builder(setter(custom), default)]
+    engines: HashMap<Model, AsyncEngine>,
+
+    /// What is done to each engine's audio after synthesis, such as the part
+    /// of its prosody it couldn't apply natively.
+    #[builder(setter(skip))]
+    processing: HashMap<Model, Processing>,
+}
+
+impl AsyncNaturalTtsBuilder {
+    pub fn cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(Some(Arc::new(Mutex::new(cache))));
+        self
+    }
+
+    /// Registers `engine` under `id`, replacing any engine already registered under it.
+    pub fn engine<M>(mut self, id: Model, engine: M) -> Self
+    where
+        M: AsyncNaturalModelTrait + 'static,
+    {
+        self.engines
+            .get_or_insert_with(HashMap::new)
+            .insert(id, Box::new(engine));
+        self
+    }
+
+    #[cfg(feature = "parler")]
+    pub fn parler_model(self, model: parler::ParlerModel) -> Self {
+        self.engine(Model::Parler, Blocking::new(model))
+    }
+
+    #[cfg(feature = "coqui")]
+    pub fn coqui_model(self, model: coqui::CoquiModel) -> Self {
+        self.engine(Model::Coqui, Blocking::new(model))
+    }
+
+    #[cfg(feature = "gtts")]
+    pub fn gtts_model(self, model: gtts::GttsModel) -> Self {
+        self.engine(Model::Gtts, model)
+    }
+
+    #[cfg(feature = "msedge")]
+    pub fn msedge_model(self, model: msedge::MSEdgeModel) -> Self {
+        self.engine(Model::MSEdge, model)
+    }
+
+    #[cfg(feature = "meta")]
+    pub fn meta_model(self, model: meta::MetaModel) -> Self {
+        self.engine(Model::Meta, Blocking::new(model))
+    }
+}
+
+impl AsyncNaturalTts {
+    /// Registers `engine` under `id`, returning the engine it replaced, if any.
+    pub fn register<M>(&mut self, id: Model, engine: M) -> Option<AsyncEngine>
+    where
+        M: AsyncNaturalModelTrait + 'static,
+    {
+        self.processing.remove(&id);
+        self.engines.insert(id, Box::new(engine))
+    }
+
+    pub fn unregister(&mut self, id: &Model) -> Option<AsyncEngine> {
+        self.processing.remove(id);
+        self.engines.remove(id)
+    }
+
+    pub fn is_registered(&self, id: &Model) -> bool {
+        self.engines.contains_key(id)
+    }
+
+    /// The ids of every registered engine, in no particular order.
+    pub fn engines(&self) -> impl Iterator<Item = &Model> {
+        self.engines.keys()
+    }
+
+    pub fn engine(&self, id: &Model) -> Result<&dyn AsyncNaturalModelTrait, TtsError> {
+        match self.engines.get(id) {
+            Some(engine) => Ok(engine.as_ref()),
+            None => Err(TtsError::NotRegistered(id.clone())),
+        }
+    }
+
+    fn default_engine(&self) -> Result<&dyn AsyncNaturalModelTrait, TtsError> {
+        match &self.default_model {
+            Some(model) => self.engine(model),
+            None => Err(TtsError::NoDefaultModel),
+        }
+    }
+
+    /// Names the default engine in errors about what it doesn't support.
+    fn tag<T>(&self, result: Result<T, TtsError>) -> Result<T, TtsError> {
+        match &self.default_model {
+            Some(model) => result.map_err(|e| e.with_engine(model)),
+            None => result,
+        }
+    }
+
+    /// Sets the prosody of the engine `id`. Whatever the engine can't do
+    /// natively is applied to the audio it synthesizes.
+    pub async fn set_prosody(&mut self, id: &Model, prosody: Prosody) -> AsyncResult<()> {
+        let rest = self
+            .engine(id)?
+            .set_prosody(prosody)
+            .await
+            .map_err(|e| e.with_engine(id))?;
+        self.update_processing(id, |x| x.prosody = rest);
+        Ok(())
+    }
+
+    /// Runs all audio of the engine `id` through `effects`, after its prosody.
+    /// An empty chain turns them off.
+    pub fn set_effects(&mut self, id: &Model, effects: EffectChain) -> AsyncResult<()> {
+        self.engine(id)?;
+        self.update_processing(id, |x| x.effects = effects);
+        Ok(())
+    }
+
+    /// Resamples all audio of the engine `id` to `sample_rate`, or leaves it
+    /// at the engine's own rate when `None`.
+    pub fn set_output_sample_rate(
+        &mut self,
+        id: &Model,
+        sample_rate: Option<u32>,
+    ) -> AsyncResult<()> {
+        if sample_rate == Some(0) {
+            return Err(TtsError::not_supported("an output sample rate of 0 Hz"));
+        }
+        self.engine(id)?;
+        self.update_processing(id, |x| x.sample_rate = sample_rate);
+        Ok(())
+    }
+
+    /// Normalizes all audio of the engine `id` to `loudness`, or leaves it at
+    /// the engine's own level when `None`.
+    pub fn set_loudness(&mut self, id: &Model, loudness: Option<Loudness>) -> AsyncResult<()> {
+        self.engine(id)?;
+        self.update_processing(id, |x| x.loudness = loudness);
+        Ok(())
+    }
+
+    fn update_processing(&mut self, id: &Model, update: impl FnOnce(&mut Processing)) {
+        let processing = self.processing.entry(id.clone()).or_default();
+        update(processing);
+        if processing.is_empty() {
+            self.processing.remove(id);
+        }
+    }
+
+    /// What is left to do to the default engine's audio, if anything.
+    fn processing(&self) -> Option<Processing> {
+        self.default_model
+            .as_ref()
+            .and_then(|x| self.processing.get(x))
+            .cloned()
+    }
+
+    /// Runs `message` through the [`Normalizer`], if there is one, in the
+    /// default engine's language.
+    async fn normalize(&self, message: String) -> String {
+        let Some(normalizer) = &self.normalizer else {
+            return message;
+        };
+        let language = match self.default_engine() {
+            Ok(engine) => engine.language().await,
+            Err(_) => None,
+        };
+        normalizer.normalize(&message, language.as_deref())
+    }
+
+    /// The cache key of `message`, already normalized, when the default
+    /// engine's audio can be cached.
+    async fn cache_key(&self, message: &str) -> Option<String> {
+        self.cache.as_ref()?;
+        let model = self.default_model.as_ref()?;
+        let configuration = self.engines.get(model)?.configuration().await?;
+        let processing = self.processing.get(model).cloned().unwrap_or_default();
+        let configuration = processing.configuration(&configuration);
+        Some(Cache::key(model, &configuration, message))
+    }
+
+    /// Synthesizes `message`, already normalized, with the default engine and
+    /// processes it, going through the cache if there is one.
+    async fn render(&self, message: String) -> AsyncResult<SynthesizedAudio<f32>> {
+        let key = self.cache_key(&message).await;
+        let cache = key.zip(self.cache.clone());
+        if let Some((key, cache)) = cache.clone() {
+            let cached = tokio::task::spawn_blocking(move || lock(&cache).map(|mut x| x.get(&key)));
+            if let Some(audio) = cached.await?? {
+                return Ok(audio);
+            }
+        }
+
+        let audio = self.default_engine()?.synthesize_to_memory(message).await;
+        let mut audio = self.tag(audio)?;
+        let processing = self.processing();
+        tokio::task::spawn_blocking(move || {
+            if let Some(processing) = processing {
+                processing.apply(&mut audio)?;
+            }
+            if let Some((key, cache)) = cache {
+                lock(&cache)?.insert(key, &audio)?;
+            }
+            Ok(audio)
+        })
+        .await?
+    }
+
+    /// Synthesizes `message` with the default engine.
+    pub async fn synthesize_to_memory(
+        &self,
+        message: String,
+    ) -> AsyncResult<SynthesizedAudio<f32>> {
+        let message = self.normalize(message).await;
+        self.render(message).await
+    }
+
+    /// Saves `message` to `path` in [`AsyncNaturalTts::output_format`], as
+    /// [`NaturalTts::save`](crate::NaturalTts::save) does.
+    pub async fn save(&self, message: String, path: &Path) -> AsyncResult<()> {
+        let message = self.normalize(message).await;
+        let format = self
+            .output_format
+            .or_else(|| OutputFormat::from_path(path))
+            .unwrap_or_default();
+        let processing = self.processing().is_some();
+        if !format.is_encodable() && processing {
+            return Err(format.not_enabled());
+        }
+
+        // Engines pick the format from the extension, with default settings.
+        let by_extension = OutputFormat::from_path(path).unwrap_or_default();
+        let in_memory = processing || self.cache_key(&message).await.is_some();
+        let by_engine = format.same_format(&by_extension)
+            && (!format.is_encodable() || (self.output_format.is_none() && !in_memory));
+        if by_engine {
+            let saved = self.default_engine()?.save(message, path).await;
+            return self.tag(saved);
+        }
+        let audio = self.render(message).await?;
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || encode::save(&audio, format, &path)).await?
+    }
+}
+
+fn lock(cache: &Mutex<Cache>) -> Result<std::sync::MutexGuard<'_, Cache>, TtsError> {
+    cache
+        .lock()
+        .map_err(|_| TtsError::engine("cache poisoned by an earlier panic"))
+}
@@ -51,7 +51,7 @@ use std::f32;
 ///
 /// Coefficient a0 is implicitly 1.0.
 #[derive(Clone)]
-struct Filter {
+pub(crate) struct Filter {
     a1: f32,
     a2: f32,
     b0: f32,
@@ -118,6 +118,28 @@ impl Filter {
         }
     }
 
+    /// A second order Butterworth high-pass at `cutoff_hz`, which unlike
+    /// stage 2 above has unity gain in the pass band.
+    pub fn butterworth_high_pass(cutoff_hz: f32, sample_rate_hz: f32) -> Filter {
+        let q = f32::consts::FRAC_1_SQRT_2;
+        let center_hz = cutoff_hz.clamp(1.0, sample_rate_hz * 0.49);
+
+        let k = (f32::consts::PI * center_hz / sample_rate_hz).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Filter {
+            a1: 2.0 * (k * k - 1.0) / a0,
+            a2: (1.0 - k / q + k * k) / a0,
+            b0: 1.0 / a0,
+            b1: -2.0 / a0,
+            b2: 1.0 / a0,
+
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
     /// Feed the next input sample, get the next output sample.
     #[inline(always)]
     pub fn apply(&mut self, x0: f32) -> f32 {
@@ -0,0 +1,155 @@
+//! Joins pieces of synthesized audio end to end, with gaps or crossfades,
+//! into one buffer that remembers which text each piece came from.
+use super::resample;
+use crate::{
+    models::{Spec, SynthesizedAudio},
+    text::timeline::Timeline,
+    TtsError,
+};
+use hound::WavSpec;
+use std::{f32::consts::FRAC_PI_2, ops::Range, time::Duration};
+
+/// How each piece follows the one before it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Join {
+    /// Silence between the pieces.
+    Gap(Duration),
+    /// Overlaps the pieces, fading one out as the next comes in at constant
+    /// power. Never longer than the part of either piece not already faded.
+    Crossfade(Duration),
+}
+
+impl Default for Join {
+    fn default() -> Self {
+        Join::Gap(Duration::ZERO)
+    }
+}
+
+/// Where the audio of a piece of text ended up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioSegment {
+    pub text: String,
+    /// Indices into the interleaved samples. Crossfaded segments overlap.
+    pub samples: Range<usize>,
+    /// From the start of the audio.
+    pub offset: Duration,
+    pub duration: Duration,
+}
+
+/// The result of [`concat`].
+pub struct Joined {
+    pub audio: SynthesizedAudio<f32>,
+    /// One per piece, in order.
+    pub segments: Vec<AudioSegment>,
+    /// The word boundaries of every piece that had them, moved to where the
+    /// piece starts.
+    pub timeline: Timeline,
+}
+
+/// Joins `pieces` of text and their audio with `join` between each two.
+///
+/// The result has the highest sample rate and the most channels of any
+/// piece, and the sample format of the first one. The others are resampled
+/// and remixed to match, see [`remix`].
+pub fn concat<S: Into<String>>(
+    pieces: impl IntoIterator<Item = (S, SynthesizedAudio<f32>)>,
+    join: Join,
+) -> Result<Joined, TtsError> {
+    let pieces: Vec<(String, SynthesizedAudio<f32>)> = pieces
+        .into_iter()
+        .map(|(text, audio)| (text.into(), audio))
+        .collect();
+    let specs = pieces
+        .iter()
+        .map(|(_, audio)| audio.spec.layout())
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = *specs
+        .first()
+        .ok_or_else(|| TtsError::not_supported("concatenating no audio"))?;
+    let spec = WavSpec {
+        sample_rate: specs.iter().map(|x| x.sample_rate).max().unwrap_or(0),
+        channels: specs.iter().map(|x| x.channels.max(1)).max().unwrap_or(1),
+        ..first
+    };
+    let channels = spec.channels as usize;
+    let to_frames = |x: Duration| (x.as_secs_f64() * spec.sample_rate as f64).round() as usize;
+    let to_time = |x: usize| Duration::from_secs_f64(x as f64 / spec.sample_rate as f64);
+
+    let mut data: Vec<f32> = Vec::new();
+    let mut segments = Vec::with_capacity(pieces.len());
+    let mut timeline = Timeline::default();
+    // Frames at the end of the last piece that haven't been faded into yet.
+    let mut unfaded = 0;
+    for ((text, audio), piece_spec) in pieces.into_iter().zip(specs) {
+        let piece_timeline = audio.timeline();
+        let samples = resample::sinc(
+            &audio.data,
+            piece_spec.channels,
+            piece_spec.sample_rate,
+            spec.sample_rate,
+        );
+        let samples = remix(&samples, piece_spec.channels, spec.channels);
+        let frames = samples.len() / channels;
+
+        let mut overlap = 0;
+        if !segments.is_empty() {
+            match join {
+                Join::Gap(gap) => data.resize(data.len() + to_frames(gap) * channels, 0.0),
+                Join::Crossfade(fade) => overlap = to_frames(fade).min(unfaded).min(frames),
+            }
+        }
+
+        let start = data.len() / channels - overlap;
+        let tail = &mut data[start * channels..];
+        for (frame, (old, new)) in tail
+            .chunks_exact_mut(channels)
+            .zip(samples.chunks_exact(channels))
+            .enumerate()
+        {
+            let angle = (frame as f32 + 0.5) / overlap as f32 * FRAC_PI_2;
+            let (fade_in, fade_out) = angle.sin_cos();
+            for (old, new) in old.iter_mut().zip(new) {
+                *old = *old * fade_out + new * fade_in;
+            }
+        }
+        data.extend_from_slice(&samples[overlap * channels..]);
+        unfaded = frames - overlap;
+
+        timeline.extend(piece_timeline, to_time(start));
+        segments.push(AudioSegment {
+            text,
+            samples: start * channels..(start + frames) * channels,
+            offset: to_time(start),
+            duration: to_time(frames),
+        });
+    }
+
+    let duration = data.len() / channels;
+    Ok(Joined {
+        audio: SynthesizedAudio::new(data, Spec::Wav(spec), Some(duration as i32)),
+        segments,
+        timeline,
+    })
+}
+
+/// Converts interleaved `samples` from `from` channels to `to`. Mono goes to
+/// every channel and anything going to mono is averaged. Otherwise channels
+/// are kept by position, new ones being silent.
+pub fn remix(samples: &[f32], from: u16, to: u16) -> Vec<f32> {
+    let (from, to) = (from.max(1) as usize, to.max(1) as usize);
+    if from == to {
+        return samples.to_vec();
+    }
+
+    let mut out = Vec::with_capacity(samples.len() / from * to);
+    for frame in samples.chunks_exact(from) {
+        if to == 1 {
+            out.push(frame.iter().sum::<f32>() / from as f32);
+        } else if from == 1 {
+            out.extend(std::iter::repeat_n(frame[0], to));
+        } else {
+            out.extend((0..to).map(|x| frame.get(x).copied().unwrap_or(0.0)));
+        }
+    }
+    out
+}
@@ -0,0 +1,413 @@
+//! Clean-up effects for synthesized speech, chained in an [`EffectChain`].
+use super::{apply_gain, bs1770};
+use crate::{models::SynthesizedAudio, TtsError};
+use std::{f32::consts::PI, time::Duration};
+
+/// Length of the windows [`Effect::Trim`] measures the level of.
+const TRIM_WINDOW: f32 = 0.01;
+/// The corner frequency of [`Effect::DcRemoval`], low enough to leave speech alone.
+const DC_CUTOFF: f32 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    /// Cuts the leading and trailing audio quieter than `threshold` dBFS,
+    /// keeping `padding` of it on either side. Audio that is quiet all along
+    /// is left as is.
+    Trim { threshold: f32, padding: Duration },
+    /// Raises the start from silence along a half cosine.
+    FadeIn(Duration),
+    /// Lowers the end to silence along a half cosine.
+    FadeOut(Duration),
+    /// In dB.
+    Gain(f32),
+    /// A second order Butterworth high-pass, for rumble and plosives below
+    /// `cutoff` Hz.
+    HighPass { cutoff: f32 },
+    /// Removes a constant offset, which would otherwise click at the ends.
+    DcRemoval,
+}
+
+impl Effect {
+    pub fn apply(&self, audio: &mut SynthesizedAudio<f32>) -> Result<(), TtsError> {
+        let spec = audio.spec.layout()?;
+        let channels = spec.channels.max(1) as usize;
+        let rate = spec.sample_rate as f32;
+        let frames = |duration: &Duration| (duration.as_secs_f32() * rate) as usize;
+
+        match self {
+            Effect::Trim { threshold, padding } => {
+                let range = loud_range(&audio.data, channels, rate, *threshold);
+                if let Some((start, end)) = range {
+                    let start = start.saturating_sub(frames(padding));
+                    let total = audio.data.len() / channels;
+                    let end = (end + frames(padding)).min(total);
+                    audio.data.truncate(end * channels);
+                    audio.data.drain(..start * channels);
+                    audio.duration = Some((end - start) as i32);
+                    shift_boundaries(audio, start as f64 / rate as f64);
+                }
+            }
+            Effect::FadeIn(duration) => {
+                let length = frames(duration).min(audio.data.len() / channels);
+                fade_in(&mut audio.data, channels, length);
+            }
+            Effect::FadeOut(duration) => {
+                let length = frames(duration).min(audio.data.len() / channels);
+                let frames = audio.data.chunks_exact_mut(channels).rev().take(length);
+                for (frame, samples) in frames.enumerate() {
+                    let gain = fade(frame, length);
+                    samples.iter_mut().for_each(|x| *x *= gain);
+                }
+            }
+            Effect::Gain(db) => apply_gain(&mut audio.data, *db),
+            Effect::HighPass { cutoff } => {
+                for channel in 0..channels {
+                    let mut filter = bs1770::Filter::butterworth_high_pass(*cutoff, rate);
+                    for sample in audio.data.iter_mut().skip(channel).step_by(channels) {
+                        *sample = filter.apply(*sample);
+                    }
+                }
+            }
+            Effect::DcRemoval => {
+                let pole = (-2.0 * PI * DC_CUTOFF / rate).exp();
+                for channel in 0..channels {
+                    let (mut input, mut output) = (0.0, 0.0);
+                    for sample in audio.data.iter_mut().skip(channel).step_by(channels) {
+                        output = *sample - input + pole * output;
+                        input = *sample;
+                        *sample = output;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Effects applied one after the other.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EffectChain {
+    pub effects: Vec<Effect>,
+}
+
+impl EffectChain {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Adds `effect` at the end of the chain.
+    pub fn then(mut self, effect: Effect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Removes DC and rumble, trims silence under -50 dBFS down to 100ms and
+    /// fades 5ms in and out, for the long silences and clicks at the ends of
+    /// neural engines' output.
+    pub fn cleanup() -> Self {
+        return Self::new()
+            .then(Effect::DcRemoval)
+            .then(Effect::HighPass { cutoff: 60.0 })
+            .then(Effect::Trim {
+                threshold: -50.0,
+                padding: Duration::from_millis(100),
+            })
+            .then(Effect::FadeIn(Duration::from_millis(5)))
+            .then(Effect::FadeOut(Duration::from_millis(5)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn apply(&self, audio: &mut SynthesizedAudio<f32>) -> Result<(), TtsError> {
+        for effect in &self.effects {
+            effect.apply(audio)?;
+        }
+        Ok(())
+    }
+
+    /// The chain for audio that arrives chunk by chunk, see [`EffectStream`].
+    pub fn stream(&self, channels: u16, sample_rate: u32) -> EffectStream {
+        let channels = channels.max(1) as usize;
+        let rate = sample_rate as f32;
+        let frames = |duration: &Duration| (duration.as_secs_f32() * rate) as usize;
+        let stages = self
+            .effects
+            .iter()
+            .map(|effect| match effect {
+                Effect::Trim { threshold, padding } => Stage::Trim {
+                    threshold: super::db_to_gain(*threshold).powi(2),
+                    padding: frames(padding),
+                    window: ((rate * TRIM_WINDOW) as usize).max(1),
+                    partial: Vec::new(),
+                    held: Vec::new(),
+                    started: false,
+                },
+                Effect::FadeIn(duration) => Stage::FadeIn {
+                    length: frames(duration),
+                    held: Vec::new(),
+                    done: false,
+                },
+                Effect::FadeOut(duration) => Stage::FadeOut {
+                    length: frames(duration),
+                    held: Vec::new(),
+                },
+                Effect::Gain(db) => Stage::Gain(*db),
+                Effect::HighPass { cutoff } => Stage::HighPass(
+                    (0..channels)
+                        .map(|_| bs1770::Filter::butterworth_high_pass(*cutoff, rate))
+                        .collect(),
+                ),
+                Effect::DcRemoval => Stage::DcRemoval {
+                    pole: (-2.0 * PI * DC_CUTOFF / rate).exp(),
+                    state: vec![(0.0, 0.0); channels],
+                },
+            })
+            .collect();
+        return EffectStream { stages, channels };
+    }
+}
+
+/// An [`EffectChain`] for audio that arrives chunk by chunk. Filters carry
+/// their state from chunk to chunk, and trimming and fades only touch the
+/// ends of the whole stream, holding back what they need to see first. The
+/// result is the same as applying the chain to all of it at once.
+pub struct EffectStream {
+    stages: Vec<Stage>,
+    channels: usize,
+}
+
+impl EffectStream {
+    /// Takes interleaved `samples` and returns what is ready of the output.
+    pub fn push(&mut self, samples: Vec<f32>) -> Vec<f32> {
+        let channels = self.channels;
+        self.stages
+            .iter_mut()
+            .fold(samples, |samples, stage| stage.push(samples, channels))
+    }
+
+    /// Returns everything held back, now that the stream has ended.
+    pub fn finish(&mut self) -> Vec<f32> {
+        let channels = self.channels;
+        self.stages.iter_mut().fold(Vec::new(), |samples, stage| {
+            let mut out = stage.push(samples, channels);
+            out.extend(stage.finish(channels));
+            out
+        })
+    }
+}
+
+enum Stage {
+    Trim {
+        /// In mean power.
+        threshold: f32,
+        /// `padding` and `window` in frames.
+        padding: usize,
+        window: usize,
+        /// The window being filled.
+        partial: Vec<f32>,
+        /// The quiet audio since the start, or since the last loud window.
+        held: Vec<f32>,
+        started: bool,
+    },
+    FadeIn {
+        length: usize,
+        /// The start, until it is as long as the fade.
+        held: Vec<f32>,
+        done: bool,
+    },
+    FadeOut {
+        length: usize,
+        /// The last `length` frames so far.
+        held: Vec<f32>,
+    },
+    Gain(f32),
+    HighPass(Vec<bs1770::Filter>),
+    DcRemoval {
+        pole: f32,
+        /// The last input and output of each channel.
+        state: Vec<(f32, f32)>,
+    },
+}
+
+impl Stage {
+    fn push(&mut self, mut samples: Vec<f32>, channels: usize) -> Vec<f32> {
+        match self {
+            Stage::Trim {
+                window, partial, ..
+            } => {
+                let size = *window * channels;
+                partial.extend(samples);
+                let full = partial.len() / size * size;
+                let windows: Vec<f32> = partial.drain(..full).collect();
+                let mut out = Vec::new();
+                for window in windows.chunks(size) {
+                    self.trim_window(window, channels, &mut out);
+                }
+                out
+            }
+            Stage::FadeIn { length, held, done } => {
+                if *done {
+                    return samples;
+                }
+                held.extend(samples);
+                if held.len() < *length * channels {
+                    return Vec::new();
+                }
+                *done = true;
+                fade_in(held, channels, *length);
+                std::mem::take(held)
+            }
+            Stage::FadeOut { length, held } => {
+                held.extend(samples);
+                let ready = held.len().saturating_sub(*length * channels);
+                held.drain(..ready - ready % channels).collect()
+            }
+            Stage::Gain(db) => {
+                apply_gain(&mut samples, *db);
+                samples
+            }
+            Stage::HighPass(filters) => {
+                for (index, sample) in samples.iter_mut().enumerate() {
+                    *sample = filters[index % channels].apply(*sample);
+                }
+                samples
+            }
+            Stage::DcRemoval { pole, state } => {
+                for (index, sample) in samples.iter_mut().enumerate() {
+                    let (input, output) = &mut state[index % channels];
+                    *output = *sample - *input + *pole * *output;
+                    *input = *sample;
+                    *sample = *output;
+                }
+                samples
+            }
+        }
+    }
+
+    fn finish(&mut self, channels: usize) -> Vec<f32> {
+        match self {
+            Stage::Trim { partial, .. } => {
+                let mut out = Vec::new();
+                let last = std::mem::take(partial);
+                if !last.is_empty() {
+                    self.trim_window(&last, channels, &mut out);
+                }
+                let Stage::Trim {
+                    padding,
+                    held,
+                    started,
+                    ..
+                } = self
+                else {
+                    return out;
+                };
+                // Audio that is quiet all along is left as is.
+                let keep = match started {
+                    true => held.len().min(*padding * channels),
+                    false => held.len(),
+                };
+                out.extend(held.drain(..).take(keep));
+                out
+            }
+            Stage::FadeIn { length, held, done } => {
+                if !*done {
+                    let length = (*length).min(held.len() / channels);
+                    fade_in(held, channels, length);
+                }
+                std::mem::take(held)
+            }
+            Stage::FadeOut { length, held } => {
+                let length = (*length).min(held.len() / channels);
+                let frames = held.chunks_exact_mut(channels).rev().take(length);
+                for (frame, samples) in frames.enumerate() {
+                    let gain = fade(frame, length);
+                    samples.iter_mut().for_each(|x| *x *= gain);
+                }
+                std::mem::take(held)
+            }
+            Stage::Gain(_) | Stage::HighPass(_) | Stage::DcRemoval { .. } => Vec::new(),
+        }
+    }
+
+    /// Passes a whole window of [`Effect::Trim`] on, along with the quiet
+    /// audio before it, or holds it back while it is quiet.
+    fn trim_window(&mut self, window: &[f32], channels: usize, out: &mut Vec<f32>) {
+        let Stage::Trim {
+            threshold,
+            padding,
+            held,
+            started,
+            ..
+        } = self
+        else {
+            return;
+        };
+        if !is_loud(window, *threshold) {
+            held.extend_from_slice(window);
+            return;
+        }
+        let skip = match started {
+            true => 0,
+            false => held.len().saturating_sub(*padding * channels),
+        };
+        *started = true;
+        out.extend(held.drain(..).skip(skip));
+        out.extend_from_slice(window);
+    }
+}
+
+/// Fades in the first `length` frames of interleaved `samples`.
+fn fade_in(samples: &mut [f32], channels: usize, length: usize) {
+    let frames = samples.chunks_exact_mut(channels).take(length);
+    for (frame, samples) in frames.enumerate() {
+        let gain = fade(frame, length);
+        samples.iter_mut().for_each(|x| *x *= gain);
+    }
+}
+
+/// The gain `frame` frames into a fade of `length` frames from silence.
+fn fade(frame: usize, length: usize) -> f32 {
+    0.5 - 0.5 * (PI * frame as f32 / length as f32).cos()
+}
+
+/// The frames from the start of the first window louder than `threshold`
+/// dBFS to the end of the last one.
+fn loud_range(
+    samples: &[f32],
+    channels: usize,
+    rate: f32,
+    threshold: f32,
+) -> Option<(usize, usize)> {
+    let window = ((rate * TRIM_WINDOW) as usize).max(1);
+    let threshold = super::db_to_gain(threshold).powi(2);
+    let loud: Vec<bool> = samples
+        .chunks(window * channels)
+        .map(|x| is_loud(x, threshold))
+        .collect();
+
+    let first = loud.iter().position(|x| *x)?;
+    let last = loud.iter().rposition(|x| *x)?;
+    let frames = samples.len() / channels;
+    Some((first * window, ((last + 1) * window).min(frames)))
+}
+
+/// Whether the mean power of `window` is over `threshold`.
+fn is_loud(window: &[f32], threshold: f32) -> bool {
+    window.iter().map(|x| x * x).sum::<f32>() / window.len() as f32 > threshold
+}
+
+/// Moves word boundaries back by `seconds` cut from the start.
+#[cfg_attr(not(feature = "msedge"), allow(unused_variables))]
+fn shift_boundaries(audio: &mut SynthesizedAudio<f32>, seconds: f64) {
+    #[cfg(feature = "msedge")]
+    if let crate::models::Spec::Synthesized(_, _, metadata) = &mut audio.spec {
+        // MSEdge counts in ticks of 100ns.
+        let ticks = (seconds * 1e7) as u64;
+        for x in metadata {
+            x.offset = x.offset.saturating_sub(ticks);
+        }
+    }
+}
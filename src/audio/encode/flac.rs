@@ -0,0 +1,335 @@
+//! A 16-bit FLAC encoder built on the fixed predictors of the format, which
+//! on speech get close to what the adaptive ones of libFLAC do.
+use std::io::{self, Write};
+
+/// Frames per block, the usual choice of libFLAC.
+const BLOCK_SIZE: usize = 4096;
+const BITS_PER_SAMPLE: u32 = 16;
+/// The largest Rice parameter the 4-bit residual coding method can signal.
+const MAX_RICE: u32 = 14;
+
+/// Writes interleaved `samples` as a FLAC stream. `compression` goes from 0,
+/// fastest, to 8, smallest.
+pub fn encode<W: Write>(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    compression: u8,
+    writer: &mut W,
+) -> io::Result<()> {
+    if !(1..=8).contains(&channels) || !(1..1 << 20).contains(&sample_rate) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "FLAC can't hold {} channels at {} Hz",
+                channels, sample_rate
+            ),
+        ));
+    }
+    let channels = channels as usize;
+    let samples: Vec<i64> = samples
+        .iter()
+        .map(|x| (x.clamp(-1.0, 1.0) * 32767.0).round() as i64)
+        .collect();
+    let frames = samples.len() / channels;
+
+    let mut out = BitWriter::default();
+    out.bytes.extend_from_slice(b"fLaC");
+    // STREAMINFO, the last and only metadata block.
+    out.write(1, 1);
+    out.write(0, 7);
+    out.write(34, 24);
+    out.write(BLOCK_SIZE as u64, 16);
+    out.write(BLOCK_SIZE as u64, 16);
+    // Frame sizes and the MD5 signature are left unknown.
+    out.write(0, 24);
+    out.write(0, 24);
+    out.write(sample_rate as u64, 20);
+    out.write(channels as u64 - 1, 3);
+    out.write(BITS_PER_SAMPLE as u64 - 1, 5);
+    out.write(frames as u64 >> 32, 4);
+    out.write(frames as u64 & 0xffff_ffff, 32);
+    out.bytes.extend_from_slice(&[0; 16]);
+
+    let settings = Settings::new(compression);
+    for (number, block) in samples[..frames * channels]
+        .chunks(BLOCK_SIZE * channels)
+        .enumerate()
+    {
+        write_frame(&mut out, block, channels, number as u32, &settings);
+        writer.write_all(&out.bytes)?;
+        out.bytes.clear();
+    }
+    writer.write_all(&out.bytes)
+}
+
+struct Settings {
+    max_order: usize,
+    max_partition_order: u32,
+}
+
+impl Settings {
+    fn new(compression: u8) -> Self {
+        return Self {
+            max_order: if compression == 0 { 2 } else { 4 },
+            max_partition_order: compression.min(8) as u32,
+        };
+    }
+}
+
+fn write_frame(
+    out: &mut BitWriter,
+    block: &[i64],
+    channels: usize,
+    number: u32,
+    settings: &Settings,
+) {
+    let size = block.len() / channels;
+    let start = out.bytes.len();
+
+    // Sync code and fixed block size.
+    out.write(0xfff8, 16);
+    // Block size as a 16-bit value at the end of the header, sample rate from STREAMINFO.
+    out.write(0b0111, 4);
+    out.write(0b0000, 4);
+    // Channels coded independently, 16 bits per sample.
+    out.write(channels as u64 - 1, 4);
+    out.write(0b100, 3);
+    out.write(0, 1);
+    for byte in utf8(number) {
+        out.write(byte as u64, 8);
+    }
+    out.write(size as u64 - 1, 16);
+    let crc = crc8(&out.bytes[start..]);
+    out.write(crc as u64, 8);
+
+    for channel in 0..channels {
+        let signal: Vec<i64> = block
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .copied()
+            .collect();
+        write_subframe(out, &signal, settings);
+    }
+
+    out.align();
+    let crc = crc16(&out.bytes[start..]);
+    out.write(crc as u64, 16);
+}
+
+/// Writes whichever of a constant, a verbatim or a fixed-predictor subframe is smallest.
+fn write_subframe(out: &mut BitWriter, signal: &[i64], settings: &Settings) {
+    if signal.iter().all(|x| *x == signal[0]) {
+        out.write(0b000000 << 1, 8);
+        out.write_signed(signal[0], BITS_PER_SAMPLE);
+        return;
+    }
+
+    let verbatim = signal.len() as u64 * BITS_PER_SAMPLE as u64;
+    let best = (0..=settings.max_order.min(signal.len() - 1))
+        .map(|order| {
+            let residual = residual(signal, order);
+            let partitions = partitions(&residual, order, signal.len(), settings);
+            (order, residual, partitions)
+        })
+        .min_by_key(|(order, _, partitions)| fixed_bits(*order, partitions));
+
+    match best {
+        Some((order, residual, partitions)) if fixed_bits(order, &partitions) < verbatim => {
+            out.write((0b001000 | order as u64) << 1, 8);
+            for sample in &signal[..order] {
+                out.write_signed(*sample, BITS_PER_SAMPLE);
+            }
+            write_residual(out, &residual, order, &partitions);
+        }
+        _ => {
+            out.write(0b000001 << 1, 8);
+            for sample in signal {
+                out.write_signed(*sample, BITS_PER_SAMPLE);
+            }
+        }
+    }
+}
+
+/// The size of a fixed-predictor subframe, but for its header.
+fn fixed_bits(order: usize, partitions: &Partitions) -> u64 {
+    order as u64 * BITS_PER_SAMPLE as u64 + partitions.bits
+}
+
+/// What the fixed predictor of `order` leaves unpredicted, from sample `order` on.
+fn residual(signal: &[i64], order: usize) -> Vec<i64> {
+    (order..signal.len())
+        .map(|i| {
+            let x = |back: usize| signal[i - back];
+            match order {
+                0 => x(0),
+                1 => x(0) - x(1),
+                2 => x(0) - 2 * x(1) + x(2),
+                3 => x(0) - 3 * x(1) + 3 * x(2) - x(3),
+                _ => x(0) - 4 * x(1) + 6 * x(2) - 4 * x(3) + x(4),
+            }
+        })
+        .collect()
+}
+
+struct Partitions {
+    order: u32,
+    parameters: Vec<u32>,
+    /// Size of the whole residual section.
+    bits: u64,
+}
+
+/// The partition order and Rice parameters that code `residual` in the fewest bits.
+fn partitions(residual: &[i64], order: usize, size: usize, settings: &Settings) -> Partitions {
+    let folded: Vec<u64> = residual.iter().map(|x| zigzag(*x)).collect();
+    let mut best: Option<Partitions> = None;
+    for partition_order in 0..=settings.max_partition_order {
+        let count = 1usize << partition_order;
+        if !size.is_multiple_of(count) || size / count <= order {
+            break;
+        }
+
+        let mut parameters = Vec::with_capacity(count);
+        let mut bits = 2 + 4;
+        let mut start = 0;
+        for partition in 0..count {
+            let length = size / count - if partition == 0 { order } else { 0 };
+            let (parameter, cost) = rice_parameter(&folded[start..start + length]);
+            parameters.push(parameter);
+            bits += 4 + cost;
+            start += length;
+        }
+
+        if best.as_ref().is_none_or(|x| bits < x.bits) {
+            best = Some(Partitions {
+                order: partition_order,
+                parameters,
+                bits,
+            });
+        }
+    }
+
+    best.unwrap_or_else(|| {
+        let (parameter, cost) = rice_parameter(&folded);
+        Partitions {
+            order: 0,
+            parameters: vec![parameter],
+            bits: 2 + 4 + 4 + cost,
+        }
+    })
+}
+
+/// The Rice parameter coding `values` in the fewest bits, with that count.
+fn rice_parameter(values: &[u64]) -> (u32, u64) {
+    (0..=MAX_RICE)
+        .map(|k| {
+            let quotients: u64 = values.iter().map(|x| x >> k).sum();
+            (k, quotients + values.len() as u64 * (k as u64 + 1))
+        })
+        .min_by_key(|x| x.1)
+        .unwrap()
+}
+
+fn write_residual(out: &mut BitWriter, residual: &[i64], order: usize, partitions: &Partitions) {
+    // Coding method 0, 4-bit Rice parameters.
+    out.write(0b00, 2);
+    out.write(partitions.order as u64, 4);
+    let count = partitions.parameters.len();
+    let size = (residual.len() + order) / count;
+    let mut start = 0;
+    for (partition, k) in partitions.parameters.iter().enumerate() {
+        let length = size - if partition == 0 { order } else { 0 };
+        out.write(*k as u64, 4);
+        for value in &residual[start..start + length] {
+            let folded = zigzag(*value);
+            out.write_unary((folded >> k) as u32);
+            if *k > 0 {
+                out.write(folded, *k);
+            }
+        }
+        start += length;
+    }
+}
+
+/// Maps signed values onto unsigned ones, 0, -1, 1, -2... becoming 0, 1, 2, 3...
+fn zigzag(x: i64) -> u64 {
+    ((x << 1) ^ (x >> 63)) as u64
+}
+
+/// The frame number, coded like a UTF-8 character.
+fn utf8(value: u32) -> Vec<u8> {
+    if value < 0x80 {
+        return vec![value as u8];
+    }
+    let mut continuation = Vec::new();
+    let mut value = value;
+    let mut room = 0x3f;
+    while value > room >> 1 {
+        continuation.push(0x80 | (value & 0x3f) as u8);
+        value >>= 6;
+        room >>= 1;
+    }
+    let length = continuation.len() + 1;
+    let lead = (0xff00u16 >> length) as u8 | value as u8;
+    std::iter::once(lead)
+        .chain(continuation.into_iter().rev())
+        .collect()
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x07,
+        })
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x8005,
+        })
+    })
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    pending: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Writes the low `bits` bits of `value`, most significant first. `bits` is at most 32.
+    fn write(&mut self, value: u64, bits: u32) {
+        self.pending = (self.pending << bits) | (value & ((1u64 << bits) - 1));
+        self.count += bits;
+        while self.count >= 8 {
+            self.count -= 8;
+            self.bytes.push((self.pending >> self.count) as u8);
+        }
+        self.pending &= (1u64 << self.count) - 1;
+    }
+
+    fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value as u64, bits);
+    }
+
+    /// `zeros` zero bits and a one.
+    fn write_unary(&mut self, mut zeros: u32) {
+        while zeros >= 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, zeros + 1);
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.write(0, 8 - self.count);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Encodes synthesized audio into the formats it can be saved as.
+//!
+//! WAV and FLAC are always available. MP3 needs the `mp3` feature, which
+//! builds LAME, Ogg Vorbis the `vorbis` feature, which builds libvorbis, and
+//! Ogg Opus the `opus` feature, which links libopus. That feature also reads
+//! Ogg Opus back with [`decode_ogg_opus`], since services answer in it.
+mod flac;
+#[cfg(feature = "mp3")]
+mod mp3;
+#[cfg(feature = "opus")]
+mod opus;
+#[cfg(feature = "vorbis")]
+mod vorbis;
+pub mod wav;
+
+use crate::{models::SynthesizedAudio, TtsError};
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    path::Path,
+};
+
+#[cfg(feature = "opus")]
+pub use opus::decode as decode_ogg_opus;
+pub use wav::WavFormat;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// In the sample format of the audio's spec when `format` is `None`.
+    Wav { format: Option<WavFormat> },
+    /// 16-bit FLAC. `compression` goes from 0, fastest, to 8, smallest.
+    Flac { compression: u8 },
+    /// Constant `bitrate` in kbps, rounded to the closest one MP3 has.
+    Mp3 { bitrate: u32 },
+    /// `quality` from -0.2, smallest, to 1.0, best.
+    OggVorbis { quality: f32 },
+    /// `bitrate` in kbps. The audio is resampled to 48kHz, the only rate Opus
+    /// decoders play back at.
+    OggOpus { bitrate: u32 },
+}
+
+impl OutputFormat {
+    /// The format `path`'s extension names, with default settings.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "wav" | "wave" => Some(Self::default()),
+            "flac" => Some(Self::Flac { compression: 5 }),
+            "mp3" => Some(Self::Mp3 { bitrate: 128 }),
+            "ogg" | "oga" => Some(Self::OggVorbis { quality: 0.5 }),
+            "opus" => Some(Self::OggOpus { bitrate: 32 }),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Wav { .. } => "WAV",
+            Self::Flac { .. } => "FLAC",
+            Self::Mp3 { .. } => "MP3",
+            Self::OggVorbis { .. } => "Ogg Vorbis",
+            Self::OggOpus { .. } => "Ogg Opus",
+        }
+    }
+
+    /// Whether [`encode`] can write this format with the features enabled.
+    pub fn is_encodable(&self) -> bool {
+        match self {
+            Self::Wav { .. } | Self::Flac { .. } => true,
+            Self::Mp3 { .. } => cfg!(feature = "mp3"),
+            Self::OggVorbis { .. } => cfg!(feature = "vorbis"),
+            Self::OggOpus { .. } => cfg!(feature = "opus"),
+        }
+    }
+
+    /// The feature that enables the encoder, if it has one.
+    fn feature(&self) -> Option<&'static str> {
+        match self {
+            Self::Wav { .. } | Self::Flac { .. } => None,
+            Self::Mp3 { .. } => Some("mp3"),
+            Self::OggVorbis { .. } => Some("vorbis"),
+            Self::OggOpus { .. } => Some("opus"),
+        }
+    }
+
+    /// The error for encoding this format without its feature.
+    pub(crate) fn not_enabled(&self) -> TtsError {
+        let feature = self.feature().unwrap_or_default();
+        TtsError::not_supported(format!(
+            "encoding {} without the `{}` feature",
+            self.name(),
+            feature
+        ))
+    }
+
+    /// Whether `other` is the same format, whatever its settings.
+    pub fn same_format(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Wav { format: None }
+    }
+}
+
+/// Writes `audio` to `writer` in `format`.
+pub fn encode<W: Write + Seek>(
+    audio: &SynthesizedAudio<f32>,
+    format: OutputFormat,
+    mut writer: W,
+) -> Result<(), TtsError> {
+    let spec = audio.spec.layout()?;
+    match format {
+        OutputFormat::Wav { format } => wav::write(audio, format, writer),
+        OutputFormat::Flac { compression } => {
+            flac::encode(
+                &audio.data,
+                spec.channels,
+                spec.sample_rate,
+                compression,
+                &mut writer,
+            )?;
+            Ok(writer.flush()?)
+        }
+        #[cfg(feature = "opus")]
+        OutputFormat::OggOpus { bitrate } => {
+            opus::encode(
+                &audio.data,
+                spec.channels,
+                spec.sample_rate,
+                bitrate,
+                &mut writer,
+            )?;
+            Ok(writer.flush()?)
+        }
+        #[cfg(feature = "mp3")]
+        OutputFormat::Mp3 { bitrate } => {
+            mp3::encode(
+                &audio.data,
+                spec.channels,
+                spec.sample_rate,
+                bitrate,
+                &mut writer,
+            )?;
+            Ok(writer.flush()?)
+        }
+        #[cfg(feature = "vorbis")]
+        OutputFormat::OggVorbis { quality } => {
+            vorbis::encode(
+                &audio.data,
+                spec.channels,
+                spec.sample_rate,
+                quality,
+                &mut writer,
+            )?;
+            Ok(writer.flush()?)
+        }
+        #[allow(unreachable_patterns)]
+        format => Err(format.not_enabled()),
+    }
+}
+
+/// Writes `audio` to the file at `path` in `format`.
+pub fn save(
+    audio: &SynthesizedAudio<f32>,
+    format: OutputFormat,
+    path: &Path,
+) -> Result<(), TtsError> {
+    if !format.is_encodable() {
+        return Err(format.not_enabled());
+    }
+    encode(audio, format, BufWriter::new(File::create(path)?))
+}
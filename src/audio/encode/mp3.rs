@@ -0,0 +1,83 @@
+//! MP3 through LAME, at a constant bitrate.
+use crate::TtsError;
+use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, InterleavedPcm, MonoPcm, Quality};
+use std::io::Write;
+
+/// Frames handed to LAME at a time.
+const BLOCK: usize = 4096;
+
+pub fn encode<W: Write>(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    bitrate: u32,
+    writer: &mut W,
+) -> Result<(), TtsError> {
+    if !(1..=2).contains(&channels) {
+        let operation = format!("MP3 with {} channels", channels);
+        return Err(TtsError::not_supported(operation));
+    }
+    let mut builder =
+        Builder::new().ok_or_else(|| TtsError::Encode("LAME failed to start".into()))?;
+    builder
+        .set_num_channels(channels as u8)
+        .map_err(encode_error)?;
+    builder.set_sample_rate(sample_rate).map_err(encode_error)?;
+    builder
+        .set_brate(to_bitrate(bitrate))
+        .map_err(encode_error)?;
+    builder.set_quality(Quality::Good).map_err(encode_error)?;
+    let mut encoder = builder.build().map_err(encode_error)?;
+
+    let channels = channels as usize;
+    let mut output = Vec::new();
+    for block in samples.chunks(BLOCK * channels) {
+        output.clear();
+        output.reserve(mp3lame_encoder::max_required_buffer_size(block.len()));
+        let written = match channels {
+            1 => encoder.encode_to_vec(MonoPcm(block), &mut output),
+            _ => encoder.encode_to_vec(InterleavedPcm(block), &mut output),
+        };
+        written.map_err(encode_error)?;
+        writer.write_all(&output)?;
+    }
+
+    output.clear();
+    output.reserve(mp3lame_encoder::max_required_buffer_size(0).max(7200));
+    encoder
+        .flush_to_vec::<FlushNoGap>(&mut output)
+        .map_err(encode_error)?;
+    writer.write_all(&output)?;
+    Ok(())
+}
+
+/// The bitrate LAME supports closest to `kbps`.
+fn to_bitrate(kbps: u32) -> Bitrate {
+    const BITRATES: [(u32, Bitrate); 16] = [
+        (8, Bitrate::Kbps8),
+        (16, Bitrate::Kbps16),
+        (24, Bitrate::Kbps24),
+        (32, Bitrate::Kbps32),
+        (40, Bitrate::Kbps40),
+        (48, Bitrate::Kbps48),
+        (64, Bitrate::Kbps64),
+        (80, Bitrate::Kbps80),
+        (96, Bitrate::Kbps96),
+        (112, Bitrate::Kbps112),
+        (128, Bitrate::Kbps128),
+        (160, Bitrate::Kbps160),
+        (192, Bitrate::Kbps192),
+        (224, Bitrate::Kbps224),
+        (256, Bitrate::Kbps256),
+        (320, Bitrate::Kbps320),
+    ];
+    BITRATES
+        .iter()
+        .min_by_key(|(x, _)| x.abs_diff(kbps))
+        .map(|(_, x)| *x)
+        .unwrap_or(Bitrate::Kbps128)
+}
+
+fn encode_error(error: impl std::error::Error + Send + Sync + 'static) -> TtsError {
+    TtsError::Encode(Box::new(error))
+}
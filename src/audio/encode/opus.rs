@@ -0,0 +1,175 @@
+//! Ogg Opus, laid out as RFC 7845 describes. Services such as MSEdge answer
+//! in it too, so it is also decoded here.
+use crate::{
+    audio::resample,
+    models::{Spec, SynthesizedAudio},
+    TtsError,
+};
+use audiopus::{
+    coder::{Decoder, Encoder},
+    Application, Bitrate, Channels, SampleRate,
+};
+use hound::{SampleFormat, WavSpec};
+use ogg::{
+    reading::PacketReader,
+    writing::{PacketWriteEndInfo, PacketWriter},
+};
+use std::io::{Cursor, Write};
+
+/// Opus always runs at 48kHz, whatever rate the input had.
+const RATE: u32 = 48_000;
+/// Frames in a 20ms packet.
+const PACKET: usize = 960;
+/// Any serial will do for a file holding a single stream.
+const SERIAL: u32 = 0x6e74_7473;
+/// What libopus recommends as the largest packet to expect.
+const MAX_PACKET: usize = 4000;
+/// Frames in the longest packet Opus allows, 120ms.
+const MAX_FRAMES: usize = 5760;
+
+pub fn encode<W: Write>(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    bitrate: u32,
+    writer: &mut W,
+) -> Result<(), TtsError> {
+    let layout = match channels {
+        1 => Channels::Mono,
+        2 => Channels::Stereo,
+        _ => {
+            let operation = format!("Opus with {} channels", channels);
+            return Err(TtsError::not_supported(operation));
+        }
+    };
+    let mut encoder = Encoder::new(SampleRate::Hz48000, layout, Application::Voip)?;
+    encoder.set_bitrate(Bitrate::BitsPerSecond(bitrate as i32 * 1000))?;
+    let pre_skip = encoder.lookahead()? as usize;
+
+    let samples = resample::sinc(samples, channels, sample_rate, RATE);
+    let channels = channels as usize;
+    let frames = samples.len() / channels;
+
+    let mut ogg = PacketWriter::new(writer);
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels as u8);
+    head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+    // The rate of the original, which players may resample back to.
+    head.extend_from_slice(&sample_rate.to_le_bytes());
+    // No output gain and the mono or stereo channel mapping.
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+    ogg.write_packet(
+        head.into_boxed_slice(),
+        SERIAL,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+
+    let vendor = concat!("natural-tts ", env!("CARGO_PKG_VERSION"));
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    ogg.write_packet(
+        tags.into_boxed_slice(),
+        SERIAL,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+
+    // Decoders drop the first `pre_skip` frames, the encoder's lookahead, so
+    // the packets run that much past the end of the audio. The granule
+    // position of the last one cuts off the padding.
+    let total = frames + pre_skip;
+    let packets = total.div_ceil(PACKET).max(1);
+    let mut input = vec![0.0; PACKET * channels];
+    let mut output = vec![0; MAX_PACKET];
+    for packet in 0..packets {
+        let start = (packet * PACKET * channels).min(samples.len());
+        let end = ((packet + 1) * PACKET * channels).min(samples.len());
+        input.fill(0.0);
+        input[..end - start].copy_from_slice(&samples[start..end]);
+        let length = encoder.encode_float(&input, &mut output)?;
+
+        let (info, granule) = match packet + 1 == packets {
+            true => (PacketWriteEndInfo::EndStream, total),
+            false => (PacketWriteEndInfo::NormalPacket, (packet + 1) * PACKET),
+        };
+        ogg.write_packet(output[..length].into(), SERIAL, info, granule as u64)?;
+    }
+
+    Ok(())
+}
+
+/// Decodes an Ogg Opus stream at 48kHz, without the encoder's pre-skip and
+/// the padding past the last granule position.
+pub fn decode(bytes: &[u8]) -> Result<SynthesizedAudio<f32>, TtsError> {
+    let mut ogg = PacketReader::new(Cursor::new(bytes));
+    let head = ogg.read_packet().map_err(decode_error)?;
+    let head = match head {
+        Some(packet) if packet.data.len() >= 19 && packet.data.starts_with(b"OpusHead") => {
+            packet.data
+        }
+        _ => return Err(TtsError::InvalidData("not an Ogg Opus stream".to_string())),
+    };
+    let channels = head[9] as u16;
+    let pre_skip = u16::from_le_bytes([head[10], head[11]]) as usize;
+    let gain = i16::from_le_bytes([head[16], head[17]]);
+    let layout = match (channels, head[18]) {
+        (1, 0) => Channels::Mono,
+        (2, 0) => Channels::Stereo,
+        _ => {
+            let operation = format!("decoding Opus with {} channels", channels);
+            return Err(TtsError::not_supported(operation));
+        }
+    };
+    // The comment header holds nothing the audio needs.
+    ogg.read_packet().map_err(decode_error)?;
+
+    let mut decoder = Decoder::new(SampleRate::Hz48000, layout).map_err(decode_error)?;
+    let channels = channels as usize;
+    let mut output = vec![0.0; MAX_FRAMES * channels];
+    let mut samples = Vec::new();
+    let mut end = None;
+    while let Some(packet) = ogg.read_packet().map_err(decode_error)? {
+        end = Some(packet.absgp_page() as usize);
+        if packet.data.is_empty() {
+            continue;
+        }
+        let input = packet.data.as_slice().try_into().map_err(decode_error)?;
+        let signals = output.as_mut_slice().try_into().map_err(decode_error)?;
+        let frames = decoder
+            .decode_float(Some(input), signals, false)
+            .map_err(decode_error)?;
+        samples.extend_from_slice(&output[..frames * channels]);
+    }
+
+    // The granule position of the last page counts the pre-skip as well.
+    if let Some(end) = end.filter(|x| *x >= pre_skip) {
+        samples.truncate((end * channels).min(samples.len()));
+    }
+    samples.drain(..(pre_skip * channels).min(samples.len()));
+    if gain != 0 {
+        // Q7.8 decibels.
+        let gain = 10f32.powf(gain as f32 / (20.0 * 256.0));
+        samples.iter_mut().for_each(|x| *x *= gain);
+    }
+
+    let duration = samples.len() / channels;
+    Ok(SynthesizedAudio::new(
+        samples,
+        Spec::Wav(WavSpec {
+            channels: channels as u16,
+            sample_rate: RATE,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        }),
+        Some(duration as i32),
+    ))
+}
+
+fn decode_error(error: impl std::error::Error + Send + Sync + 'static) -> TtsError {
+    TtsError::Decode(Box::new(error))
+}
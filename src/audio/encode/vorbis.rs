@@ -0,0 +1,54 @@
+//! Ogg Vorbis through libvorbis, in quality-based VBR.
+use crate::TtsError;
+use std::{
+    io::Write,
+    num::{NonZeroU32, NonZeroU8},
+};
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+/// Frames handed to libvorbis at a time, what its documentation suggests.
+const BLOCK: usize = 1024;
+/// Any serial will do for a file holding a single stream.
+const SERIAL: i32 = 0x6e74_7473;
+
+pub fn encode<W: Write>(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    quality: f32,
+    writer: &mut W,
+) -> Result<(), TtsError> {
+    let (Some(rate), Some(layout)) = (
+        NonZeroU32::new(sample_rate),
+        u8::try_from(channels).ok().and_then(NonZeroU8::new),
+    ) else {
+        let operation = format!("Vorbis with {} channels at {} Hz", channels, sample_rate);
+        return Err(TtsError::not_supported(operation));
+    };
+    let quality = match quality.is_finite() {
+        true => quality.clamp(-0.2, 1.0),
+        false => 0.5,
+    };
+    let mut encoder = VorbisEncoderBuilder::new_with_serial(rate, layout, writer, SERIAL)
+        .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+            target_quality: quality,
+        })
+        .build()
+        .map_err(encode_error)?;
+
+    let channels = channels as usize;
+    let mut block = vec![Vec::with_capacity(BLOCK); channels];
+    for frames in samples.chunks(BLOCK * channels) {
+        for (channel, planar) in block.iter_mut().enumerate() {
+            planar.clear();
+            planar.extend(frames.iter().skip(channel).step_by(channels));
+        }
+        encoder.encode_audio_block(&block).map_err(encode_error)?;
+    }
+    encoder.finish().map_err(encode_error)?;
+    Ok(())
+}
+
+fn encode_error(error: vorbis_rs::VorbisError) -> TtsError {
+    TtsError::Encode(Box::new(error))
+}
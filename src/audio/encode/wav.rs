@@ -0,0 +1,79 @@
+//! The WAV writer every engine and the cache save through.
+use crate::{models::SynthesizedAudio, TtsError};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    path::Path,
+};
+
+/// How samples are stored in a WAV file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WavFormat {
+    Int16,
+    Int24,
+    Int32,
+    Float32,
+}
+
+impl WavFormat {
+    pub fn bits_per_sample(&self) -> u16 {
+        match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Int32 | Self::Float32 => 32,
+        }
+    }
+
+    pub fn sample_format(&self) -> SampleFormat {
+        match self {
+            Self::Float32 => SampleFormat::Float,
+            _ => SampleFormat::Int,
+        }
+    }
+}
+
+/// Writes `audio` to `writer` in `format`, or in the sample format of its
+/// spec when `None`. Any number of channels is written as it is interleaved.
+pub fn write<W: Write + Seek>(
+    audio: &SynthesizedAudio<f32>,
+    format: Option<WavFormat>,
+    writer: W,
+) -> Result<(), TtsError> {
+    let mut spec = audio.spec.layout()?;
+    if let Some(format) = format {
+        spec = WavSpec {
+            bits_per_sample: format.bits_per_sample(),
+            sample_format: format.sample_format(),
+            ..spec
+        };
+    }
+
+    let mut writer = WavWriter::new(writer, spec)?;
+    match spec.sample_format {
+        SampleFormat::Float => {
+            for sample in &audio.data {
+                writer.write_sample(*sample)?;
+            }
+        }
+        SampleFormat::Int => {
+            let max = ((1i64 << (spec.bits_per_sample - 1)) - 1) as f64;
+            for sample in &audio.data {
+                let sample = (sample.clamp(-1.0, 1.0) as f64 * max).round();
+                writer.write_sample(sample as i32)?;
+            }
+        }
+    }
+    writer.finalize()?;
+
+    Ok(())
+}
+
+/// Writes `audio` to the file at `path`, see [`write`].
+pub fn save(
+    audio: &SynthesizedAudio<f32>,
+    format: Option<WavFormat>,
+    path: &Path,
+) -> Result<(), TtsError> {
+    write(audio, format, BufWriter::new(File::create(path)?))
+}
@@ -0,0 +1,360 @@
+//! Loudness normalization to a target in LUFS, measured as BS.1770 integrated
+//! loudness like EBU R128 does, with a true-peak limiter so the gain can't clip.
+use super::{
+    apply_gain, bs1770, db_to_gain,
+    resample::{self, Resampler},
+};
+use crate::{models::SynthesizedAudio, TtsError};
+use std::collections::VecDeque;
+
+/// How far ahead the limiter looks, so it turns the gain down before a peak.
+const LOOKAHEAD: f32 = 0.005;
+/// How long the limiter takes to recover most of the gain after a peak.
+const RELEASE: f32 = 0.05;
+/// True peaks are estimated at this multiple of the sample rate, as BS.1770 recommends.
+const OVERSAMPLING: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness to reach, in LUFS.
+    pub target: f32,
+    /// The highest true peak allowed, in dBTP.
+    pub true_peak: f32,
+}
+
+impl Default for Loudness {
+    /// -16 LUFS, the usual target for speech on the web and in podcasts.
+    fn default() -> Self {
+        return Self {
+            target: -16.0,
+            true_peak: -1.0,
+        };
+    }
+}
+
+impl Loudness {
+    pub fn new(target: f32, true_peak: f32) -> Self {
+        return Self { target, true_peak };
+    }
+
+    /// EBU R128 broadcast loudness, -23 LUFS.
+    pub fn ebu_r128() -> Self {
+        return Self::new(-23.0, -1.0);
+    }
+
+    /// Brings interleaved `samples` to the target, then limits their true peaks.
+    /// Audio too quiet to measure only goes through the limiter.
+    pub fn normalize(&self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        if let Some(loudness) = integrated(samples, channels, sample_rate) {
+            apply_gain(samples, self.target - loudness);
+        }
+        limit(samples, channels, sample_rate, self.true_peak);
+    }
+
+    pub fn apply(&self, audio: &mut SynthesizedAudio<f32>) -> Result<(), TtsError> {
+        let spec = audio.spec.layout()?;
+        self.normalize(&mut audio.data, spec.channels, spec.sample_rate);
+        Ok(())
+    }
+
+    /// Normalization for audio that arrives chunk by chunk, see [`LoudnessStream`].
+    pub fn stream(&self, channels: u16, sample_rate: u32) -> LoudnessStream {
+        return LoudnessStream {
+            target: self.target,
+            channels: channels.max(1) as usize,
+            meters: (0..channels.max(1))
+                .map(|_| bs1770::ChannelLoudnessMeter::new(sample_rate))
+                .collect(),
+            gain: None,
+            limiter: Limiter::new(channels, sample_rate, self.true_peak),
+        };
+    }
+}
+
+/// The integrated loudness of interleaved `samples` in LUFS, with every
+/// channel weighted equally. `None` for silence, anything under -70 LUFS.
+///
+/// Audio shorter than the 400ms gating blocks of BS.1770 is measured over
+/// its 100ms windows instead, so short replies still get normalized.
+pub fn integrated(samples: &[f32], channels: u16, sample_rate: u32) -> Option<f32> {
+    let channels = channels.max(1) as usize;
+    let mut power: Option<bs1770::Windows100ms<Vec<bs1770::Power>>> = None;
+    for channel in 0..channels {
+        let mut meter = bs1770::ChannelLoudnessMeter::new(sample_rate);
+        meter.push(samples.iter().skip(channel).step_by(channels).copied());
+        let windows = meter.into_100ms_windows();
+        power = Some(match power {
+            Some(sum) => bs1770::reduce_stereo(sum.as_ref(), windows.as_ref()),
+            None => windows,
+        });
+    }
+
+    integrated_windows(power?.as_ref())
+}
+
+/// Like [`integrated`], from the 100ms windows of a meter that may still be
+/// running, as when audio is measured chunk by chunk.
+pub fn integrated_windows(power: bs1770::Windows100ms<&[bs1770::Power]>) -> Option<f32> {
+    if power.len() >= 4 {
+        return bs1770::gated_mean(power).map(|x| x.loudness_lkfs());
+    }
+    let windows = power.inner;
+    if windows.is_empty() {
+        return None;
+    }
+    let mean = windows.iter().map(|x| x.0).sum::<f32>() / windows.len() as f32;
+    let loudness = bs1770::Power(mean).loudness_lkfs();
+    (loudness > -70.0).then_some(loudness)
+}
+
+/// The true peak of interleaved `samples` in dBTP.
+pub fn true_peak(samples: &[f32], channels: u16, sample_rate: u32) -> f32 {
+    let peak = frame_peaks(samples, channels, sample_rate)
+        .into_iter()
+        .fold(0.0, f32::max);
+    super::gain_to_db(peak)
+}
+
+/// Keeps the true peaks of interleaved `samples` under `ceiling` dBTP. The
+/// gain comes down over a short lookahead before each peak, rather than
+/// clipping it, and recovers smoothly afterwards.
+pub fn limit(samples: &mut [f32], channels: u16, sample_rate: u32, ceiling: f32) {
+    let mut limiter = Limiter::new(channels, sample_rate, ceiling);
+    let mut limited = limiter.push(samples);
+    limited.extend(limiter.finish());
+    samples[..limited.len()].copy_from_slice(&limited);
+}
+
+/// [`limit`] for audio that arrives chunk by chunk. Each frame is held back
+/// until the peaks its gain depends on have come in, about twice the
+/// lookahead, and the gain carries over from one chunk to the next, so the
+/// start of a chunk is limited like the rest of it.
+pub struct Limiter {
+    channels: usize,
+    ceiling: f32,
+    lookahead: usize,
+    release: f32,
+    oversampler: Resampler,
+    /// The oversampled audio of the frames not yet measured.
+    oversampled: VecDeque<f32>,
+    /// Frames not yet put out, interleaved.
+    pending: VecDeque<f32>,
+    /// Frames measured and frames put out so far.
+    measured: usize,
+    limited: usize,
+    /// The peak of the last frame measured on its own.
+    last_peak: f32,
+    /// How many gains were needed so far, after `lookahead - 1` of unity gain
+    /// so the first frames can be ramped into too, and the increasing ones
+    /// that may yet be the lowest of a window of `lookahead`.
+    needed: usize,
+    candidates: VecDeque<(usize, f32)>,
+    /// The lowest needed gain of each window from frame `limited` on, how many
+    /// there were so far, and the sum of all but the last of a lookahead.
+    held: VecDeque<f32>,
+    holds: usize,
+    sum: f32,
+    gain: f32,
+}
+
+impl Limiter {
+    pub fn new(channels: u16, sample_rate: u32, ceiling: f32) -> Self {
+        let lookahead = ((sample_rate as f32 * LOOKAHEAD) as usize).max(1);
+        let mut limiter = Self {
+            channels: channels.max(1) as usize,
+            ceiling: db_to_gain(ceiling),
+            lookahead,
+            release: 1.0 - (-1.0 / (RELEASE * sample_rate as f32)).exp(),
+            oversampler: Resampler::new(channels, sample_rate, sample_rate * OVERSAMPLING),
+            oversampled: VecDeque::new(),
+            pending: VecDeque::new(),
+            measured: 0,
+            limited: 0,
+            last_peak: 0.0,
+            needed: 0,
+            candidates: VecDeque::new(),
+            held: VecDeque::new(),
+            holds: 0,
+            sum: 0.0,
+            gain: 1.0,
+        };
+        for _ in 1..lookahead {
+            limiter.need(1.0);
+        }
+        return limiter;
+    }
+
+    /// Takes interleaved `samples` and returns the frames whose gain is known.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.pending.extend(samples);
+        let oversampled = self.oversampler.push(samples);
+        self.oversampled.extend(oversampled);
+        self.measure(false);
+        self.drain()
+    }
+
+    /// Returns the frames held back, now that the stream has ended.
+    pub fn finish(&mut self) -> Vec<f32> {
+        let oversampled = self.oversampler.finish();
+        self.oversampled.extend(oversampled);
+        self.measure(true);
+        // The last windows are cut short by the end.
+        while self.holds < self.needed {
+            let first = self.holds;
+            while self.candidates.front().is_some_and(|x| x.0 < first) {
+                self.candidates.pop_front();
+            }
+            let lowest = self.candidates.front().map_or(1.0, |x| x.1);
+            self.hold(lowest);
+        }
+        self.drain()
+    }
+
+    /// Takes the peak of every frame whose oversampled audio is in. A peak
+    /// between two frames is reached from either side.
+    fn measure(&mut self, last: bool) {
+        let channels = self.channels;
+        let step = OVERSAMPLING as usize * channels;
+        let frames = self.limited + self.pending.len() / channels;
+        while self.measured < frames && (last || self.oversampled.len() >= step) {
+            let start = (self.measured - self.limited) * channels;
+            let own = self.pending.range(start..start + channels).copied();
+            let between = step.min(self.oversampled.len());
+            let peak = own
+                .chain(self.oversampled.drain(..between))
+                .fold(0f32, |peak, x| peak.max(x.abs()));
+            let reached = peak.max(self.last_peak);
+            self.last_peak = peak;
+            self.measured += 1;
+            self.need((self.ceiling / reached).min(1.0));
+        }
+    }
+
+    /// Adds the gain the next frame needs, holding the lowest of the window
+    /// of `lookahead` it completes.
+    fn need(&mut self, gain: f32) {
+        let index = self.needed;
+        self.needed += 1;
+        while self.candidates.back().is_some_and(|x| x.1 >= gain) {
+            self.candidates.pop_back();
+        }
+        self.candidates.push_back((index, gain));
+        if let Some(first) = (index + 1).checked_sub(self.lookahead) {
+            while self.candidates.front().is_some_and(|x| x.0 < first) {
+                self.candidates.pop_front();
+            }
+            self.hold(self.candidates[0].1);
+        }
+    }
+
+    fn hold(&mut self, gain: f32) {
+        if self.holds < self.lookahead - 1 {
+            self.sum += gain;
+        }
+        self.holds += 1;
+        self.held.push_back(gain);
+    }
+
+    /// Puts out every frame whose lookahead of held gains is complete.
+    /// Averaging the held gains over the lookahead gives a ramp that reaches
+    /// the gain a peak needs by the time it plays.
+    fn drain(&mut self) -> Vec<f32> {
+        let channels = self.channels;
+        let mut out = Vec::new();
+        while self.held.len() >= self.lookahead && self.limited < self.measured {
+            self.sum += self.held[self.lookahead - 1];
+            let ramp = self.sum / self.lookahead as f32;
+            self.sum -= self.held.pop_front().unwrap_or(1.0);
+
+            self.gain = (self.gain + (1.0 - self.gain) * self.release).min(ramp);
+            let gain = self.gain;
+            out.extend(self.pending.drain(..channels).map(|x| x * gain));
+            self.limited += 1;
+        }
+        out
+    }
+}
+
+/// [`Loudness`] for audio that arrives chunk by chunk. The gain follows the
+/// integrated loudness of everything so far, ramping over each chunk to the
+/// latest estimate so there are no steps between chunks, and the limiter
+/// carries over from one chunk to the next. The estimate settles as more
+/// comes in, so the first chunks can be a few dB off what the whole would get.
+pub struct LoudnessStream {
+    target: f32,
+    channels: usize,
+    meters: Vec<bs1770::ChannelLoudnessMeter>,
+    /// The gain at the end of the last chunk, in dB.
+    gain: Option<f32>,
+    limiter: Limiter,
+}
+
+impl LoudnessStream {
+    /// Takes interleaved `samples` and returns what is ready of the output.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<f32> {
+        let channels = self.channels;
+        for (channel, meter) in self.meters.iter_mut().enumerate() {
+            meter.push(samples.iter().skip(channel).step_by(channels).copied());
+        }
+        let mut windows = self.meters.iter().map(|x| x.as_100ms_windows());
+        let loudness = match windows.next() {
+            Some(first) => {
+                let sum = windows.fold(first.inner.to_vec(), |sum, x| {
+                    let sum = bs1770::Windows100ms { inner: &sum[..] };
+                    bs1770::reduce_stereo(sum, x).inner
+                });
+                integrated_windows(bs1770::Windows100ms { inner: &sum[..] })
+            }
+            None => None,
+        };
+
+        let previous = self.gain;
+        let gain = match loudness {
+            Some(x) => self.target - x,
+            None => previous.unwrap_or(0.0),
+        };
+        let previous = previous.unwrap_or(gain);
+        self.gain = Some(gain);
+
+        let mut samples = samples.to_vec();
+        let frames = (samples.len() / channels).max(1) as f32;
+        for (frame, samples) in samples.chunks_mut(channels).enumerate() {
+            let ramp = db_to_gain(previous + (gain - previous) * (frame + 1) as f32 / frames);
+            samples.iter_mut().for_each(|x| *x *= ramp);
+        }
+        self.limiter.push(&samples)
+    }
+
+    /// Returns what the limiter held back, now that the stream has ended.
+    pub fn finish(&mut self) -> Vec<f32> {
+        self.limiter.finish()
+    }
+}
+
+/// The highest absolute value, across channels, between each frame and the
+/// next one, estimated by oversampling.
+fn frame_peaks(samples: &[f32], channels: u16, sample_rate: u32) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let oversampled = resample::sinc(
+        samples,
+        channels as u16,
+        sample_rate,
+        sample_rate * OVERSAMPLING,
+    );
+
+    let step = OVERSAMPLING as usize * channels;
+    let mut peaks: Vec<f32> = (0..frames)
+        .map(|frame| {
+            let start = frame * channels;
+            let own = samples[start..start + channels].iter();
+            let between = oversampled.iter().skip(frame * step).take(step);
+            own.chain(between).fold(0f32, |peak, x| peak.max(x.abs()))
+        })
+        .collect();
+    // A peak between two frames is reached from either side.
+    for frame in (1..peaks.len()).rev() {
+        peaks[frame] = peaks[frame].max(peaks[frame - 1]);
+    }
+    peaks
+}
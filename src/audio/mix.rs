@@ -0,0 +1,251 @@
+//! Mixes tracks of speech, music and sound effects at offsets into one
+//! buffer, ducking the music under the speech.
+use super::{
+    apply_gain,
+    concat::remix,
+    db_to_gain,
+    encode::{self, OutputFormat},
+    loudness::Loudness,
+    resample,
+};
+use crate::{
+    models::{Spec, SynthesizedAudio},
+    TtsError,
+};
+use hound::{SampleFormat, WavSpec};
+use std::{path::Path, time::Duration};
+
+/// How fast the level of the speech falls after a peak, in seconds. Short
+/// enough that ducking lets go soon after the speech stops, long enough to
+/// bridge the periods of a low voice.
+const ENVELOPE_RELEASE: f32 = 0.02;
+
+/// What a track is for, which decides how it takes part in ducking.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// Drives the ducking of background tracks.
+    Speech,
+    /// Turned down while any speech plays, such as a music bed.
+    Background,
+    /// Neither ducks nor is ducked.
+    Effect,
+}
+
+/// One source in a [`Mixer`].
+pub struct Track {
+    pub audio: SynthesizedAudio<f32>,
+    pub role: Role,
+    /// From the start of the mix.
+    pub offset: Duration,
+    /// In dB.
+    pub gain: f32,
+}
+
+impl Track {
+    /// A track starting with the mix, at its own level.
+    pub fn new(audio: SynthesizedAudio<f32>, role: Role) -> Self {
+        return Self {
+            audio,
+            role,
+            offset: Duration::ZERO,
+            gain: 0.0,
+        };
+    }
+
+    pub fn at(mut self, offset: Duration) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn gain(mut self, gain: f32) -> Self {
+        self.gain = gain;
+        self
+    }
+}
+
+/// How far and how fast background tracks are turned down under speech.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ducking {
+    /// Speech louder than this, in dBFS, ducks the background.
+    pub threshold: f32,
+    /// The gain of ducked background tracks, in dB.
+    pub depth: f32,
+    /// How long the background takes to go down once speech starts.
+    pub attack: Duration,
+    /// How long it takes to come back up once speech stops.
+    pub release: Duration,
+}
+
+impl Default for Ducking {
+    fn default() -> Self {
+        return Self {
+            threshold: -40.0,
+            depth: -12.0,
+            attack: Duration::from_millis(50),
+            release: Duration::from_millis(400),
+        };
+    }
+}
+
+impl Ducking {
+    /// The gain of background tracks at each frame, following the `speech`
+    /// level of each frame.
+    fn gains(&self, speech: &[f32], sample_rate: u32) -> Vec<f32> {
+        let rate = sample_rate as f32;
+        let smoothing = |x: Duration| 1.0 - (-1.0 / (x.as_secs_f32() * rate).max(1.0)).exp();
+        let (attack, release) = (smoothing(self.attack), smoothing(self.release));
+        let decay = smoothing(Duration::from_secs_f32(ENVELOPE_RELEASE));
+        let threshold = db_to_gain(self.threshold);
+        let floor = db_to_gain(self.depth);
+
+        let mut envelope = 0f32;
+        let mut gain = 1f32;
+        speech
+            .iter()
+            .map(|level| {
+                // Peaks are followed at once, and the slow release of the
+                // gain keeps short pauses from pumping the background.
+                envelope = level.max(envelope + (level - envelope) * decay);
+                let target = if envelope > threshold { floor } else { 1.0 };
+                let speed = if target < gain { attack } else { release };
+                gain += (target - gain) * speed;
+                gain
+            })
+            .collect()
+    }
+}
+
+/// Tracks mixed down to one buffer.
+#[derive(Default)]
+pub struct Mixer {
+    pub tracks: Vec<Track>,
+    /// `None` leaves background tracks at their own gain under speech.
+    pub ducking: Option<Ducking>,
+    /// Normalizes the mix, see [`Loudness`]. Without it, tracks that overlap
+    /// can add up past full scale.
+    pub master: Option<Loudness>,
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    pub fn track(mut self, track: Track) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    pub fn ducking(mut self, ducking: Ducking) -> Self {
+        self.ducking = Some(ducking);
+        self
+    }
+
+    pub fn master(mut self, master: Loudness) -> Self {
+        self.master = Some(master);
+        self
+    }
+
+    /// Mixes every track down, at the highest sample rate and with the most
+    /// channels of any track, in 32-bit float. The mix lasts until the last
+    /// track ends.
+    pub fn render(&self) -> Result<SynthesizedAudio<f32>, TtsError> {
+        let specs = self
+            .tracks
+            .iter()
+            .map(|x| x.audio.spec.layout())
+            .collect::<Result<Vec<_>, _>>()?;
+        if specs.is_empty() {
+            return Err(TtsError::not_supported("mixing no tracks"));
+        }
+        let spec = WavSpec {
+            sample_rate: specs.iter().map(|x| x.sample_rate).max().unwrap_or(0),
+            channels: specs.iter().map(|x| x.channels.max(1)).max().unwrap_or(1),
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let channels = spec.channels as usize;
+
+        // Every track at the mix's layout, as (role, first frame, samples).
+        let tracks: Vec<(Role, usize, Vec<f32>)> = self
+            .tracks
+            .iter()
+            .zip(specs)
+            .map(|(track, track_spec)| {
+                let samples = resample::sinc(
+                    &track.audio.data,
+                    track_spec.channels,
+                    track_spec.sample_rate,
+                    spec.sample_rate,
+                );
+                let mut samples = remix(&samples, track_spec.channels, spec.channels);
+                apply_gain(&mut samples, track.gain);
+                let start = (track.offset.as_secs_f64() * spec.sample_rate as f64).round();
+                (track.role, start as usize, samples)
+            })
+            .collect();
+        let frames = tracks
+            .iter()
+            .map(|(_, start, samples)| start + samples.len() / channels)
+            .max()
+            .unwrap_or(0);
+
+        let mut mix = vec![0.0; frames * channels];
+        for (role, start, samples) in &tracks {
+            if *role != Role::Background {
+                mix_into(&mut mix[start * channels..], samples, channels, None);
+            }
+        }
+
+        let ducked = match self.ducking {
+            Some(ducking) if tracks.iter().any(|x| x.0 == Role::Background) => {
+                // The speech alone, with the loudest channel of each frame.
+                let mut speech = vec![0f32; frames];
+                for (_, start, samples) in tracks.iter().filter(|x| x.0 == Role::Speech) {
+                    let frames = samples.chunks_exact(channels);
+                    for (level, frame) in speech[*start..].iter_mut().zip(frames) {
+                        *level += frame.iter().fold(0f32, |a, b| a.max(b.abs()));
+                    }
+                }
+                Some(ducking.gains(&speech, spec.sample_rate))
+            }
+            _ => None,
+        };
+        for (role, start, samples) in &tracks {
+            if *role == Role::Background {
+                let gains = ducked.as_ref().map(|x| &x[*start..]);
+                mix_into(&mut mix[start * channels..], samples, channels, gains);
+            }
+        }
+
+        if let Some(master) = self.master {
+            master.normalize(&mut mix, spec.channels, spec.sample_rate);
+        }
+        Ok(SynthesizedAudio::new(
+            mix,
+            Spec::Wav(spec),
+            Some(frames as i32),
+        ))
+    }
+
+    /// Renders the mix and saves it in the format of `path`'s extension, WAV
+    /// when it has none.
+    pub fn save(&self, path: &Path) -> Result<(), TtsError> {
+        let format = OutputFormat::from_path(path).unwrap_or_default();
+        encode::save(&self.render()?, format, path)
+    }
+}
+
+/// Adds interleaved `samples` to the start of `mix`, scaling each frame by
+/// its gain in `gains` when given.
+fn mix_into(mix: &mut [f32], samples: &[f32], channels: usize, gains: Option<&[f32]>) {
+    let frames = mix
+        .chunks_exact_mut(channels)
+        .zip(samples.chunks_exact(channels));
+    for (frame, (out, samples)) in frames.enumerate() {
+        let gain = gains.map_or(1.0, |x| x[frame]);
+        for (out, sample) in out.iter_mut().zip(samples) {
+            *out += sample * gain;
+        }
+    }
+}
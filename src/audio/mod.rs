@@ -0,0 +1,29 @@
+//! DSP on synthesized PCM, for whatever an engine can't do itself.
+//!
+//! Samples are `f32` in `[-1, 1]`, interleaved when there is more than one channel.
+pub mod bs1770;
+pub mod concat;
+pub mod effects;
+pub mod encode;
+pub mod loudness;
+pub mod mix;
+pub mod resample;
+pub mod stretch;
+
+/// Converts a gain in dB to a linear factor.
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Converts a linear factor to a gain in dB.
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.log10()
+}
+
+/// Scales every sample by `db`.
+pub fn apply_gain(samples: &mut [f32], db: f32) {
+    let gain = db_to_gain(db);
+    for sample in samples {
+        *sample *= gain;
+    }
+}
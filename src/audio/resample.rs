@@ -0,0 +1,208 @@
+/// Resamples interleaved audio by `ratio` (output rate over input rate) with
+/// linear interpolation.
+///
+/// This is cheap but aliases, so it is only good enough for previews and
+/// other audio nobody keeps; [`sinc`] is the one to save.
+pub fn linear(samples: &[f32], channels: u16, ratio: f64) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    if frames == 0 || ratio <= 0.0 || (ratio - 1.0).abs() < 1e-9 {
+        return samples.to_vec();
+    }
+
+    let out_frames = (frames as f64 * ratio).round() as usize;
+    let mut out = Vec::with_capacity(out_frames * channels);
+    for frame in 0..out_frames {
+        let position = frame as f64 / ratio;
+        let index = (position.floor() as usize).min(frames - 1);
+        let next = (index + 1).min(frames - 1);
+        let fraction = (position - index as f64) as f32;
+        for channel in 0..channels {
+            let a = samples[index * channels + channel];
+            let b = samples[next * channels + channel];
+            out.push(a + (b - a) * fraction);
+        }
+    }
+
+    out
+}
+
+/// Zero crossings of the sinc on each side of the filter.
+const ZERO_CROSSINGS: usize = 32;
+/// Kernel values per zero crossing in the lookup table.
+const TABLE_RESOLUTION: usize = 128;
+/// Where the pass band ends, relative to the lower Nyquist frequency. The
+/// rest of the band is left for the filter to roll off in.
+const PASS_BAND: f64 = 0.95;
+/// Kaiser window shape, trading transition width for about 90dB of stop band.
+const KAISER_BETA: f64 = 9.0;
+
+/// Resamples interleaved audio from `from` to `to` Hz with a Kaiser-windowed
+/// sinc filter. Unlike [`linear`], it keeps the pass band flat and filters out
+/// what would alias when lowering the rate, so it is the one to use between
+/// engines and before saving.
+pub fn sinc(samples: &[f32], channels: u16, from: u32, to: u32) -> Vec<f32> {
+    let mut resampler = Resampler::new(channels, from, to);
+    let mut out = resampler.push(samples);
+    out.extend(resampler.finish());
+    out
+}
+
+/// [`sinc`] for audio that arrives chunk by chunk. It keeps the input the
+/// filter still reaches back to and holds back the output that needs input
+/// not yet pushed, so the chunks join up as if resampled all at once.
+pub struct Resampler {
+    channels: usize,
+    /// `None` when the rate doesn't change.
+    filter: Option<Filter>,
+    /// Input from frame `offset` on, interleaved.
+    input: Vec<f32>,
+    offset: usize,
+    /// Input frames pushed and output frames produced so far.
+    received: usize,
+    produced: usize,
+}
+
+struct Filter {
+    ratio: f64,
+    cutoff: f64,
+    reach: isize,
+    table: Vec<f64>,
+}
+
+impl Filter {
+    fn kernel(&self, distance: f64) -> f64 {
+        let position = (distance * self.cutoff).abs() * TABLE_RESOLUTION as f64;
+        let index = position as usize;
+        match self.table.get(index + 1) {
+            Some(next) => {
+                let fraction = position - index as f64;
+                self.table[index] + (next - self.table[index]) * fraction
+            }
+            None => 0.0,
+        }
+    }
+
+    /// The input frame each output frame is centered on.
+    fn center(&self, frame: usize) -> isize {
+        (frame as f64 / self.ratio).floor() as isize
+    }
+}
+
+impl Resampler {
+    pub fn new(channels: u16, from: u32, to: u32) -> Self {
+        let filter = (from != 0 && to != 0 && from != to).then(|| {
+            let ratio = to as f64 / from as f64;
+            // In cycles per input sample, halved, so 1.0 is the input's Nyquist frequency.
+            let cutoff = ratio.min(1.0) * PASS_BAND;
+            Filter {
+                ratio,
+                cutoff,
+                reach: (ZERO_CROSSINGS as f64 / cutoff).ceil() as isize,
+                table: kernel_table(),
+            }
+        });
+        return Self {
+            channels: channels.max(1) as usize,
+            filter,
+            input: Vec::new(),
+            offset: 0,
+            received: 0,
+            produced: 0,
+        };
+    }
+
+    /// Takes interleaved `samples` and returns the output they complete.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<f32> {
+        if self.filter.is_none() {
+            return samples.to_vec();
+        }
+        self.input.extend_from_slice(samples);
+        self.received += samples.len() / self.channels;
+        self.drain(false)
+    }
+
+    /// Returns the output held back for input that never came, which is
+    /// taken as silence.
+    pub fn finish(&mut self) -> Vec<f32> {
+        match self.filter.is_some() {
+            true => self.drain(true),
+            false => Vec::new(),
+        }
+    }
+
+    fn drain(&mut self, last: bool) -> Vec<f32> {
+        let Some(filter) = &self.filter else {
+            return Vec::new();
+        };
+        let channels = self.channels;
+        let frames = match last {
+            true => (self.received as f64 * filter.ratio).round() as usize,
+            false => usize::MAX,
+        };
+
+        let mut out = Vec::new();
+        let mut sums = vec![0.0f64; channels];
+        while self.produced < frames {
+            let center = filter.center(self.produced);
+            if !last && center + filter.reach >= self.received as isize {
+                break;
+            }
+            let position = self.produced as f64 / filter.ratio;
+            let first = (center - filter.reach + 1).max(self.offset as isize);
+            let end = (center + filter.reach).min(self.received as isize - 1);
+
+            sums.fill(0.0);
+            for input in first..=end {
+                let weight = filter.kernel(position - input as f64);
+                let input = (input as usize - self.offset) * channels;
+                for (channel, sum) in sums.iter_mut().enumerate() {
+                    *sum += self.input[input + channel] as f64 * weight;
+                }
+            }
+            out.extend(sums.iter().map(|x| (x * filter.cutoff) as f32));
+            self.produced += 1;
+        }
+
+        // Input before what the next output frame reaches back to is done with.
+        let needed = (filter.center(self.produced) - filter.reach + 1).max(0) as usize;
+        let done = needed.min(self.received).saturating_sub(self.offset);
+        self.input.drain(..done * channels);
+        self.offset += done;
+        out
+    }
+}
+
+/// The windowed sinc from its center to its last zero crossing.
+fn kernel_table() -> Vec<f64> {
+    let length = ZERO_CROSSINGS * TABLE_RESOLUTION + 1;
+    (0..length)
+        .map(|i| {
+            let x = i as f64 / TABLE_RESOLUTION as f64;
+            let sinc = match i {
+                0 => 1.0,
+                _ => (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x),
+            };
+            let window = x / ZERO_CROSSINGS as f64;
+            let window = bessel_i0(KAISER_BETA * (1.0 - window * window).max(0.0).sqrt())
+                / bessel_i0(KAISER_BETA);
+            sinc * window
+        })
+        .collect()
+}
+
+/// The zeroth order modified Bessel function of the first kind, by its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..50 {
+        term *= half / k as f64;
+        let squared = term * term;
+        sum += squared;
+        if squared < sum * 1e-16 {
+            break;
+        }
+    }
+    sum
+}
@@ -0,0 +1,107 @@
+use super::resample;
+use std::f32::consts::PI;
+
+/// Length of each overlap-added frame.
+const FRAME_MS: usize = 40;
+
+/// Changes the tempo of interleaved audio by `speed` without changing its
+/// pitch, using WSOLA (waveform similarity overlap-add).
+///
+/// A `speed` of 2.0 halves the duration. Each frame is taken from near its
+/// nominal position, shifted to where it best lines up with the continuation
+/// of the previous frame, so periodic speech doesn't get phasey.
+pub fn wsola(samples: &[f32], channels: u16, sample_rate: u32, speed: f32) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    if frames == 0 || speed <= 0.0 || (speed - 1.0).abs() < 1e-3 {
+        return samples.to_vec();
+    }
+
+    let window_len = (sample_rate as usize * FRAME_MS / 1000 / 2 * 2).max(2);
+    let hop = window_len / 2;
+    let tolerance = hop / 2;
+    let analysis_hop = hop as f64 * speed as f64;
+    // Periodic Hann, which sums to one at 50% overlap.
+    let window: Vec<f32> = (0..window_len)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / window_len as f32).cos())
+        .collect();
+
+    let mono: Vec<f32> = samples
+        .chunks_exact(channels)
+        .map(|x| x.iter().sum::<f32>() / channels as f32)
+        .collect();
+    let at = |i: usize| mono.get(i).copied().unwrap_or(0.0);
+
+    let out_frames = (frames as f64 / speed as f64).round() as usize;
+    let mut out = vec![0.0; (out_frames + window_len) * channels];
+    let last_start = frames.saturating_sub(window_len);
+    let mut previous = 0;
+
+    for k in 0.. {
+        let out_pos = k * hop;
+        if out_pos >= out_frames {
+            break;
+        }
+
+        let nominal = (k as f64 * analysis_hop).round() as usize;
+        let position = if k == 0 {
+            0
+        } else {
+            let natural = previous + hop;
+            let low = nominal.saturating_sub(tolerance).min(last_start);
+            let high = (nominal + tolerance).min(last_start);
+            (low..=high)
+                .max_by(|a, b| {
+                    similarity(&at, *a, natural, hop).total_cmp(&similarity(&at, *b, natural, hop))
+                })
+                .unwrap_or(low)
+        };
+
+        for (i, w) in window.iter().enumerate() {
+            // The first frame has nothing to overlap with, so it isn't faded in.
+            let w = if k == 0 && i < hop { 1.0 } else { *w };
+            let src = position + i;
+            if src >= frames {
+                break;
+            }
+            for channel in 0..channels {
+                out[(out_pos + i) * channels + channel] += samples[src * channels + channel] * w;
+            }
+        }
+        previous = position;
+    }
+
+    out.truncate(out_frames * channels);
+    out
+}
+
+/// Normalized cross-correlation of `len` samples starting at `a` and `b`.
+fn similarity(at: &impl Fn(usize) -> f32, a: usize, b: usize, len: usize) -> f32 {
+    let mut dot = 0.0;
+    let mut energy = 0.0;
+    for i in 0..len {
+        let x = at(a + i);
+        dot += x * at(b + i);
+        energy += x * x;
+    }
+    dot / (energy.sqrt() + 1e-9)
+}
+
+/// Shifts the pitch of interleaved audio by `semitones` and changes its tempo
+/// by `speed` in one go: a WSOLA stretch followed by a resample.
+///
+/// The resample plays the stretched audio as if it were at `sample_rate`
+/// times the pitch ratio, rounded to the nearest Hz, which is well under a
+/// cent off.
+pub fn pitch_and_tempo(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    semitones: f32,
+    speed: f32,
+) -> Vec<f32> {
+    let ratio = 2f32.powf(semitones / 12.0);
+    let stretched = wsola(samples, channels, sample_rate, speed / ratio);
+    let from = (sample_rate as f32 * ratio).round() as u32;
+    resample::sinc(&stretched, channels, from, sample_rate)
+}
@@ -0,0 +1,286 @@
+//! Keeps synthesized audio around, so prompts that are spoken again and
+//! again are only synthesized once.
+//!
+//! Entries live in an in-memory LRU and, when a directory is given, on disk
+//! as a WAV next to a `.key` file holding the full key, so the store survives
+//! restarts. Only samples are kept: engine specific metadata such as
+//! MSEdge's word boundaries is dropped.
+use crate::{
+    audio::encode::wav,
+    models::{Spec, SynthesizedAudio},
+    Model, TtsError,
+};
+use derive_builder::Builder;
+use hound::{SampleFormat, WavReader, WavSpec};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Builder, Debug)]
+#[builder(setter(into))]
+pub struct Cache {
+    /// Most entries kept in memory.
+    #[builder(default = "128")]
+    pub max_entries: usize,
+    /// Most bytes of samples kept in memory.
+    #[builder(default = "64 * 1024 * 1024")]
+    pub max_memory: usize,
+    /// Where entries are stored on disk. `None` keeps them in memory only.
+    #[builder(default = "None")]
+    pub directory: Option<PathBuf>,
+    /// Most bytes of WAVs kept on disk.
+    #[builder(default = "512 * 1024 * 1024")]
+    pub max_disk: u64,
+
+    #[builder(setter(skip))]
+    entries: HashMap<String, Entry>,
+    /// Incremented on every access, to find the least recently used entry.
+    #[builder(setter(skip))]
+    clock: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    spec: WavSpec,
+    data: Vec<f32>,
+    duration: Option<i32>,
+    used: u64,
+}
+
+impl Entry {
+    fn audio(&self) -> SynthesizedAudio<f32> {
+        SynthesizedAudio::new(self.data.clone(), Spec::Wav(self.spec), self.duration)
+    }
+
+    fn size(&self) -> usize {
+        self.data.len() * size_of::<f32>()
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        return CacheBuilder::default().build().unwrap();
+    }
+}
+
+impl Cache {
+    /// The key of `text` spoken by the engine `engine` configured as
+    /// `configuration`, which should describe every setting that changes the audio.
+    pub fn key(engine: &Model, configuration: &str, text: &str) -> String {
+        format!("{}\n{}\n{}", engine, configuration, text)
+    }
+
+    /// Entries held in memory.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes of samples held in memory.
+    pub fn memory_size(&self) -> usize {
+        self.entries.values().map(Entry::size).sum()
+    }
+
+    /// Looks `key` up in memory, then on disk. A disk entry that can't be
+    /// read counts as a miss.
+    pub fn get(&mut self, key: &str) -> Option<SynthesizedAudio<f32>> {
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.used = self.clock;
+            return Some(entry.audio());
+        }
+
+        let entry = self.load(key).ok()??;
+        let audio = entry.audio();
+        self.remember(key.to_string(), entry);
+        Some(audio)
+    }
+
+    /// Stores `audio` under `key`. Audio without a known layout isn't cached.
+    pub fn insert(&mut self, key: String, audio: &SynthesizedAudio<f32>) -> Result<(), TtsError> {
+        let Some(spec) = audio.spec.wav_spec() else {
+            return Ok(());
+        };
+
+        if let Some((file, key_file)) = self.paths(&key) {
+            if let Some(directory) = file.parent() {
+                fs::create_dir_all(directory)?;
+            }
+            wav::save(audio, None, &file)?;
+            fs::write(key_file, &key)?;
+            self.prune_disk()?;
+        }
+
+        self.clock += 1;
+        let entry = Entry {
+            spec,
+            data: audio.data.clone(),
+            duration: Some(frames(&spec, &audio.data)),
+            used: self.clock,
+        };
+        self.remember(key, entry);
+        Ok(())
+    }
+
+    /// Removes `key` from memory and disk, returning whether it was cached.
+    pub fn remove(&mut self, key: &str) -> io::Result<bool> {
+        let mut found = self.entries.remove(key).is_some();
+        if let Some((wav, key_file)) = self.paths(key) {
+            if fs::read_to_string(&key_file).is_ok_and(|x| x == key) {
+                remove_pair(&wav, &key_file)?;
+                found = true;
+            }
+        }
+        Ok(found)
+    }
+
+    /// Removes every entry of the engine `engine`, as after changing its
+    /// weights in a way its configuration doesn't show.
+    pub fn invalidate_engine(&mut self, engine: &Model) -> io::Result<()> {
+        let prefix = format!("{}\n", engine);
+        self.entries.retain(|key, _| !key.starts_with(&prefix));
+        self.remove_from_disk(|key| key.starts_with(&prefix))
+    }
+
+    /// Removes every entry from memory and disk.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.entries.clear();
+        self.remove_from_disk(|_| true)
+    }
+
+    fn remember(&mut self, key: String, entry: Entry) {
+        self.entries.insert(key, entry);
+        while self.entries.len() > self.max_entries || self.memory_size() > self.max_memory {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, x)| x.used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => self.entries.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    /// The WAV and key file of `key`, named after its hash.
+    fn paths(&self, key: &str) -> Option<(PathBuf, PathBuf)> {
+        let directory = self.directory.as_ref()?;
+        let name = format!("{:016x}", fnv1a(key.as_bytes()));
+        Some((
+            directory.join(format!("{}.wav", name)),
+            directory.join(format!("{}.key", name)),
+        ))
+    }
+
+    fn load(&self, key: &str) -> Result<Option<Entry>, TtsError> {
+        let Some((wav, key_file)) = self.paths(key) else {
+            return Ok(None);
+        };
+        // Different keys may share a hash, so the key itself has to match.
+        if !key_file.exists() || fs::read_to_string(&key_file)? != key {
+            return Ok(None);
+        }
+
+        let mut reader = WavReader::open(&wav)?;
+        let spec = reader.spec();
+        let data = match spec.sample_format {
+            SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+            SampleFormat::Int => {
+                let max = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|x| x.map(|x| x as f32 / max))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+        // Refresh the modification time, which orders the disk store by use.
+        // Read-only stores are still served, they just stop tracking use.
+        let _ = fs::File::options()
+            .append(true)
+            .open(&wav)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+
+        Ok(Some(Entry {
+            spec,
+            duration: Some(frames(&spec, &data)),
+            data,
+            used: self.clock,
+        }))
+    }
+
+    /// Every WAV on disk with its key file, size and modification time.
+    fn disk_entries(&self) -> io::Result<Vec<(PathBuf, PathBuf, u64, SystemTime)>> {
+        let Some(directory) = &self.directory else {
+            return Ok(Vec::new());
+        };
+        if !directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for file in fs::read_dir(directory)? {
+            let path = file?.path();
+            if path.extension().is_some_and(|x| x == "wav") {
+                let metadata = fs::metadata(&path)?;
+                let key_file = path.with_extension("key");
+                entries.push((path, key_file, metadata.len(), metadata.modified()?));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Deletes the least recently used WAVs until the store fits [`Cache::max_disk`].
+    fn prune_disk(&self) -> io::Result<()> {
+        let mut entries = self.disk_entries()?;
+        entries.sort_by_key(|x| x.3);
+
+        let mut total: u64 = entries.iter().map(|x| x.2).sum();
+        for (wav, key_file, size, _) in entries {
+            if total <= self.max_disk {
+                break;
+            }
+            remove_pair(&wav, &key_file)?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    fn remove_from_disk(&self, matches: impl Fn(&str) -> bool) -> io::Result<()> {
+        for (wav, key_file, _, _) in self.disk_entries()? {
+            let key = fs::read_to_string(&key_file).unwrap_or_default();
+            if matches(&key) {
+                remove_pair(&wav, &key_file)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The duration of `data` in frames, which is what the disk store can restore.
+fn frames(spec: &WavSpec, data: &[f32]) -> i32 {
+    (data.len() / spec.channels.max(1) as usize) as i32
+}
+
+fn remove_pair(wav: &Path, key_file: &Path) -> io::Result<()> {
+    for path in [wav, key_file] {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
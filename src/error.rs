@@ -0,0 +1,305 @@
+use crate::Model;
+use std::{error::Error, io, path::PathBuf};
+use thiserror::Error as TError;
+
+/// An error from a dependency or an engine outside this crate, kept as the
+/// source of a [`TtsError`].
+pub type BoxedError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug, TError)]
+pub enum TtsError {
+    /// The engine can't do this at all, as opposed to failing at it.
+    #[error("{operation} is not supported{}", by(.engine))]
+    NotSupported {
+        engine: Option<String>,
+        operation: String,
+    },
+    #[error("Model Not Loaded")]
+    NotLoaded,
+    #[error("Engine not registered: {0}")]
+    NotRegistered(Model),
+    #[error("Didn't save {}", .0.display())]
+    NotSaved(PathBuf),
+    #[error("Default model not set")]
+    NoDefaultModel,
+    #[error("Unknown voice: {0}")]
+    UnknownVoice(String),
+    #[error("Invalid SSML: {0}")]
+    InvalidSsml(String),
+    #[error("Empty text")]
+    EmptyText,
+    /// `length` and `limit` are in whatever the engine counts, characters or tokens.
+    #[error("Text too long: {length} is over the limit of {limit}")]
+    TextTooLong { length: usize, limit: usize },
+    /// A request to a speech service failed on the way, as with a timeout, a
+    /// dropped connection or a 5xx. Worth retrying.
+    #[error("Network error")]
+    Network(#[source] BoxedError),
+    /// Model weights or configuration couldn't be fetched for the same
+    /// reasons as [`TtsError::Network`]. Worth retrying.
+    #[error("Model download failed")]
+    Download(#[source] BoxedError),
+    /// A request was refused or couldn't be made, as with a 404, an invalid
+    /// URL or a TLS failure. Retrying won't help.
+    #[error("Request failed")]
+    Request(#[source] BoxedError),
+    /// A service or model file returned something that couldn't be understood.
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+    #[error("Tensor error")]
+    Tensor(#[source] BoxedError),
+    #[error("Tokenizer error")]
+    Tokenizer(#[source] BoxedError),
+    #[error("Python error")]
+    Python(#[source] BoxedError),
+    /// The platform's own speech API failed.
+    #[error("Platform error")]
+    Platform(#[source] BoxedError),
+    #[error("Audio device error")]
+    AudioDevice(#[source] BoxedError),
+    #[error("Audio decoding error")]
+    Decode(#[source] BoxedError),
+    #[error("Audio encoding error")]
+    Encode(#[source] BoxedError),
+    #[error("I/O error")]
+    Io(#[from] io::Error),
+    /// Anything else, as reported by engines outside this crate.
+    #[error("Engine error")]
+    Engine(#[source] BoxedError),
+}
+
+fn by(engine: &Option<String>) -> String {
+    match engine {
+        Some(engine) => format!(" by {}", engine),
+        None => String::new(),
+    }
+}
+
+/// Server errors, rate limits and request timeouts, which may pass.
+#[allow(dead_code)]
+fn is_transient_status(status: u16) -> bool {
+    status >= 500 || status == 429 || status == 408
+}
+
+#[allow(dead_code)]
+fn is_transient_io(error: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    matches!(
+        error.kind(),
+        TimedOut
+            | ConnectionRefused
+            | ConnectionReset
+            | ConnectionAborted
+            | NotConnected
+            | BrokenPipe
+            | UnexpectedEof
+            | Interrupted
+            | WouldBlock
+    )
+}
+
+/// Whether an I/O error somewhere in the chain of `error` may pass.
+#[allow(dead_code)]
+fn has_transient_io(error: &(dyn Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if error
+            .downcast_ref::<io::Error>()
+            .is_some_and(is_transient_io)
+        {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+impl TtsError {
+    pub fn not_supported(operation: impl Into<String>) -> Self {
+        TtsError::NotSupported {
+            engine: None,
+            operation: operation.into(),
+        }
+    }
+
+    /// Wraps an error from an engine outside this crate.
+    pub fn engine(error: impl Into<BoxedError>) -> Self {
+        TtsError::Engine(error.into())
+    }
+
+    /// A failed request to a speech service, [`TtsError::Network`] when
+    /// `transient` and [`TtsError::Request`] otherwise.
+    pub(crate) fn request(error: impl Into<BoxedError>, transient: bool) -> Self {
+        match transient {
+            true => TtsError::Network(error.into()),
+            false => TtsError::Request(error.into()),
+        }
+    }
+
+    /// A request answered with the HTTP status `status`.
+    #[allow(dead_code)]
+    pub(crate) fn http(status: u16, reason: &str) -> Self {
+        let message = format!("HTTP {} {}", status, reason);
+        TtsError::request(message, is_transient_status(status))
+    }
+
+    /// Whether the same call may succeed when retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, TtsError::Network(_) | TtsError::Download(_))
+    }
+
+    /// Names `engine` in a [`TtsError::NotSupported`] that doesn't say which engine it came from.
+    pub(crate) fn with_engine(self, engine: &Model) -> Self {
+        match self {
+            TtsError::NotSupported {
+                engine: None,
+                operation,
+            } => TtsError::NotSupported {
+                engine: Some(engine.to_string()),
+                operation,
+            },
+            other => other,
+        }
+    }
+}
+
+impl From<hound::Error> for TtsError {
+    fn from(error: hound::Error) -> Self {
+        match error {
+            hound::Error::IoError(e) => TtsError::Io(e),
+            e => TtsError::Decode(Box::new(e)),
+        }
+    }
+}
+
+impl From<rodio::decoder::DecoderError> for TtsError {
+    fn from(error: rodio::decoder::DecoderError) -> Self {
+        TtsError::Decode(Box::new(error))
+    }
+}
+
+impl From<rodio::StreamError> for TtsError {
+    fn from(error: rodio::StreamError) -> Self {
+        TtsError::AudioDevice(Box::new(error))
+    }
+}
+
+impl From<rodio::PlayError> for TtsError {
+    fn from(error: rodio::PlayError) -> Self {
+        TtsError::AudioDevice(Box::new(error))
+    }
+}
+
+#[cfg(feature = "opus")]
+impl From<audiopus::Error> for TtsError {
+    fn from(error: audiopus::Error) -> Self {
+        TtsError::Encode(Box::new(error))
+    }
+}
+
+#[cfg(feature = "meta")]
+impl From<candle_core::Error> for TtsError {
+    fn from(error: candle_core::Error) -> Self {
+        TtsError::Tensor(Box::new(error))
+    }
+}
+
+#[cfg(feature = "meta")]
+impl From<hf_hub::api::sync::ApiError> for TtsError {
+    fn from(error: hf_hub::api::sync::ApiError) -> Self {
+        use hf_hub::api::sync::ApiError;
+        let transient = match error {
+            ApiError::IoError(e) => return TtsError::Io(e),
+            ApiError::RequestError(ref e) => match e.as_ref() {
+                ureq::Error::Status(status, _) => is_transient_status(*status),
+                ureq::Error::Transport(e) => matches!(
+                    e.kind(),
+                    ureq::ErrorKind::Dns
+                        | ureq::ErrorKind::ConnectionFailed
+                        | ureq::ErrorKind::Io
+                        | ureq::ErrorKind::ProxyConnect
+                ),
+            },
+            // Another process is downloading the same file.
+            ApiError::TooManyRetries(_) | ApiError::LockAcquisition(_) => true,
+            _ => false,
+        };
+        match transient {
+            true => TtsError::Download(Box::new(error)),
+            false => TtsError::Request(Box::new(error)),
+        }
+    }
+}
+
+#[cfg(feature = "meta")]
+impl From<serde_json::Error> for TtsError {
+    fn from(error: serde_json::Error) -> Self {
+        TtsError::InvalidData(error.to_string())
+    }
+}
+
+#[cfg(feature = "coqui")]
+impl From<pyo3::PyErr> for TtsError {
+    fn from(error: pyo3::PyErr) -> Self {
+        TtsError::Python(Box::new(error))
+    }
+}
+
+#[cfg(feature = "gtts")]
+impl From<minreq::Error> for TtsError {
+    fn from(error: minreq::Error) -> Self {
+        let transient = match &error {
+            minreq::Error::IoError(e) => is_transient_io(e),
+            minreq::Error::AddressNotFound => true,
+            _ => false,
+        };
+        TtsError::request(error, transient)
+    }
+}
+
+#[cfg(feature = "msedge")]
+impl From<msedge_tts::error::Error> for TtsError {
+    fn from(error: msedge_tts::error::Error) -> Self {
+        use msedge_tts::error::Error;
+        let transient = match &error {
+            Error::UnexpectedMessage(message) => return TtsError::InvalidData(message.clone()),
+            Error::SerdeJsonError(e) => return TtsError::InvalidData(e.to_string()),
+            Error::IsahcError(e) => e.is_timeout() || e.is_network(),
+            Error::TungsteniteError(e) => match e {
+                tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => true,
+                tungstenite::Error::Io(e) => is_transient_io(e),
+                tungstenite::Error::Http(response) => {
+                    is_transient_status(response.status().as_u16())
+                }
+                _ => false,
+            },
+            Error::ProxyError(_) => has_transient_io(&error),
+        };
+        TtsError::request(error, transient)
+    }
+}
+
+#[cfg(feature = "tts-rs")]
+impl From<tts::Error> for TtsError {
+    fn from(error: tts::Error) -> Self {
+        TtsError::Platform(Box::new(error))
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<reqwest::Error> for TtsError {
+    fn from(error: reqwest::Error) -> Self {
+        let transient = match error.status() {
+            Some(status) => is_transient_status(status.as_u16()),
+            None => error.is_timeout() || error.is_connect() || error.is_body(),
+        };
+        TtsError::request(error, transient)
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<tokio::task::JoinError> for TtsError {
+    fn from(error: tokio::task::JoinError) -> Self {
+        TtsError::Engine(Box::new(error))
+    }
+}
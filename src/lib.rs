@@ -17,21 +17,53 @@
 // LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
+#![allow(clippy::needless_return)]
+// Engines have always taken `&PathBuf`, and implementations outside the crate
+// depend on those signatures.
+#![allow(clippy::ptr_arg)]
+
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod audio;
+pub mod cache;
+mod error;
 pub mod models;
+pub mod playback;
+mod processing;
+pub mod queue;
+pub mod text;
 mod utils;
 
 #[cfg(test)]
 mod test;
 
-use crate::models::NaturalModelTrait;
+pub use error::{BoxedError, TtsError};
+
+use crate::{
+    audio::{
+        effects::EffectChain,
+        encode::{self, OutputFormat},
+        loudness::Loudness,
+    },
+    cache::Cache,
+    models::{render_segments, NaturalModelTrait, Prosody, SynthesizedAudio},
+    playback::{Playback, Player},
+    processing::Processing,
+    text::{normalize::Normalizer, ssml::Ssml},
+};
 use derive_builder::Builder;
-use models::AudioHandler;
+use models::{AudioHandler, Voice};
 use rodio::Sink;
-use std::{error::Error, path::PathBuf};
-use thiserror::Error as TError;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+#[cfg(feature = "tts-rs")]
 use tts::Tts;
 
-#[cfg(feature = "gtts")]
+#[cfg(feature = "coqui")]
 use crate::models::coqui;
 #[cfg(feature = "gtts")]
 use crate::models::gtts;
@@ -44,205 +76,651 @@ use crate::models::parler;
 #[cfg(feature = "tts-rs")]
 use crate::models::tts_rs::TtsModel;
 
-#[derive(Builder, Default, Clone)]
-#[builder(setter(into))]
+/// A boxed engine as stored in the [`NaturalTts`] registry.
+pub type Engine = Box<dyn NaturalModelTrait<SynthesizeType = f32>>;
+
+#[derive(Builder, Default)]
+#[builder(setter(into), pattern = "owned")]
 pub struct NaturalTts {
     pub default_model: Option<Model>,
     #[builder(default = "None")]
     pub audio_handler: Option<AudioHandler>,
 
-    #[cfg(feature = "tts-rs")]
+    /// Where audio is played. Opened on the default device the first time
+    /// something is played when `None`; pass one in to share it between instances.
     #[builder(default = "None")]
-    pub tts_model: Option<TtsModel>,
+    pub player: Option<Player>,
 
-    #[cfg(feature = "parler")]
+    /// Expands numbers, dates and the like before text reaches an engine.
+    /// Off by default, which sends text as is.
     #[builder(default = "None")]
-    pub parler_model: Option<parler::ParlerModel>,
+    pub normalizer: Option<Normalizer>,
 
-    #[cfg(feature = "coqui")]
+    /// Serves repeated messages without synthesizing them again. Off by default.
     #[builder(default = "None")]
-    pub coqui_model: Option<coqui::CoquiModel>,
+    pub cache: Option<Cache>,
 
-    #[cfg(feature = "gtts")]
+    /// The format [`NaturalTts::save`] writes. `None` picks it from the
+    /// extension of the path, falling back to WAV.
     #[builder(default = "None")]
-    pub gtts_model: Option<gtts::GttsModel>,
+    pub output_format: Option<OutputFormat>,
+
+    #[builder(setter(custom), default)]
+    engines: HashMap<Model, Engine>,
+
+    /// What is done to each engine's audio after synthesis, such as the part
+    /// of its prosody it couldn't apply natively.
+    #[builder(setter(skip))]
+    processing: HashMap<Model, Processing>,
+}
+
+impl NaturalTtsBuilder {
+    /// Registers `engine` under `id`, replacing any engine already registered under it.
+    pub fn engine<M>(mut self, id: Model, engine: M) -> Self
+    where
+        M: NaturalModelTrait<SynthesizeType = f32> + 'static,
+    {
+        self.engines
+            .get_or_insert_with(HashMap::new)
+            .insert(id, Box::new(engine));
+        self
+    }
+
+    #[cfg(feature = "tts-rs")]
+    pub fn tts_model(self, model: TtsModel) -> Self {
+        self.engine(Model::TTS, model)
+    }
+
+    #[cfg(feature = "parler")]
+    pub fn parler_model(self, model: parler::ParlerModel) -> Self {
+        self.engine(Model::Parler, model)
+    }
+
+    #[cfg(feature = "coqui")]
+    pub fn coqui_model(self, model: coqui::CoquiModel) -> Self {
+        self.engine(Model::Coqui, model)
+    }
+
+    #[cfg(feature = "gtts")]
+    pub fn gtts_model(self, model: gtts::GttsModel) -> Self {
+        self.engine(Model::Gtts, model)
+    }
 
     #[cfg(feature = "msedge")]
-    #[builder(default = "None")]
-    pub msedge_model: Option<msedge::MSEdgeModel>,
+    pub fn msedge_model(self, model: msedge::MSEdgeModel) -> Self {
+        self.engine(Model::MSEdge, model)
+    }
 
     #[cfg(feature = "meta")]
-    #[builder(default = "None")]
-    pub meta_model: Option<meta::MetaModel>,
+    pub fn meta_model(self, model: meta::MetaModel) -> Self {
+        self.engine(Model::Meta, model)
+    }
 }
 
 impl NaturalTts {
-    pub fn get_tts_handler(&mut self) -> Result<&mut Tts, TtsError> {
-        if let Some(tts) = &mut self.tts_model {
-            Ok(&mut tts.0)
-        } else {
-            Err(TtsError::NotLoaded)
+    /// Registers `engine` under `id`, returning the engine it replaced, if any.
+    pub fn register<M>(&mut self, id: Model, engine: M) -> Option<Engine>
+    where
+        M: NaturalModelTrait<SynthesizeType = f32> + 'static,
+    {
+        self.processing.remove(&id);
+        self.engines.insert(id, Box::new(engine))
+    }
+
+    pub fn unregister(&mut self, id: &Model) -> Option<Engine> {
+        self.processing.remove(id);
+        self.engines.remove(id)
+    }
+
+    pub fn is_registered(&self, id: &Model) -> bool {
+        self.engines.contains_key(id)
+    }
+
+    /// The ids of every registered engine, in no particular order.
+    pub fn engines(&self) -> impl Iterator<Item = &Model> {
+        self.engines.keys()
+    }
+
+    pub fn engine_mut(
+        &mut self,
+        id: &Model,
+    ) -> Result<&mut dyn NaturalModelTrait<SynthesizeType = f32>, TtsError> {
+        match self.engines.get_mut(id) {
+            Some(engine) => Ok(engine.as_mut()),
+            None => Err(TtsError::NotRegistered(id.clone())),
+        }
+    }
+
+    fn default_engine(
+        &mut self,
+    ) -> Result<&mut dyn NaturalModelTrait<SynthesizeType = f32>, TtsError> {
+        match self.default_model.clone() {
+            Some(model) => self.engine_mut(&model),
+            None => Err(TtsError::NoDefaultModel),
+        }
+    }
+
+    /// Names the default engine in errors about what it doesn't support.
+    fn tag<T>(&self, result: Result<T, TtsError>) -> Result<T, TtsError> {
+        match &self.default_model {
+            Some(model) => result.map_err(|e| e.with_engine(model)),
+            None => result,
+        }
+    }
+
+    /// Sets the prosody of the engine `id`. Whatever the engine can't do
+    /// natively is applied to the audio it synthesizes.
+    pub fn set_prosody(&mut self, id: &Model, prosody: Prosody) -> Result<(), TtsError> {
+        let rest = self
+            .engine_mut(id)?
+            .set_prosody(&prosody)
+            .map_err(|e| e.with_engine(id))?;
+        self.update_processing(id, |x| x.prosody = rest);
+        Ok(())
+    }
+
+    /// Runs all audio of the engine `id` through `effects`, after its prosody.
+    /// An empty chain turns them off.
+    pub fn set_effects(&mut self, id: &Model, effects: EffectChain) -> Result<(), TtsError> {
+        self.engine_mut(id)?;
+        self.update_processing(id, |x| x.effects = effects);
+        Ok(())
+    }
+
+    /// The chain set with [`NaturalTts::set_effects`], if any.
+    pub fn effects(&self, id: &Model) -> Option<&EffectChain> {
+        self.processing
+            .get(id)
+            .map(|x| &x.effects)
+            .filter(|x| !x.is_empty())
+    }
+
+    /// Resamples all audio of the engine `id` to `sample_rate`, or leaves it
+    /// at the engine's own rate when `None`.
+    pub fn set_output_sample_rate(
+        &mut self,
+        id: &Model,
+        sample_rate: Option<u32>,
+    ) -> Result<(), TtsError> {
+        if sample_rate == Some(0) {
+            return Err(TtsError::not_supported("an output sample rate of 0 Hz"));
+        }
+        self.engine_mut(id)?;
+        self.update_processing(id, |x| x.sample_rate = sample_rate);
+        Ok(())
+    }
+
+    /// The rate set with [`NaturalTts::set_output_sample_rate`].
+    pub fn output_sample_rate(&self, id: &Model) -> Option<u32> {
+        self.processing.get(id).and_then(|x| x.sample_rate)
+    }
+
+    /// Normalizes all audio of the engine `id` to `loudness`, or leaves it at
+    /// the engine's own level when `None`. This comes after the prosody, so it
+    /// overrides its volume.
+    pub fn set_loudness(&mut self, id: &Model, loudness: Option<Loudness>) -> Result<(), TtsError> {
+        self.engine_mut(id)?;
+        self.update_processing(id, |x| x.loudness = loudness);
+        Ok(())
+    }
+
+    /// The loudness set with [`NaturalTts::set_loudness`].
+    pub fn loudness(&self, id: &Model) -> Option<Loudness> {
+        self.processing.get(id).and_then(|x| x.loudness)
+    }
+
+    fn update_processing(&mut self, id: &Model, update: impl FnOnce(&mut Processing)) {
+        let processing = self.processing.entry(id.clone()).or_default();
+        update(processing);
+        if processing.is_empty() {
+            self.processing.remove(id);
+        }
+    }
+
+    /// What is left to do to the default engine's audio, if anything.
+    fn processing(&self) -> Option<Processing> {
+        self.default_model
+            .as_ref()
+            .and_then(|x| self.processing.get(x))
+            .cloned()
+    }
+
+    /// The default engine's language, which text is normalized in.
+    fn language(&self) -> Option<String> {
+        self.default_model
+            .as_ref()
+            .and_then(|x| self.engines.get(x))
+            .and_then(|x| x.language())
+    }
+
+    /// Runs `message` through the [`Normalizer`], if there is one.
+    fn normalize(&self, message: String) -> String {
+        match &self.normalizer {
+            Some(normalizer) => normalizer.normalize(&message, self.language().as_deref()),
+            None => message,
+        }
+    }
+
+    /// The cache key of `message`, already normalized, when the default
+    /// engine's audio can be cached.
+    fn cache_key(&self, message: &str) -> Option<String> {
+        self.cache.as_ref()?;
+        let model = self.default_model.as_ref()?;
+        let configuration = self.engines.get(model)?.configuration()?;
+        let processing = self.processing.get(model).cloned().unwrap_or_default();
+        let configuration = processing.configuration(&configuration);
+        Some(Cache::key(model, &configuration, message))
+    }
+
+    /// Whether `message` would currently be served from the cache.
+    pub fn is_cached(&mut self, message: String) -> bool {
+        let message = self.normalize(message);
+        match (self.cache_key(&message), &mut self.cache) {
+            (Some(key), Some(cache)) => cache.get(&key).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Drops the cached audio of `message` for the default engine as it is
+    /// configured now, returning whether there was any.
+    pub fn uncache(&mut self, message: String) -> Result<bool, TtsError> {
+        let message = self.normalize(message);
+        match (self.cache_key(&message), &mut self.cache) {
+            (Some(key), Some(cache)) => Ok(cache.remove(&key)?),
+            _ => Ok(false),
+        }
+    }
+
+    /// Serves `message`, already normalized, from the cache, running
+    /// `synthesize` and storing its audio on a miss.
+    fn through_cache(
+        &mut self,
+        message: String,
+        synthesize: impl FnOnce(&mut Self, String) -> Result<SynthesizedAudio<f32>, TtsError>,
+    ) -> Result<SynthesizedAudio<f32>, TtsError> {
+        let Some(key) = self.cache_key(&message) else {
+            return synthesize(self, message);
+        };
+        if let Some(audio) = self.cache.as_mut().and_then(|x| x.get(&key)) {
+            return Ok(audio);
+        }
+
+        let audio = synthesize(self, message)?;
+        if let Some(cache) = &mut self.cache {
+            cache.insert(key, &audio)?;
         }
+        Ok(audio)
     }
 
-    pub fn get_rodio_sink(&mut self) -> Result<&mut Sink, TtsError> {
+    /// Gathers the voices of every registered engine, ordered by engine id.
+    pub fn list_voices(&self) -> Result<Vec<Voice>, TtsError> {
+        let mut ids: Vec<&Model> = self.engines.keys().collect();
+        ids.sort_by_key(|id| id.to_string());
+
+        let mut voices = Vec::new();
+        for id in ids {
+            for mut voice in self.engines[id].voices()? {
+                voice.engine = id.clone();
+                voices.push(voice);
+            }
+        }
+
+        Ok(voices)
+    }
+
+    /// Configures the engine owning the voice `id` to speak with it and makes
+    /// that engine the default. `id` may be qualified with the engine, as in
+    /// `msedge:en-US-AriaNeural`, to tell apart voices that share an id.
+    pub fn select_voice(&mut self, id: &str) -> Result<Voice, TtsError> {
+        let voice = self
+            .list_voices()?
+            .into_iter()
+            .find(|x| x.matches(id))
+            .ok_or_else(|| TtsError::UnknownVoice(id.to_string()))?;
+
+        self.engine_mut(&voice.engine)?
+            .set_voice(&voice)
+            .map_err(|e| e.with_engine(&voice.engine))?;
+        self.default_model = Some(voice.engine.clone());
+        Ok(voice)
+    }
+
+    #[cfg(feature = "tts-rs")]
+    pub fn get_tts_handler(&mut self) -> Result<&mut Tts, TtsError> {
         match &mut self.audio_handler {
-            Some(AudioHandler::Sink(x)) => Ok(x),
-            Some(_) => return Err(TtsError::NotSupported),
-            _ => return Err(TtsError::NotLoaded),
+            Some(AudioHandler::Tts(x)) => Ok(x),
+            Some(_) => Err(TtsError::not_supported(
+                "a tts-rs handler for rodio playback",
+            )),
+            _ => Err(TtsError::NotLoaded),
         }
     }
 
-    pub fn start(&mut self, message: String, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        if let Some(model) = &self.default_model {
-            self.audio_handler = Some(match model {
-                #[cfg(feature = "coqui")]
-                Model::Coqui => match &mut self.coqui_model {
-                    Some(x) => Ok(x.start(message, path)?),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "parler")]
-                Model::Parler => match &mut self.parler_model {
-                    Some(x) => Ok(x.start(message, path)?),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "tts-rs")]
-                Model::TTS => match &mut self.tts_model {
-                    Some(x) => Ok(x.start(message, path)?),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "msedge")]
-                Model::MSEdge => match &mut self.msedge_model {
-                    Some(x) => Ok(x.start(message, path)?),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "meta")]
-                Model::Meta => match &mut self.meta_model {
-                    Some(x) => Ok(x.start(message, path)?),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "gtts")]
-                _ => match &mut self.gtts_model {
-                    Some(x) => Ok(x.start(message, path)?),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-            }?);
-
-            return Ok(());
+    /// A handle to the sound last started, which stays valid after the next one starts.
+    pub fn playback(&self) -> Result<Playback, TtsError> {
+        match &self.audio_handler {
+            Some(AudioHandler::Playback(x)) => Ok(x.clone()),
+            #[cfg(feature = "tts-rs")]
+            Some(_) => Err(TtsError::not_supported(
+                "a playback handle for tts-rs playback",
+            )),
+            None => Err(TtsError::NotLoaded),
+        }
+    }
+
+    pub fn get_rodio_sink(&self) -> Result<&Sink, TtsError> {
+        match &self.audio_handler {
+            Some(AudioHandler::Playback(x)) => Ok(x.sink()),
+            #[cfg(feature = "tts-rs")]
+            Some(_) => Err(TtsError::not_supported("a rodio sink for tts-rs playback")),
+            None => Err(TtsError::NotLoaded),
+        }
+    }
+
+    /// The player audio goes through, opening the default device if there is none yet.
+    pub fn player(&mut self) -> Result<Player, TtsError> {
+        match &self.player {
+            Some(player) => Ok(player.clone()),
+            None => {
+                let player = Player::new()?;
+                self.player = Some(player.clone());
+                Ok(player)
+            }
+        }
+    }
+
+    /// Whether the default engine's audio has to pass through memory, either
+    /// to be processed or for the cache.
+    fn in_memory(&self, message: &str) -> bool {
+        self.processing().is_some() || self.cache_key(message).is_some()
+    }
+
+    /// Synthesizes `message`, already normalized, with the default engine and
+    /// processes it, going through the cache if there is one.
+    fn render(
+        &mut self,
+        message: String,
+        path: Option<&PathBuf>,
+    ) -> Result<SynthesizedAudio<f32>, TtsError> {
+        self.through_cache(message, |tts, message| {
+            let processing = tts.processing();
+            let engine = tts.default_engine()?;
+            let audio = match path {
+                Some(path) => engine.synthesize(message, path),
+                None => engine.synthesize_to_memory(message),
+            };
+            let mut audio = tts.tag(audio)?;
+            if let Some(processing) = processing {
+                processing.apply(&mut audio)?;
+            }
+            Ok(audio)
+        })
+    }
+
+    /// Starts speaking `message`, stopping the sound started before. Use a
+    /// [`SpeechQueue`](queue::SpeechQueue) to queue messages instead.
+    pub fn start(&mut self, message: String, path: &PathBuf) -> Result<(), TtsError> {
+        if let Some(mut previous) = self.audio_handler.take() {
+            previous.stop()?;
         }
+        let handler = self.launch(message, path)?;
+        self.audio_handler = Some(handler);
+        Ok(())
+    }
 
-        Err(Box::new(TtsError::NoDefaultModel))
+    /// Starts speaking `message` alongside anything already playing.
+    pub(crate) fn launch(
+        &mut self,
+        message: String,
+        path: &PathBuf,
+    ) -> Result<AudioHandler, TtsError> {
+        let message = self.normalize(message);
+        let player = self.player()?;
+        match self.in_memory(&message) {
+            true => Ok(AudioHandler::Playback(
+                player.play(&self.render(message, None)?)?,
+            )),
+            false => {
+                let handler = self.default_engine()?.start(message, path, &player);
+                self.tag(handler)
+            }
+        }
     }
 
     pub fn synthesize(
         &mut self,
         message: String,
         path: &PathBuf,
-    ) -> Result<models::SynthesizedAudio<f32>, Box<dyn Error>> {
-        if let Some(model) = &self.default_model {
-            return match model {
-                #[cfg(feature = "coqui")]
-                Model::Coqui => match &mut self.coqui_model {
-                    Some(x) => x.synthesize(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "parler")]
-                Model::Parler => match &mut self.parler_model {
-                    Some(x) => x.synthesize(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "tts-rs")]
-                Model::TTS => match &mut self.tts_model {
-                    Some(x) => x.synthesize(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "msedge")]
-                Model::MSEdge => match &mut self.msedge_model {
-                    Some(x) => x.synthesize(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "meta")]
-                Model::Meta => match &mut self.meta_model {
-                    Some(x) => x.synthesize(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "gtts")]
-                _ => match &mut self.gtts_model {
-                    Some(x) => x.synthesize(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-            };
+    ) -> Result<SynthesizedAudio<f32>, TtsError> {
+        let message = self.normalize(message);
+        self.render(message, Some(path))
+    }
+
+    pub fn synthesize_to_memory(
+        &mut self,
+        message: String,
+    ) -> Result<SynthesizedAudio<f32>, TtsError> {
+        let message = self.normalize(message);
+        self.render(message, None)
+    }
+
+    /// Synthesizes an SSML document with the default engine, natively when it
+    /// supports SSML and segment by segment otherwise. Whatever the parser or
+    /// the engine couldn't handle is listed in [`Ssml::warnings`].
+    pub fn synthesize_ssml(&mut self, ssml: &mut Ssml) -> Result<SynthesizedAudio<f32>, TtsError> {
+        let processing = self.processing();
+        let normalized;
+        let document = match &self.normalizer {
+            Some(normalizer) => {
+                normalized = ssml.normalized(normalizer, self.language().as_deref());
+                &normalized
+            }
+            None => &*ssml,
+        };
+        let mut warnings = Vec::new();
+        let engine = self.default_engine()?;
+        let audio = match engine.synthesize_ssml(document) {
+            Ok(Some(audio)) => Ok(audio),
+            Ok(None) => render_segments(engine, &document.segments(), &mut warnings),
+            Err(e) => Err(e),
+        };
+        ssml.warnings.extend(warnings);
+        let mut audio = self.tag(audio)?;
+        if let Some(processing) = processing {
+            processing.apply(&mut audio)?;
         }
+        Ok(audio)
+    }
 
-        Err(Box::new(TtsError::NoDefaultModel))
-    }
-
-    pub fn save(&mut self, message: String, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        if let Some(model) = &self.default_model {
-            return match model {
-                #[cfg(feature = "coqui")]
-                Model::Coqui => match &mut self.coqui_model {
-                    Some(x) => x.save(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "parler")]
-                Model::Parler => match &mut self.parler_model {
-                    Some(x) => x.save(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "tts-rs")]
-                Model::TTS => match &mut self.tts_model {
-                    Some(x) => x.save(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "msedge")]
-                Model::MSEdge => match &mut self.msedge_model {
-                    Some(x) => x.save(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "meta")]
-                Model::Meta => match &mut self.meta_model {
-                    Some(x) => x.save(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-                #[cfg(feature = "gtts")]
-                _ => match &mut self.gtts_model {
-                    Some(x) => x.save(message, path),
-                    None => Err(Box::new(TtsError::NotLoaded)),
-                },
-            };
+    /// Saves an SSML document to `path` in [`NaturalTts::output_format`].
+    /// Only formats whose encoder is enabled are supported.
+    pub fn save_ssml(&mut self, ssml: &mut Ssml, path: &Path) -> Result<(), TtsError> {
+        let format = self.output_format(path);
+        if !format.is_encodable() {
+            return Err(format.not_enabled());
         }
+        encode::save(&self.synthesize_ssml(ssml)?, format, path)
+    }
 
-        Err(Box::new(TtsError::NoDefaultModel))
+    /// Synthesizes `message` with the default engine, yielding chunks of audio
+    /// while generation continues.
+    ///
+    /// Effects, the output sample rate and loudness run across the chunks as
+    /// if the audio were whole, holding back what they need to see first, so
+    /// the chunks don't line up with the engine's. Rate and pitch the engine
+    /// can't apply itself can't be streamed.
+    pub fn synthesize_stream(
+        &mut self,
+        message: String,
+    ) -> Result<models::AudioStream<'_, f32>, TtsError> {
+        let processing = self.processing();
+        let message = self.normalize(message);
+        let model = self.default_model.clone();
+        let stream = self.default_engine()?.synthesize_stream(message);
+        let stream = match model {
+            Some(model) => stream.map_err(|e| e.with_engine(&model))?,
+            None => stream?,
+        };
+        match processing {
+            Some(processing) => processing.stream(stream),
+            None => Ok(stream),
+        }
     }
 
-    pub fn resume(&mut self) -> Result<(), Box<dyn Error>> {
-        match &mut self.audio_handler {
-            Some(AudioHandler::Sink(x)) => x.play(),
-            Some(_) => return Err(Box::new(TtsError::NotSupported)),
-            _ => return Err(Box::new(TtsError::NotLoaded)),
+    /// Like [`NaturalTts::start`], but playback begins as soon as the first chunk
+    /// is ready. Returns once the whole message has been queued.
+    pub fn start_stream(&mut self, message: String) -> Result<(), TtsError> {
+        let model = self.default_model.clone().ok_or(TtsError::NoDefaultModel)?;
+        let processing = self.processing.get(&model).cloned().unwrap_or_default();
+        let message = self.normalize(message);
+        let player = self.player()?;
+        if let Some(mut previous) = self.audio_handler.take() {
+            previous.stop()?;
+        }
+        let engine = match self.engines.get_mut(&model) {
+            Some(engine) => engine,
+            None => return Err(TtsError::NotRegistered(model)),
+        };
+
+        let mut playback: Option<Playback> = None;
+        let stream = engine
+            .synthesize_stream(message)
+            .map_err(|e| e.with_engine(&model))?;
+        for chunk in processing.stream(stream)? {
+            let chunk = chunk?;
+
+            match &playback {
+                Some(playback) => playback.append(&chunk)?,
+                None => {
+                    let started = player.play(&chunk)?;
+                    self.audio_handler = Some(AudioHandler::Playback(started.clone()));
+                    playback = Some(started);
+                }
+            }
         }
 
         Ok(())
     }
 
-    pub fn stop(&mut self) -> Result<(), Box<dyn Error>> {
-        match &mut self.audio_handler {
-            Some(AudioHandler::Sink(x)) => x.stop(),
-            Some(AudioHandler::Tts) => match &mut self.tts_model {
-                Some(x) => {
-                    let _ = x.0.stop()?;
+    /// Saves `message` to `path` in [`NaturalTts::output_format`].
+    ///
+    /// Formats whose encoder feature is off, such as MP3 without `mp3`, are
+    /// saved as the engine produces them, bypassing the cache, so only
+    /// engines that produce them support them, and not with prosody applied
+    /// as DSP or an output sample rate.
+    pub fn save(&mut self, message: String, path: &PathBuf) -> Result<(), TtsError> {
+        let message = self.normalize(message);
+        let format = self.output_format(path);
+        if !format.is_encodable() && self.processing().is_some() {
+            return Err(format.not_enabled());
+        }
+
+        // Engines pick the format from the extension, with default settings.
+        let by_extension = OutputFormat::from_path(path).unwrap_or_default();
+        let by_engine = format.same_format(&by_extension)
+            && (!format.is_encodable()
+                || (self.output_format.is_none() && !self.in_memory(&message)));
+        if by_engine {
+            let saved = self.default_engine()?.save(message, path);
+            return self.tag(saved);
+        }
+        let audio = self.render(message, None)?;
+        encode::save(&audio, format, path)
+    }
+
+    fn output_format(&self, path: &Path) -> OutputFormat {
+        self.output_format
+            .or_else(|| OutputFormat::from_path(path))
+            .unwrap_or_default()
+    }
+
+    /// The sound last started, for controls tts-rs playback doesn't have.
+    /// tts-rs 0.26 can't pause, resume or seek on any platform.
+    #[cfg_attr(not(feature = "tts-rs"), allow(unused_variables))]
+    fn current(&self, operation: &str) -> Result<&Playback, TtsError> {
+        match &self.audio_handler {
+            Some(AudioHandler::Playback(x)) => Ok(x),
+            #[cfg(feature = "tts-rs")]
+            Some(_) => Err(TtsError::not_supported(format!(
+                "{} tts-rs playback",
+                operation
+            ))),
+            None => Err(TtsError::NotLoaded),
+        }
+    }
+
+    pub fn pause(&mut self) -> Result<(), TtsError> {
+        self.current("pausing")?.pause();
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<(), TtsError> {
+        self.current("resuming")?.resume();
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> Result<bool, TtsError> {
+        Ok(self.current("pausing")?.is_paused())
+    }
+
+    /// How far into the sound last started playback is.
+    pub fn position(&self) -> Result<Duration, TtsError> {
+        Ok(self.current("tracking the position of")?.position())
+    }
+
+    /// The length of the sound last started, as far as it has been synthesized.
+    pub fn duration(&self) -> Result<Duration, TtsError> {
+        Ok(self.current("measuring")?.duration())
+    }
+
+    pub fn seek(&mut self, position: Duration) -> Result<(), TtsError> {
+        self.current("seeking in")?.seek(position)
+    }
+
+    /// Sets the linear gain of the sound last started, see [`Playback::set_volume`].
+    pub fn set_volume(&mut self, volume: f32) -> Result<(), TtsError> {
+        self.current("changing the volume of")?.set_volume(volume);
+        Ok(())
+    }
+
+    /// Sets the speed of the sound last started, see [`Playback::set_speed`].
+    pub fn set_speed(&mut self, speed: f32) -> Result<(), TtsError> {
+        self.current("changing the speed of")?.set_speed(speed);
+        Ok(())
+    }
+
+    /// Blocks until the sound last started is done or stopped.
+    pub fn wait_until_done(&self) -> Result<(), TtsError> {
+        match &self.audio_handler {
+            Some(AudioHandler::Playback(x)) => x.wait_until_done(),
+            #[cfg(feature = "tts-rs")]
+            Some(AudioHandler::Tts(x)) => {
+                if !x.supported_features().is_speaking {
+                    return Err(TtsError::not_supported("waiting for tts-rs playback"));
                 }
-                None => return Err(Box::new(TtsError::NotSupported)),
-            },
-            _ => return Err(Box::new(TtsError::NotLoaded)),
+                while x.is_speaking()? {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            }
+            None => return Err(TtsError::NotLoaded),
         }
 
         Ok(())
     }
+
+    pub fn stop(&mut self) -> Result<(), TtsError> {
+        match &mut self.audio_handler {
+            Some(handler) => handler.stop(),
+            None => Err(TtsError::NotLoaded),
+        }
+    }
 }
 
-#[derive(Default, Clone)]
+/// Identifies an engine in the [`NaturalTts`] registry.
+///
+/// Built-in engines have their own variants; anything else can be registered
+/// under [`Model::Custom`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Model {
     #[cfg(feature = "coqui")]
     Coqui,
@@ -260,26 +738,39 @@ pub enum Model {
     Meta,
 
     #[cfg(feature = "gtts")]
-    #[default]
     Gtts,
+
+    Custom(String),
 }
 
-#[derive(Debug, TError, Clone)]
-pub enum TtsError {
-    #[error("Not Supported")]
-    NotSupported,
-    #[error("Operation failed")]
-    OperationFailed,
-    #[error("Model Not Loaded")]
-    NotLoaded,
-    #[error("Didn't Save")]
-    NotSaved,
-    #[error("Default model not set")]
-    NoDefaultModel,
-    #[error("Tensor Error")]
-    Tensor,
-    #[error("No Tokenizer Key")]
-    NoTokenizerKey,
-    #[error("Json Error")]
-    Json,
+/// Gtts, as it always was. Builds without it default to an unnamed custom
+/// engine, which has to be registered like any other.
+impl Default for Model {
+    fn default() -> Self {
+        #[cfg(feature = "gtts")]
+        return Model::Gtts;
+        #[cfg(not(feature = "gtts"))]
+        return Model::Custom(String::new());
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "coqui")]
+            Model::Coqui => write!(f, "coqui"),
+            #[cfg(feature = "parler")]
+            Model::Parler => write!(f, "parler"),
+            #[cfg(feature = "tts-rs")]
+            Model::TTS => write!(f, "tts"),
+            #[cfg(feature = "msedge")]
+            Model::MSEdge => write!(f, "msedge"),
+            #[cfg(feature = "meta")]
+            Model::Meta => write!(f, "meta"),
+            #[cfg(feature = "gtts")]
+            Model::Gtts => write!(f, "gtts"),
+            // Kept apart from the built-in ids, which a custom id may reuse.
+            Model::Custom(id) => write!(f, "custom:{}", id),
+        }
+    }
 }
@@ -1,10 +1,14 @@
 use super::*;
-use pyo3::prelude::*;
+use crate::audio::encode::{self, OutputFormat};
+use pyo3::{prelude::*, types::PyDict};
 
 #[derive(Debug)]
 pub struct CoquiModel {
     model: Py<PyAny>,
     device: String,
+    model_name: String,
+    /// Speaker of a multi-speaker model, chosen with [`NaturalModelTrait::set_voice`].
+    speaker: Option<String>,
 }
 
 impl Clone for CoquiModel {
@@ -13,14 +17,16 @@ impl Clone for CoquiModel {
             return Self {
                 model: self.model.clone_ref(py),
                 device: self.device.clone(),
+                model_name: self.model_name.clone(),
+                speaker: self.speaker.clone(),
             };
         });
     }
 }
 
 impl CoquiModel {
-    pub fn new(model_name: String, use_gpu: bool) -> Result<Self, Box<dyn Error>> {
-        let m = Python::with_gil(|py| -> Result<Self, Box<dyn Error>> {
+    pub fn new(model_name: String, use_gpu: bool) -> Result<Self, TtsError> {
+        let m = Python::with_gil(|py| -> Result<Self, TtsError> {
             let torch = py.import("torch")?;
             let tts = py.import("TTS.api")?;
 
@@ -38,38 +44,135 @@ impl CoquiModel {
 
             let model = tts
                 .getattr("TTS")?
-                .call1((("model_name", model_name), ("progress_bar", false)))?
+                .call1((("model_name", model_name.clone()), ("progress_bar", false)))?
                 .getattr("to")?
                 .call1((device.clone(), ("return_tensors", "pt")))?
                 .unbind();
 
-            return Ok(Self { model, device });
+            return Ok(Self {
+                model,
+                device,
+                model_name,
+                speaker: None,
+            });
         });
 
         return m;
     }
 
-    pub fn generate(&self, message: String, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        return Python::with_gil(|py| -> Result<(), Box<dyn Error>> {
+    pub fn generate(&self, message: String, path: &PathBuf) -> Result<(), TtsError> {
+        return Python::with_gil(|py| -> Result<(), TtsError> {
+            let kwargs = self.kwargs(py, message)?;
+            kwargs.set_item("file_path", path)?;
             self.model
-                .getattr(py, "tts_to_file")?
-                .call1(py, (("text", message), ("file_path", path.to_str().unwrap())))?;
+                .call_method(py, "tts_to_file", (), Some(&kwargs))?;
             Ok(())
         });
     }
+
+    /// Runs the model in memory, returning the waveform and its sample rate.
+    pub fn generate_pcm(&self, message: String) -> Result<(Vec<f32>, u32), TtsError> {
+        return Python::with_gil(|py| -> Result<(Vec<f32>, u32), TtsError> {
+            let kwargs = self.kwargs(py, message)?;
+            let pcm: Vec<f32> = self
+                .model
+                .call_method(py, "tts", (), Some(&kwargs))?
+                .extract(py)?;
+            let sample_rate: u32 = self
+                .model
+                .getattr(py, "synthesizer")?
+                .getattr(py, "output_sample_rate")?
+                .extract(py)?;
+            Ok((pcm, sample_rate))
+        });
+    }
+
+    fn kwargs<'py>(&self, py: Python<'py>, message: String) -> PyResult<Bound<'py, PyDict>> {
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("text", message)?;
+        if let Some(speaker) = &self.speaker {
+            kwargs.set_item("speaker", speaker)?;
+        }
+        Ok(kwargs)
+    }
+
+    /// The language segment of the model name, e.g. `en` in `tts_models/en/ljspeech/vits`.
+    fn language_tag(&self) -> String {
+        match self.model_name.split('/').nth(1) {
+            Some("multilingual") | None => "und".to_string(),
+            Some(x) => x.to_string(),
+        }
+    }
 }
 
 impl Default for CoquiModel {
+    /// Panics if the model can't be loaded, see [`CoquiModel::new`].
     fn default() -> Self {
-        return Self::new("tts_models/en/ljspeech/vits".to_string(), true).unwrap();
+        return Self::new("tts_models/en/ljspeech/vits".to_string(), true)
+            .expect("failed to load tts_models/en/ljspeech/vits");
     }
 }
 
 impl NaturalModelTrait for CoquiModel {
     type SynthesizeType = f32;
 
-    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        let _ = self.generate(message, path)?;
+    /// Coqui writes WAVs itself. Other formats are encoded from memory.
+    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), TtsError> {
+        match OutputFormat::from_path(path).unwrap_or_default() {
+            OutputFormat::Wav { format: None } => self.generate(message, path)?,
+            format => encode::save(&self.synthesize_to_memory(message)?, format, path)?,
+        }
         did_save(path)
     }
+
+    fn synthesize_to_memory(
+        &mut self,
+        message: String,
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
+        let (pcm, sample_rate) = self.generate_pcm(message)?;
+        let duration = pcm.len() as i32;
+        Ok(SynthesizedAudio::new(
+            pcm,
+            Spec::Wav(WavSpec {
+                sample_rate,
+                channels: 1,
+                sample_format: hound::SampleFormat::Float,
+                bits_per_sample: 32,
+            }),
+            Some(duration),
+        ))
+    }
+
+    /// The speakers of a multi-speaker model, otherwise the model itself.
+    fn voices(&self) -> Result<Vec<Voice>, TtsError> {
+        let speakers = Python::with_gil(|py| -> PyResult<Option<Vec<String>>> {
+            self.model.getattr(py, "speakers")?.extract(py)
+        })?;
+
+        let ids = speakers.unwrap_or_else(|| vec![self.model_name.clone()]);
+        Ok(ids
+            .into_iter()
+            .map(|x| Voice::new(x, crate::Model::Coqui, self.language_tag()))
+            .collect())
+    }
+
+    fn language(&self) -> Option<String> {
+        Some(self.language_tag()).filter(|x| x != "und")
+    }
+
+    fn configuration(&self) -> Option<String> {
+        Some(format!("{}\n{:?}", self.model_name, self.speaker))
+    }
+
+    /// Ids starting with `tts_models/` load that model, anything else picks a speaker.
+    fn set_voice(&mut self, voice: &Voice) -> Result<(), TtsError> {
+        if !voice.id.starts_with("tts_models/") {
+            self.speaker = Some(voice.id.clone());
+        } else if voice.id != self.model_name {
+            *self = Self::new(voice.id.clone(), self.device != "cpu")?;
+        } else {
+            self.speaker = None;
+        }
+        Ok(())
+    }
 }
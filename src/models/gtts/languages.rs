@@ -194,6 +194,69 @@ impl FromStr for Languages {
 }
 
 impl Languages {
+    /// Every supported language.
+    pub const ALL: [Languages; 59] = [
+        Languages::Afrikaans,
+        Languages::Albanian,
+        Languages::Arabic,
+        Languages::Armenian,
+        Languages::Bengali,
+        Languages::Bosnian,
+        Languages::Bulgarian,
+        Languages::Catalan,
+        Languages::Chinese,
+        Languages::Croatian,
+        Languages::Czech,
+        Languages::Danish,
+        Languages::Dutch,
+        Languages::English,
+        Languages::Esperanto,
+        Languages::Estonian,
+        Languages::Filipino,
+        Languages::Finnish,
+        Languages::French,
+        Languages::German,
+        Languages::Greek,
+        Languages::Gujarati,
+        Languages::Hindi,
+        Languages::Hungarian,
+        Languages::Icelandic,
+        Languages::Indonesian,
+        Languages::Italian,
+        Languages::Japanese,
+        Languages::Javanese,
+        Languages::Kannada,
+        Languages::Khmer,
+        Languages::Korean,
+        Languages::Latin,
+        Languages::Latvian,
+        Languages::Macedonian,
+        Languages::Marathi,
+        Languages::Nepali,
+        Languages::Norwegian,
+        Languages::Polish,
+        Languages::Portuguese,
+        Languages::Romanian,
+        Languages::Russian,
+        Languages::Serbian,
+        Languages::Sinhala,
+        Languages::Slovak,
+        Languages::Spanish,
+        Languages::Swahili,
+        Languages::Swedish,
+        Languages::Tamil,
+        Languages::Telugu,
+        Languages::Thai,
+        Languages::Turkish,
+        Languages::Ukrainian,
+        Languages::Urdu,
+        Languages::Vietnamese,
+        Languages::Welsh,
+        Languages::MyanmarAKABurmese,
+        Languages::Malayalam,
+        Languages::Sundanese,
+    ];
+
     pub fn as_code(&self) -> &'static str {
         match self {
             Languages::Afrikaans => "af",
@@ -1,49 +1,189 @@
 pub mod languages;
+pub mod tokenizer;
 pub mod url;
 use super::*;
+#[cfg(feature = "async")]
+use crate::asynchronous::{AsyncNaturalModelTrait, AsyncResult, BoxFuture};
+use crate::{
+    audio::encode::{self, OutputFormat},
+    utils::decode_audio,
+};
 use minreq::get;
-use std::io::Write;
+use std::{io::Write, path::Path};
 use url::EncodedFragment;
 
+/// The longest text, in characters, that translate_tts accepts in one request.
+pub const MAX_CHARS: usize = 100;
+
+/// Regional accents, picked through the top-level domain the request is sent to,
+/// as `(BCP-47 tag, language, tld)`.
+const ACCENTS: &[(&str, &str, &str)] = &[
+    ("en-US", "en", "com"),
+    ("en-GB", "en", "co.uk"),
+    ("en-AU", "en", "com.au"),
+    ("en-CA", "en", "ca"),
+    ("en-IN", "en", "co.in"),
+    ("en-IE", "en", "ie"),
+    ("en-ZA", "en", "co.za"),
+    ("fr-FR", "fr", "fr"),
+    ("fr-CA", "fr", "ca"),
+    ("pt-BR", "pt", "com.br"),
+    ("pt-PT", "pt", "pt"),
+    ("es-ES", "es", "es"),
+    ("es-MX", "es", "com.mx"),
+    ("es-US", "es", "us"),
+];
+
 #[derive(Clone, Debug)]
 pub struct GttsModel {
+    /// Linear gain applied to the decoded audio. MP3s are saved as received
+    /// only when the volume is unchanged, otherwise they need the `mp3` feature.
     pub volume: f32,
     pub language: languages::Languages,
     pub tld: String,
+    pub speed: Speed,
+    /// Linear gain of the prosody volume, on top of [`GttsModel::volume`].
+    prosody_gain: f32,
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum Speed {
+    #[default]
     Normal,
     Slow,
 }
 
+impl Speed {
+    /// The `ttsspeed` parameter of translate_tts.
+    pub fn as_param(&self) -> &'static str {
+        match self {
+            Speed::Normal => "1",
+            Speed::Slow => "0.3",
+        }
+    }
+}
+
 impl GttsModel {
     pub fn new(volume: f32, language: languages::Languages, tld: String) -> Self {
         Self {
             language,
             volume,
             tld,
+            speed: Speed::Normal,
+            prosody_gain: 1.0,
         }
     }
 
-    pub fn generate(&self, message: String, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        let len = message.len();
-        if len > 100 {
-            return Err(format!("The text is too long. Max length is {}", 100).into());
+    /// The gain applied to the decoded audio, the user's and the prosody's.
+    fn gain(&self) -> f32 {
+        self.volume * self.prosody_gain
+    }
+
+    /// Saves `message` to `path`, keeping the MP3 from translate_tts if the
+    /// extension asks for it and the volume is unchanged, and encoding the
+    /// format it names otherwise.
+    pub fn generate(&self, message: String, path: &PathBuf) -> Result<(), TtsError> {
+        let pieces = self.fetch_pieces(message)?;
+        write_pieces(pieces, path, self.gain())
+    }
+
+    /// Requests `message` from translate_tts, returning the MP3 bytes.
+    ///
+    /// Messages longer than [`MAX_CHARS`] are split into several requests whose
+    /// MP3 streams are joined together.
+    pub fn fetch(&self, message: String) -> Result<Vec<u8>, TtsError> {
+        Ok(self.fetch_pieces(message)?.concat())
+    }
+
+    /// Requests every piece of `message` from translate_tts, returning one MP3 per piece.
+    pub fn fetch_pieces(&self, message: String) -> Result<Vec<Vec<u8>>, TtsError> {
+        let urls = self.urls(&message)?;
+        let mut pieces = Vec::with_capacity(urls.len());
+        for url in urls {
+            let rep = get(url).send()?;
+            if rep.status_code != 200 {
+                return Err(TtsError::http(rep.status_code as u16, &rep.reason_phrase));
+            }
+            pieces.push(rep.into_bytes());
         }
+
+        Ok(pieces)
+    }
+
+    fn urls(&self, message: &str) -> Result<Vec<String>, TtsError> {
+        let pieces = tokenizer::tokenize(message, MAX_CHARS);
+        if pieces.is_empty() {
+            return Err(TtsError::EmptyText);
+        }
+
         let language = self.language.as_code();
-        let text = EncodedFragment::fragmenter(&message)?;
-        let rep = get(format!("https://translate.google.{}/translate_tts?ie=UTF-8&q={}&tl={}&total=1&idx=0&textlen={}&tl={}&client=tw-ob", self.tld, text.encoded, language, len, language))
-          .send()
-          .map_err(|e| format!("{}", e))?;
-        let mut file = File::create(path)?;
-        let bytes = rep.as_bytes();
-        let _ = file.write_all(bytes)?;
+        let total = pieces.len();
+        pieces
+            .iter()
+            .enumerate()
+            .map(|(idx, piece)| {
+                let len = piece.chars().count();
+                let text = EncodedFragment::fragmenter(piece).map_err(TtsError::engine)?;
+                Ok(format!("https://translate.google.{}/translate_tts?ie=UTF-8&q={}&tl={}&total={}&idx={}&textlen={}&tl={}&ttsspeed={}&client=tw-ob", self.tld, text.encoded, language, total, idx, len, language, self.speed.as_param()))
+            })
+            .collect()
+    }
 
-        Ok(())
+    /// Async counterpart of [`GttsModel::fetch_pieces`].
+    #[cfg(feature = "async")]
+    pub async fn fetch_pieces_async(&self, message: String) -> AsyncResult<Vec<Vec<u8>>> {
+        let urls = self.urls(&message)?;
+        let mut pieces = Vec::with_capacity(urls.len());
+        for url in urls {
+            let rep = reqwest::get(url).await?.error_for_status()?;
+            pieces.push(rep.bytes().await?.to_vec());
+        }
+
+        Ok(pieces)
+    }
+}
+
+/// Writes the MP3 pieces straight to `path` when it names an MP3 and `volume`
+/// leaves them as they are, otherwise decodes them and encodes the format it names.
+fn write_pieces(pieces: Vec<Vec<u8>>, path: &Path, volume: f32) -> Result<(), TtsError> {
+    match OutputFormat::from_path(path) {
+        Some(OutputFormat::Mp3 { .. }) if volume == 1.0 => {
+            let mut file = File::create(path)?;
+            file.write_all(&pieces.concat())?;
+            Ok(())
+        }
+        Some(format @ OutputFormat::Mp3 { .. }) if !format.is_encodable() => Err(
+            TtsError::not_supported("changing the volume of an MP3 without the `mp3` feature"),
+        ),
+        format => encode::save(
+            &decode_pieces(pieces, volume)?,
+            format.unwrap_or_default(),
+            path,
+        ),
     }
 }
 
+/// Decodes every MP3 piece and joins their samples into one buffer, scaled by `volume`.
+fn decode_pieces(pieces: Vec<Vec<u8>>, volume: f32) -> Result<SynthesizedAudio<f32>, TtsError> {
+    let mut joined: Option<SynthesizedAudio<f32>> = None;
+    for piece in pieces {
+        let decoded = decode_audio(piece)?;
+        match &mut joined {
+            Some(x) => {
+                x.duration = x.duration.zip(decoded.duration).map(|(a, b)| a + b);
+                x.data.extend(decoded.data);
+            }
+            None => joined = Some(decoded),
+        }
+    }
+
+    let mut joined = joined.ok_or(TtsError::EmptyText)?;
+    for sample in &mut joined.data {
+        *sample *= volume;
+    }
+    Ok(joined)
+}
+
 impl Default for GttsModel {
     fn default() -> Self {
         return Self::new(1.0, languages::Languages::English, String::from("com"));
@@ -52,8 +192,118 @@ impl Default for GttsModel {
 
 impl NaturalModelTrait for GttsModel {
     type SynthesizeType = f32;
-    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        let _ = self.generate(message, path)?;
+    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), TtsError> {
+        self.generate(message, path)?;
         did_save(path)
     }
+
+    fn synthesize_to_memory(
+        &mut self,
+        message: String,
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
+        decode_pieces(self.fetch_pieces(message)?, self.gain())
+    }
+
+    /// The volume is applied along with [`GttsModel::volume`], translate_tts
+    /// has no finer control over rate than [`Speed`] and none over pitch.
+    fn set_prosody(&mut self, prosody: &Prosody) -> Result<Prosody, TtsError> {
+        self.prosody_gain = crate::audio::db_to_gain(prosody.volume);
+        Ok(Prosody {
+            volume: 0.0,
+            ..*prosody
+        })
+    }
+
+    /// One voice per language, plus the regional accents in [`ACCENTS`].
+    fn voices(&self) -> Result<Vec<Voice>, TtsError> {
+        let mut voices = Vec::new();
+        for language in languages::Languages::ALL {
+            let code = language.as_code();
+            let accents: Vec<_> = ACCENTS.iter().filter(|x| x.1 == code).collect();
+            if accents.is_empty() {
+                // Javanese is `jw` to translate_tts but `jv` in BCP-47.
+                let tag = if code == "jw" { "jv" } else { code };
+                voices.push(Voice::new(code, crate::Model::Gtts, tag));
+            } else {
+                voices.extend(
+                    accents
+                        .into_iter()
+                        .map(|(tag, _, _)| Voice::new(*tag, crate::Model::Gtts, *tag)),
+                );
+            }
+        }
+
+        Ok(voices)
+    }
+
+    fn configuration(&self) -> Option<String> {
+        Some(format!(
+            "{}\n{}\n{:?}\n{}",
+            self.language.as_code(),
+            self.tld,
+            self.speed,
+            self.gain()
+        ))
+    }
+
+    fn language(&self) -> Option<String> {
+        let code = self.language.as_code();
+        let accent = ACCENTS.iter().find(|x| x.1 == code && x.2 == self.tld);
+        Some(match accent {
+            Some((tag, _, _)) => tag.to_string(),
+            None if code == "jw" => "jv".to_string(),
+            None => code.to_string(),
+        })
+    }
+
+    fn set_voice(&mut self, voice: &Voice) -> Result<(), TtsError> {
+        match ACCENTS.iter().find(|x| x.0 == voice.id) {
+            Some((_, language, tld)) => {
+                self.language = language
+                    .parse()
+                    .map_err(|_| TtsError::UnknownVoice(voice.id.clone()))?;
+                self.tld = tld.to_string();
+            }
+            None => {
+                self.language = voice
+                    .id
+                    .parse()
+                    .map_err(|_| TtsError::UnknownVoice(voice.id.clone()))?;
+                self.tld = String::from("com");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncNaturalModelTrait for GttsModel {
+    fn save<'a>(&'a self, message: String, path: &'a Path) -> BoxFuture<'a, AsyncResult<()>> {
+        Box::pin(async move {
+            let pieces = self.fetch_pieces_async(message).await?;
+            let path = path.to_path_buf();
+            let volume = self.gain();
+            tokio::task::spawn_blocking(move || write_pieces(pieces, &path, volume)).await?
+        })
+    }
+
+    fn synthesize_to_memory(
+        &self,
+        message: String,
+    ) -> BoxFuture<'_, AsyncResult<SynthesizedAudio<f32>>> {
+        Box::pin(async move {
+            let pieces = self.fetch_pieces_async(message).await?;
+            let volume = self.gain();
+            tokio::task::spawn_blocking(move || decode_pieces(pieces, volume)).await?
+        })
+    }
+
+    fn language(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(std::future::ready(NaturalModelTrait::language(self)))
+    }
+
+    fn configuration(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(std::future::ready(NaturalModelTrait::configuration(self)))
+    }
 }
@@ -0,0 +1,75 @@
+/// Punctuation that always ends a piece.
+const HARD_BREAKS: &[char] = &[
+    '?', '!', '？', '！', '¡', '¿', '(', ')', '[', ']', '…', '‥', '،', ';', '—', '。', '，', '、',
+    '：', '\n',
+];
+
+/// Punctuation that only ends a piece when followed by whitespace, so "3.5" and "10:30" survive.
+const SOFT_BREAKS: &[char] = &['.', ',', ':'];
+
+/// Splits `text` into pieces of at most `max_chars` characters, the way gTTS does.
+///
+/// The text is first cut on punctuation, pieces that are still too long are
+/// cut on the last whitespace that fits (or hard cut if there is none), and
+/// neighbouring pieces are then merged back together as long as they fit so
+/// that as few requests as possible are made.
+pub fn tokenize(text: &str, max_chars: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    for token in split_punctuation(text) {
+        minimize(&token, max_chars, &mut pieces);
+    }
+
+    let mut merged: Vec<String> = Vec::new();
+    for piece in pieces {
+        match merged.last_mut() {
+            Some(last) if last.chars().count() + 1 + piece.chars().count() <= max_chars => {
+                last.push(' ');
+                last.push_str(&piece);
+            }
+            _ => merged.push(piece),
+        }
+    }
+    merged
+}
+
+fn split_punctuation(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        current.push(c);
+        let soft = SOFT_BREAKS.contains(&c) && chars.peek().is_none_or(|x| x.is_whitespace());
+        if soft || HARD_BREAKS.contains(&c) {
+            tokens.push(std::mem::take(&mut current));
+        }
+    }
+    tokens.push(current);
+
+    tokens
+        .into_iter()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+fn minimize(token: &str, max_chars: usize, pieces: &mut Vec<String>) {
+    let mut rest = token.trim();
+    while rest.chars().count() > max_chars {
+        // Byte index just past the last character that fits.
+        let limit = rest
+            .char_indices()
+            .nth(max_chars)
+            .map_or(rest.len(), |(i, _)| i);
+        let cut = match rest[..limit].rfind(char::is_whitespace) {
+            Some(i) if i > 0 => i,
+            _ => limit,
+        };
+        pieces.push(rest[..cut].trim().to_string());
+        rest = rest[cut..].trim_start();
+    }
+
+    if !rest.is_empty() {
+        pieces.push(rest.to_string());
+    }
+}
@@ -1,8 +1,10 @@
-pub mod bs1770;
 pub mod utils;
 
-use super::{did_save, NaturalModelTrait, SynthesizedAudio};
-use crate::TtsError;
+use super::{did_save, NaturalModelTrait, SynthesizedAudio, Voice};
+use crate::{
+    audio::encode::{self, OutputFormat},
+    TtsError,
+};
 use candle_core::{DType, Device, IndexOp, Tensor};
 use candle_nn::VarBuilder;
 use candle_transformers::{
@@ -16,10 +18,12 @@ use derive_builder::Builder;
 use hf_hub::api::sync::Api;
 use hound::WavSpec;
 use rand::{distributions::Distribution, SeedableRng};
-use std::{error::Error, io::Write, path::PathBuf};
+use std::path::{Path, PathBuf};
 use utils::*;
 
 const MODEL_NAME: &str = "lmz/candle-metavoice";
+/// Voice id standing for the speaker embedding shipped with the model.
+const DEFAULT_VOICE: &str = "default";
 
 #[derive(Builder, Clone, Debug, PartialEq)]
 pub struct MetaModelOptions {
@@ -61,7 +65,7 @@ pub struct MetaModel {
 }
 
 impl MetaModel {
-    pub fn new(options: MetaModelOptions) -> Result<Self, Box<dyn Error>> {
+    pub fn new(options: MetaModelOptions) -> Result<Self, TtsError> {
         use tracing_chrome::ChromeLayerBuilder;
         use tracing_subscriber::prelude::*;
 
@@ -113,10 +117,10 @@ impl MetaModel {
         });
     }
 
-    pub fn generate(&mut self, prompt: String) -> Result<SynthesizedAudio<f32>, Box<dyn Error>> {
+    pub fn generate(&mut self, prompt: String) -> Result<SynthesizedAudio<f32>, TtsError> {
         let second_stage_vb = unsafe {
             VarBuilder::from_mmaped_safetensors(
-                &[self.second_stage_weights.clone()],
+                std::slice::from_ref(&self.second_stage_weights),
                 self.dtype,
                 &self.device,
             )?
@@ -132,7 +136,7 @@ impl MetaModel {
 
         let encodec_vb = unsafe {
             VarBuilder::from_mmaped_safetensors(
-                &[self.encodec_weights.clone()],
+                std::slice::from_ref(&self.encodec_weights),
                 self.dtype,
                 &encodec_device,
             )?
@@ -142,6 +146,13 @@ impl MetaModel {
 
         let fs_tokenizer = get_fs_tokenizer(self.first_stage_meta.clone())?;
         let prompt_tokens = fs_tokenizer.encode(&prompt)?;
+        // The second stage sees the prompt and its audio in one block, so
+        // leave at least half of it for the audio.
+        let limit = second_stage_config.block_size / 2;
+        if prompt_tokens.len() > limit {
+            let length = prompt_tokens.len();
+            return Err(TtsError::TextTooLong { length, limit });
+        }
         let mut tokens = prompt_tokens.clone();
 
         let api = Api::new()?;
@@ -155,7 +166,13 @@ impl MetaModel {
         let spk_emb = candle_core::safetensors::load(&spk_emb_file, &candle_core::Device::Cpu)?;
 
         let spk_emb = match spk_emb.get("spk_emb") {
-            None => return Err(TtsError::Tensor.into()),
+            None => {
+                let file = spk_emb_file.display();
+                return Err(TtsError::InvalidData(format!(
+                    "no spk_emb tensor in {}",
+                    file
+                )));
+            }
             Some(spk_emb) => spk_emb.to_dtype(self.dtype)?,
         };
 
@@ -176,15 +193,8 @@ impl MetaModel {
             let logits = logits.i((0, logits.dim(1)? - 1))?;
             //let logits = ((logits0 * self.guidance_scale)? + logits1 * (1. - self.guidance_scale))?;
             let logits = logits.to_dtype(self.dtype)?;
-            let next_token = match logits_processor.sample(&logits) {
-                Ok(x) => x,
-                Err(e) => {
-                    println!("{}", e.to_string());
-                    continue;
-                }
-            };
+            let next_token = logits_processor.sample(&logits)?;
             tokens.push(next_token);
-            std::io::stdout().flush()?;
             if next_token == 2048 {
                 break;
             }
@@ -223,7 +233,8 @@ impl MetaModel {
                 let logits = logits.i(step)?.to_dtype(DType::F32)?;
                 let logits = &(&logits / 1.0)?;
                 let prs = candle_nn::ops::softmax_last_dim(logits)?.to_vec1::<f32>()?;
-                let distr = rand::distributions::WeightedIndex::new(prs.as_slice())?;
+                let distr = rand::distributions::WeightedIndex::new(prs.as_slice())
+                    .map_err(|e| TtsError::Tensor(Box::new(e)))?;
                 let sample = distr.sample(&mut rng) as u32;
                 codes_.push(sample)
             }
@@ -236,50 +247,81 @@ impl MetaModel {
         //let tilted_encodec = adapters::TiltedEncodec::new(512);
         let codes = codes.i(0)?.to_vec2::<u32>()?;
         let (_, audio_ids) = tilted_encodec.decode(&codes);
-        let audio_ids = Tensor::new(audio_ids, &encodec_device)
-            .unwrap()
-            .unsqueeze(0)
-            .unwrap();
+        let audio_ids = Tensor::new(audio_ids, &encodec_device)?.unsqueeze(0)?;
 
         let pcm = encodec_model.decode(&audio_ids)?;
         let pcm = pcm.i(0)?.i(0)?.to_dtype(DType::F32)?;
-        let pcm = normalize_loudness(&pcm, 24_000, true)?;
-
-        let pcm = pcm.to_vec1::<f32>()?;
-        return Ok(SynthesizedAudio::new(
-            pcm,
-            super::Spec::Wav(WavSpec {
-                sample_rate: encodec_config.sampling_rate as u32,
-                channels: encodec_config.audio_channels as u16,
-                sample_format: hound::SampleFormat::Float,
-                bits_per_sample: encodec_config.sampling_rate as u16,
-            }),
-            None,
-        ));
+        let mut pcm = pcm.to_vec1::<f32>()?;
+        let spec = WavSpec {
+            sample_rate: encodec_config.sampling_rate as u32,
+            channels: encodec_config.audio_channels as u16,
+            sample_format: hound::SampleFormat::Float,
+            bits_per_sample: 32,
+        };
+        LOUDNESS.normalize(&mut pcm, spec.channels, spec.sample_rate);
+
+        return Ok(SynthesizedAudio::new(pcm, super::Spec::Wav(spec), None));
     }
 }
 
 impl Default for MetaModel {
+    /// Panics if the model can't be downloaded or loaded, see [`MetaModel::new`].
     fn default() -> Self {
-        return Self::new(MetaModelOptionsBuilder::default().build().unwrap()).unwrap();
+        return Self::new(MetaModelOptionsBuilder::default().build().unwrap())
+            .expect("failed to load lmz/candle-metavoice");
     }
 }
 
 impl NaturalModelTrait for MetaModel {
     type SynthesizeType = f32;
 
-    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        let data = self.synthesize(message, path)?;
-        let mut output = std::fs::File::create(&path)?;
-        write_pcm_as_wav(&mut output, &data.data, 24_000 as u32)?;
+    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), TtsError> {
+        let data = self.synthesize_to_memory(message)?;
+        let format = OutputFormat::from_path(path).unwrap_or_default();
+        encode::save(&data, format, path)?;
         did_save(path)
     }
 
-    fn synthesize(
+    fn synthesize_to_memory(
         &mut self,
         message: String,
-        _path: &PathBuf,
-    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, Box<dyn Error>> {
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
         self.generate(message)
     }
+
+    /// The bundled speaker, plus the `spk_emb` file in use if there is one.
+    fn voices(&self) -> Result<Vec<Voice>, TtsError> {
+        let mut voices = vec![Voice::new(DEFAULT_VOICE, crate::Model::Meta, "en")];
+        if let Some(path) = &self.spk_emb {
+            voices.push(Voice::new(path.clone(), crate::Model::Meta, "en"));
+        }
+        Ok(voices)
+    }
+
+    fn language(&self) -> Option<String> {
+        Some("en".to_string())
+    }
+
+    fn configuration(&self) -> Option<String> {
+        Some(format!(
+            "{}\n{:?}\n{}\n{}\n{}\n{}\n{}",
+            self.repo_path,
+            self.spk_emb,
+            self.seed,
+            self.guidance_scale,
+            self.temperature,
+            self.max_tokens,
+            self.encodec_ntokens
+        ))
+    }
+
+    /// Any other id is taken as the path of a `spk_emb` safetensors file.
+    fn set_voice(&mut self, voice: &Voice) -> Result<(), TtsError> {
+        self.spk_emb = match voice.id.as_str() {
+            DEFAULT_VOICE => None,
+            path if Path::new(path).is_file() => Some(path.to_string()),
+            _ => return Err(TtsError::UnknownVoice(voice.id.clone())),
+        };
+        Ok(())
+    }
 }
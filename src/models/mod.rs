@@ -8,66 +8,199 @@ pub mod meta;
 pub mod msedge;
 #[cfg(feature = "parler")]
 pub mod parler;
+pub mod prosody;
 #[cfg(feature = "tts-rs")]
 pub mod tts_rs;
+pub mod voice;
+
+pub use prosody::Prosody;
+pub use voice::{Gender, Voice};
 
 use crate::{
-    utils::{play_wav_file, read_wav_file},
+    audio::{
+        concat::{self, Join, Joined},
+        resample, stretch,
+    },
+    playback::{Playback, Player},
+    text::{
+        sentences,
+        ssml::{Segment, Ssml},
+        timeline::Timeline,
+    },
     TtsError,
 };
 use hound::WavSpec;
 #[cfg(feature = "msedge")]
 use msedge_tts::tts::AudioMetadata;
-use rodio::{Sample, Sink};
-use std::{error::Error, fs::File, path::PathBuf};
+use rodio::Sample;
+use std::{fs::File, path::PathBuf, time::Duration};
+#[cfg(feature = "tts-rs")]
+use tts::Tts;
 
+/// What an engine plays its audio through: a [`Player`], or the platform
+/// itself for tts-rs.
+#[derive(Clone)]
 pub enum AudioHandler {
-    Sink(Sink),
-    Tts,
+    Playback(Playback),
+    #[cfg(feature = "tts-rs")]
+    Tts(Tts),
 }
 
-impl Clone for AudioHandler {
-    fn clone(&self) -> Self {
+impl AudioHandler {
+    /// Whether the sound is done or was stopped.
+    pub fn is_finished(&self) -> Result<bool, TtsError> {
         match self {
-            Self::Sink(_) => panic!("Sink cant be cloned"),
-            Self::Tts => Self::Tts,
+            Self::Playback(x) => Ok(x.is_finished()),
+            #[cfg(feature = "tts-rs")]
+            Self::Tts(x) => Ok(!x.is_speaking()?),
         }
     }
+
+    pub fn stop(&mut self) -> Result<(), TtsError> {
+        match self {
+            Self::Playback(x) => x.stop(),
+            #[cfg(feature = "tts-rs")]
+            Self::Tts(x) => {
+                x.stop()?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
-impl From<Sink> for AudioHandler {
-    fn from(value: Sink) -> Self {
-        Self::Sink(value)
+impl From<Playback> for AudioHandler {
+    fn from(value: Playback) -> Self {
+        Self::Playback(value)
     }
 }
 
+/// Chunks of audio yielded while synthesis is still running.
+pub type AudioStream<'a, T> = Box<dyn Iterator<Item = Result<SynthesizedAudio<T>, TtsError>> + 'a>;
+
 pub trait NaturalModelTrait {
     type SynthesizeType: Sample + Send + hound::Sample;
-    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), Box<dyn Error>>;
+    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), TtsError>;
+
+    /// Synthesizes `message` without touching the filesystem.
+    fn synthesize_to_memory(
+        &mut self,
+        message: String,
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError>;
+
+    /// Synthesizes `message` in chunks, so playback can begin before the whole
+    /// message is done. By default each sentence is synthesized separately.
+    fn synthesize_stream(
+        &mut self,
+        message: String,
+    ) -> Result<AudioStream<'_, Self::SynthesizeType>, TtsError> {
+        Ok(Box::new(
+            sentences(&message)
+                .into_iter()
+                .map(move |sentence| self.synthesize_to_memory(sentence)),
+        ))
+    }
+
+    /// The voices this engine can speak with. Engines without a notion of
+    /// voices return none.
+    fn voices(&self) -> Result<Vec<Voice>, TtsError> {
+        Ok(Vec::new())
+    }
+
+    /// Switches to `voice`, which should be one of [`NaturalModelTrait::voices`].
+    fn set_voice(&mut self, _voice: &Voice) -> Result<(), TtsError> {
+        Err(TtsError::not_supported("choosing a voice"))
+    }
+
+    /// The BCP-47 tag of the language the engine currently speaks, if known.
+    /// [`NaturalTts`](crate::NaturalTts) normalizes text in this language.
+    fn language(&self) -> Option<String> {
+        None
+    }
 
-    fn start(&mut self, message: String, path: &PathBuf) -> Result<AudioHandler, Box<dyn Error>> {
-        let _ = self.save(message.clone(), path);
-        Ok(AudioHandler::Sink(play_wav_file(&path)?))
+    /// Every setting that changes the audio, such as the voice and sampling
+    /// parameters. It is part of the [`Cache`](crate::cache::Cache) key, and
+    /// engines returning `None` are never cached.
+    fn configuration(&self) -> Option<String> {
+        None
+    }
+
+    /// Synthesizes an SSML document with the engine's own SSML support.
+    /// Engines without one return `None`, and the document is then rendered
+    /// segment by segment with [`render_segments`].
+    fn synthesize_ssml(
+        &mut self,
+        _ssml: &Ssml,
+    ) -> Result<Option<SynthesizedAudio<Self::SynthesizeType>>, TtsError> {
+        Ok(None)
+    }
+
+    /// Applies as much of `prosody` as the engine supports natively and returns
+    /// the rest, which [`NaturalTts`](crate::NaturalTts) then applies to the
+    /// synthesized audio. By default nothing is native.
+    fn set_prosody(&mut self, prosody: &Prosody) -> Result<Prosody, TtsError> {
+        Ok(*prosody)
+    }
+
+    /// Starts speaking `message` on `player`. Engines that play audio
+    /// themselves may ignore it.
+    fn start(
+        &mut self,
+        message: String,
+        _path: &PathBuf,
+        player: &Player,
+    ) -> Result<AudioHandler, TtsError> {
+        let synthesized = self.synthesize_to_memory(message)?;
+        let data: Vec<f32> = synthesized.data.iter().map(|x| x.to_f32()).collect();
+        let audio = SynthesizedAudio::new(data, synthesized.spec, synthesized.duration);
+
+        Ok(AudioHandler::Playback(player.play(&audio)?))
     }
 
     fn synthesize(
         &mut self,
         message: String,
-        path: &PathBuf,
-    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, Box<dyn Error>> {
-        let _ = self.save(message.clone(), path);
-        let rwf = read_wav_file(path)?;
-        Ok(rwf)
+        _path: &PathBuf,
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
+        self.synthesize_to_memory(message)
     }
 }
 
 pub enum Spec {
     Wav(WavSpec),
+    /// Decoded MSEdge audio, along with the format it was requested in and its metadata.
     #[cfg(feature = "msedge")]
-    Synthesized(String, Vec<AudioMetadata>),
+    Synthesized(WavSpec, String, Vec<AudioMetadata>),
     Unknown,
 }
 
+impl Spec {
+    /// The layout of the decoded samples, if known.
+    pub fn wav_spec(&self) -> Option<WavSpec> {
+        match self {
+            Spec::Wav(x) => Some(*x),
+            #[cfg(feature = "msedge")]
+            Spec::Synthesized(x, _, _) => Some(*x),
+            Spec::Unknown => None,
+        }
+    }
+
+    /// Like [`Spec::wav_spec`], for operations that can't go on without the layout.
+    pub fn layout(&self) -> Result<WavSpec, TtsError> {
+        self.wav_spec()
+            .ok_or_else(|| TtsError::not_supported("audio of unknown layout"))
+    }
+
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: u32) {
+        match self {
+            Spec::Wav(x) => x.sample_rate = sample_rate,
+            #[cfg(feature = "msedge")]
+            Spec::Synthesized(x, _, _) => x.sample_rate = sample_rate,
+            Spec::Unknown => {}
+        }
+    }
+}
+
 pub struct SynthesizedAudio<T: rodio::Sample> {
     pub spec: Spec,
     pub data: Vec<T>,
@@ -82,12 +215,205 @@ impl<T: rodio::Sample> SynthesizedAudio<T> {
             duration,
         };
     }
+
+    /// When each word and sentence is spoken. Empty unless the engine reports
+    /// it, which only MSEdge does.
+    pub fn timeline(&self) -> Timeline {
+        match &self.spec {
+            #[cfg(feature = "msedge")]
+            Spec::Synthesized(_, _, metadata) => Timeline::from(metadata.as_slice()),
+            _ => Timeline::default(),
+        }
+    }
+}
+
+impl SynthesizedAudio<f32> {
+    /// Resamples to `sample_rate` with [`resample::sinc`]. Word boundaries are
+    /// in time, so they stay where they were.
+    pub fn resample(&mut self, sample_rate: u32) -> Result<(), TtsError> {
+        let spec = self.spec.layout()?;
+        if sample_rate == 0 {
+            return Err(TtsError::not_supported("resampling to 0 Hz"));
+        }
+        if spec.sample_rate == sample_rate {
+            return Ok(());
+        }
+
+        self.data = resample::sinc(&self.data, spec.channels, spec.sample_rate, sample_rate);
+        self.duration = Some((self.data.len() / spec.channels.max(1) as usize) as i32);
+        self.spec.set_sample_rate(sample_rate);
+        Ok(())
+    }
+
+    /// Changes the tempo by `speed` without changing the pitch, 2.0 halving
+    /// the duration.
+    pub fn stretch(&mut self, speed: f32) -> Result<(), TtsError> {
+        self.pitch_and_tempo(0.0, speed)
+    }
+
+    /// Shifts the pitch by `semitones` without changing the duration.
+    pub fn shift_pitch(&mut self, semitones: f32) -> Result<(), TtsError> {
+        self.pitch_and_tempo(semitones, 1.0)
+    }
+
+    /// Shifts the pitch by `semitones` and changes the tempo by `speed` in one
+    /// pass, with [`stretch::pitch_and_tempo`]. Word boundaries move with the speech.
+    pub fn pitch_and_tempo(&mut self, semitones: f32, speed: f32) -> Result<(), TtsError> {
+        let spec = self.spec.layout()?;
+        if !(speed.is_finite() && speed > 0.0 && semitones.is_finite()) {
+            return Err(TtsError::not_supported(format!(
+                "a speed of {} and a pitch shift of {} semitones",
+                speed, semitones
+            )));
+        }
+        if speed == 1.0 && semitones == 0.0 {
+            return Ok(());
+        }
+
+        self.data = stretch::pitch_and_tempo(
+            &self.data,
+            spec.channels,
+            spec.sample_rate,
+            semitones,
+            speed,
+        );
+        self.duration = Some((self.data.len() / spec.channels.max(1) as usize) as i32);
+        #[cfg(feature = "msedge")]
+        if let Spec::Synthesized(_, _, metadata) = &mut self.spec {
+            for x in metadata {
+                x.offset = (x.offset as f64 / speed as f64) as u64;
+                x.duration = (x.duration as f64 / speed as f64) as u64;
+            }
+        }
+        Ok(())
+    }
+
+    /// Joins `pieces` of text and their audio with `join` between each two,
+    /// matching their sample rates and channels, see [`concat::concat`].
+    pub fn concat<S: Into<String>>(
+        pieces: impl IntoIterator<Item = (S, SynthesizedAudio<f32>)>,
+        join: Join,
+    ) -> Result<Joined, TtsError> {
+        concat::concat(pieces, join)
+    }
+
+    /// Stretches to last exactly `target`, keeping the pitch, as when speech
+    /// has to fill a fixed slot. Rounding is made up with silence or a cut at
+    /// the end.
+    pub fn fit_to_duration(&mut self, target: Duration) -> Result<(), TtsError> {
+        let spec = self.spec.layout()?;
+        let channels = spec.channels.max(1) as usize;
+        let frames = self.data.len() / channels;
+        let target = (target.as_secs_f64() * spec.sample_rate as f64).round() as usize;
+        if frames > 0 && target > 0 {
+            self.stretch((frames as f64 / target as f64) as f32)?;
+        }
+
+        self.data.resize(target * channels, 0.0);
+        self.duration = Some(target as i32);
+        Ok(())
+    }
+}
+
+/// The layout of a document made only of breaks, which has no speech to
+/// take one from.
+const SILENCE_SPEC: WavSpec = WavSpec {
+    channels: 1,
+    sample_rate: 24_000,
+    bits_per_sample: 32,
+    sample_format: hound::SampleFormat::Float,
+};
+
+/// Synthesizes each text segment on its own, applies its prosody and joins
+/// them with silence for the breaks. Voice changes are left to engines with
+/// native SSML support, so each one ignored here is added to `warnings`.
+pub fn render_segments<M>(
+    engine: &mut M,
+    segments: &[Segment],
+    warnings: &mut Vec<String>,
+) -> Result<SynthesizedAudio<f32>, TtsError>
+where
+    M: NaturalModelTrait<SynthesizeType = f32> + ?Sized,
+{
+    let mut spec: Option<WavSpec> = None;
+    let mut data = Vec::new();
+    let mut pending = Duration::ZERO;
+
+    for segment in segments {
+        let (text, prosody) = match segment {
+            Segment::Break(duration) => {
+                match spec {
+                    Some(x) => data.extend(silence(&x, *duration)),
+                    None => pending += *duration,
+                }
+                continue;
+            }
+            Segment::Text {
+                text,
+                prosody,
+                voice,
+            } => {
+                if let Some(voice) = voice {
+                    let warning = format!(
+                        "Ignored <voice name=\"{}\">, the engine has no native SSML support",
+                        voice
+                    );
+                    if !warnings.contains(&warning) {
+                        warnings.push(warning);
+                    }
+                }
+                (text, prosody)
+            }
+        };
+
+        let mut audio = engine.synthesize_to_memory(text.clone())?;
+        prosody.apply(&mut audio)?;
+        let chunk_spec = audio.spec.layout()?;
+        let target = *spec.get_or_insert(chunk_spec);
+        if chunk_spec.channels != target.channels {
+            return Err(TtsError::not_supported(
+                "segments with different channel counts",
+            ));
+        }
+
+        data.extend(silence(&target, std::mem::take(&mut pending)));
+        if chunk_spec.sample_rate == target.sample_rate {
+            data.extend(audio.data);
+        } else {
+            data.extend(resample::sinc(
+                &audio.data,
+                target.channels,
+                chunk_spec.sample_rate,
+                target.sample_rate,
+            ));
+        }
+    }
+
+    let spec = match spec {
+        Some(spec) => spec,
+        None if pending > Duration::ZERO => {
+            data = silence(&SILENCE_SPEC, pending);
+            SILENCE_SPEC
+        }
+        None => return Err(TtsError::EmptyText),
+    };
+    let duration = data.len() / spec.channels.max(1) as usize;
+    Ok(SynthesizedAudio::new(
+        data,
+        Spec::Wav(spec),
+        Some(duration as i32),
+    ))
+}
+
+fn silence(spec: &WavSpec, duration: Duration) -> Vec<f32> {
+    let frames = (duration.as_secs_f64() * spec.sample_rate as f64).round() as usize;
+    vec![0.0; frames * spec.channels as usize]
 }
 
-pub fn did_save(path: &PathBuf) -> Result<(), Box<dyn Error>> {
+pub fn did_save(path: &PathBuf) -> Result<(), TtsError> {
     let file = File::open(path);
     match file {
         Ok(_) => Ok(()),
-        Err(_) => Err(Box::new(TtsError::NotSaved)),
+        Err(_) => Err(TtsError::NotSaved(path.to_path_buf())),
     }
 }
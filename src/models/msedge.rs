@@ -1,9 +1,28 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use super::{AudioHandler, NaturalModelTrait, Spec, SynthesizedAudio};
-use crate::utils::{play_audio, save_wav};
+use super::{Gender, NaturalModelTrait, Prosody, Spec, SynthesizedAudio};
+#[cfg(feature = "async")]
+use crate::asynchronous::{AsyncNaturalModelTrait, AsyncResult, BoxFuture};
+use crate::{
+    audio::{
+        db_to_gain,
+        encode::{self, OutputFormat},
+    },
+    text::ssml::{to_markup, Segment, Ssml},
+    utils::decode_audio,
+    Model, TtsError,
+};
+use hound::{SampleFormat, WavSpec};
+#[cfg(feature = "async")]
+use msedge_tts::tts::client::connect_async;
 use msedge_tts::{
-    tts::{client::connect, SpeechConfig as OtherConfig},
+    tts::{
+        client::{connect, SynthesizedAudio as MSEdgeAudio},
+        SpeechConfig as OtherConfig,
+    },
     voice::{get_voices_list, Voice},
 };
 
@@ -25,56 +44,449 @@ impl MSEdgeModel {
 }
 
 impl Default for MSEdgeModel {
+    /// Speaks with `en-US-AriaNeural`, without asking the service for its voices.
     fn default() -> Self {
-        let voice = get_voices_list().unwrap();
-        return Self::new(SpeechConfig::from(voice.first().unwrap()));
+        return Self::new(SpeechConfig {
+            voice_name: voice_name("en-US-AriaNeural"),
+            audio_format: "audio-24khz-48kbitrate-mono-mp3".to_string(),
+            pitch: 0,
+            rate: 0,
+            volume: 0,
+        });
     }
 }
 
 impl NaturalModelTrait for MSEdgeModel {
     type SynthesizeType = f32;
-    fn start(
+
+    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), TtsError> {
+        let mut tts = connect()?;
+        let audio = tts.synthesize(message.as_str(), &self.config.as_msedge())?;
+        save_msedge(audio, path)
+    }
+
+    fn synthesize_to_memory(
         &mut self,
         message: String,
-        path: &PathBuf,
-    ) -> Result<AudioHandler, Box<dyn std::error::Error>> {
-        let synthesized = Self::synthesize(self, message, path)?;
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
+        let mut tts = connect()?;
+        let audio = tts.synthesize(message.as_str(), &self.config.as_msedge())?;
+        return decode_msedge(audio);
+    }
+
+    fn voices(&self) -> Result<Vec<super::Voice>, TtsError> {
+        Ok(get_voices_list()?.iter().map(to_voice).collect())
+    }
 
-        let rate = match self.config.rate {
-            x if x <= 0 => 16000,
-            x => x,
+    fn set_voice(&mut self, voice: &super::Voice) -> Result<(), TtsError> {
+        self.config.voice_name = voice_name(&voice.id);
+        Ok(())
+    }
+
+    fn configuration(&self) -> Option<String> {
+        let config = &self.config;
+        Some(format!(
+            "{}\n{}\n{}\n{}\n{}",
+            config.voice_name, config.audio_format, config.rate, config.pitch, config.volume
+        ))
+    }
+
+    /// The locale of the voice, read from either its long or short name.
+    fn language(&self) -> Option<String> {
+        let name = &self.config.voice_name;
+        let locale = match name.split_once('(') {
+            Some((_, rest)) => rest.split(',').next()?.trim().to_string(),
+            None => name.splitn(3, '-').take(2).collect::<Vec<_>>().join("-"),
         };
+        Some(locale).filter(|x| !x.is_empty())
+    }
+
+    /// Sends the markup straight to the service, one request per voice.
+    fn synthesize_ssml(
+        &mut self,
+        ssml: &Ssml,
+    ) -> Result<Option<SynthesizedAudio<Self::SynthesizeType>>, TtsError> {
+        let mut tts = connect()?;
+        let mut joined: Option<SynthesizedAudio<f32>> = None;
+
+        for (voice, segments) in by_voice(ssml.segments()) {
+            let mut config = self.config.as_msedge();
+            if let Some(voice) = voice {
+                config.voice_name = voice_name(&voice);
+            }
+            let audio = decode_msedge(tts.synthesize(&to_markup(&segments), &config)?)?;
+
+            joined = Some(match joined {
+                Some(mut joined) => {
+                    append(&mut joined, audio);
+                    joined
+                }
+                None => audio,
+            });
+        }
 
-        Ok(AudioHandler::Sink(match synthesized.spec {
-            Spec::Wav(x) => play_audio(&synthesized.data, x.sample_rate),
-            _ => play_audio(&synthesized.data, rate as u32),
-        }?))
+        Ok(joined)
     }
 
-    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-        let synthesized = Self::synthesize(self, message, path)?;
+    /// Rate and attenuation are sent to the service. Its pitch is an offset in
+    /// Hz, which depends on the voice, and its volume can't go above the
+    /// default, so pitch and boosts are left to DSP.
+    fn set_prosody(&mut self, prosody: &Prosody) -> Result<Prosody, TtsError> {
+        self.config.rate = ((prosody.rate - 1.0) * 100.0).round() as i32;
+        self.config.volume = ((db_to_gain(prosody.volume.min(0.0)) - 1.0) * 100.0).round() as i32;
+        self.config.pitch = 0;
+        Ok(Prosody {
+            rate: 1.0,
+            pitch: prosody.pitch,
+            volume: prosody.volume.max(0.0),
+        })
+    }
+}
+
+fn to_voice(voice: &Voice) -> super::Voice {
+    let id = voice
+        .short_name
+        .clone()
+        .unwrap_or_else(|| voice.name.clone());
+    let language = voice.locale.clone().unwrap_or_default();
+
+    let mut converted = super::Voice::new(id, Model::MSEdge, language);
+    if let Some(name) = &voice.friendly_name {
+        converted.name = name.clone();
+    }
+    converted.gender = match voice.gender.as_deref() {
+        Some("Female") => Some(Gender::Female),
+        Some("Male") => Some(Gender::Male),
+        Some("Neutral") => Some(Gender::Neutral),
+        _ => None,
+    };
+    if let Some(tag) = &voice.voice_tag {
+        converted.styles = tag
+            .content_categories
+            .iter()
+            .chain(tag.voice_personalities.iter())
+            .flatten()
+            .cloned()
+            .collect();
+    }
+    converted
+}
 
-        let rate = match self.config.rate {
-            x if x <= 0 => 16000,
-            x => x,
+/// Groups consecutive segments spoken by the same voice.
+fn by_voice(segments: Vec<Segment>) -> Vec<(Option<String>, Vec<Segment>)> {
+    let mut groups: Vec<(Option<String>, Vec<Segment>)> = Vec::new();
+    for segment in segments {
+        let voice = match &segment {
+            Segment::Text { voice, .. } => voice.clone(),
+            // Breaks stay with whatever voice came before them.
+            Segment::Break(_) => groups.last().and_then(|x| x.0.clone()),
         };
+        match groups.last_mut() {
+            Some((last, group)) if *last == voice => group.push(segment),
+            _ => groups.push((voice, vec![segment])),
+        }
+    }
+    groups
+}
 
-        let _ = save_wav(&synthesized.data, path, rate as u32);
-        Ok(())
+/// Appends `audio` to `joined`, shifting its metadata offsets to match.
+fn append(joined: &mut SynthesizedAudio<f32>, audio: SynthesizedAudio<f32>) {
+    if let (Spec::Synthesized(spec, _, metadata), Spec::Synthesized(_, _, more)) =
+        (&mut joined.spec, audio.spec)
+    {
+        // Offsets are in ticks of 100ns.
+        let frames = joined.data.len() as u64 / spec.channels.max(1) as u64;
+        let offset = frames * 10_000_000 / spec.sample_rate.max(1) as u64;
+        metadata.extend(more.into_iter().map(|mut x| {
+            x.offset += offset;
+            x
+        }));
     }
+    joined.duration = joined.duration.zip(audio.duration).map(|(a, b)| a + b);
+    joined.data.extend(audio.data);
+}
 
-    fn synthesize(
-        &mut self,
+/// Expands a short name like `en-US-AriaNeural` into the full name the
+/// service expects. Anything else is passed through as is.
+fn voice_name(id: &str) -> String {
+    match id.rsplit_once('-') {
+        Some((locale, name)) if locale.contains('-') && !id.contains(' ') => format!(
+            "Microsoft Server Speech Text to Speech Voice ({}, {})",
+            locale, name
+        ),
+        _ => id.to_string(),
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncNaturalModelTrait for MSEdgeModel {
+    fn save<'a>(&'a self, message: String, path: &'a Path) -> BoxFuture<'a, AsyncResult<()>> {
+        Box::pin(async move {
+            let mut tts = connect_async().await?;
+            let audio = tts
+                .synthesize(message.as_str(), &self.config.as_msedge())
+                .await?;
+
+            let path = path.to_path_buf();
+            tokio::task::spawn_blocking(move || save_msedge(audio, &path)).await?
+        })
+    }
+
+    fn synthesize_to_memory(
+        &self,
         message: String,
-        _path: &PathBuf,
-    ) -> Result<super::SynthesizedAudio<Self::SynthesizeType>, Box<dyn std::error::Error>> {
-        let mut tts = connect().unwrap();
-        let audio = tts.synthesize(message.as_str(), &self.config.as_msedge())?;
-        return Ok(SynthesizedAudio::new(
-            audio.audio_bytes.iter().map(|x| x.clone() as f32).collect(),
-            Spec::Synthesized(audio.audio_format, audio.audio_metadata),
-            None,
-        ));
+    ) -> BoxFuture<'_, AsyncResult<SynthesizedAudio<f32>>> {
+        Box::pin(async move {
+            let mut tts = connect_async().await?;
+            let audio = tts
+                .synthesize(message.as_str(), &self.config.as_msedge())
+                .await?;
+            tokio::task::spawn_blocking(move || decode_msedge(audio)).await?
+        })
+    }
+
+    fn language(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(std::future::ready(NaturalModelTrait::language(self)))
+    }
+
+    fn configuration(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(std::future::ready(NaturalModelTrait::configuration(self)))
+    }
+}
+
+/// Writes the encoded audio straight to `path` if it is in the format the
+/// extension names, otherwise decodes it and encodes that format.
+fn save_msedge(audio: MSEdgeAudio, path: &Path) -> Result<(), TtsError> {
+    let format: AudioFormat = audio.audio_format.parse().map_err(TtsError::InvalidData)?;
+    let output = OutputFormat::from_path(path).unwrap_or_default();
+    let as_is = match (format.container, format.codec, output) {
+        (_, Codec::Mp3, OutputFormat::Mp3 { .. }) => true,
+        // `.ogg` is as likely to hold Opus as Vorbis.
+        (Container::Ogg, Codec::Opus, OutputFormat::OggOpus { .. })
+        | (Container::Ogg, Codec::Opus, OutputFormat::OggVorbis { .. }) => true,
+        _ => false,
+    };
+    if as_is {
+        std::fs::write(path, &audio.audio_bytes)?;
+        return Ok(());
+    }
+
+    encode::save(&decode_msedge(audio)?, output, path)
+}
+
+/// Decodes the audio returned by MSEdge into PCM, according to its `audio_format`.
+fn decode_msedge(audio: MSEdgeAudio) -> Result<SynthesizedAudio<f32>, TtsError> {
+    let format: AudioFormat = audio.audio_format.parse().map_err(TtsError::InvalidData)?;
+
+    let decoded = match (format.container, format.codec) {
+        (Container::Audio | Container::Riff, Codec::Mp3 | Codec::Pcm) => {
+            decode_audio(audio.audio_bytes)?
+        }
+        (Container::Raw | Container::Riff, codec) => {
+            let bytes = match format.container {
+                Container::Riff => riff_data(&audio.audio_bytes)?,
+                _ => &audio.audio_bytes,
+            };
+            let data = decode_raw(bytes, codec, format.bits_per_sample.unwrap_or(16))?;
+            let duration = data.len() / format.channels.max(1) as usize;
+            SynthesizedAudio::new(
+                data,
+                Spec::Wav(WavSpec {
+                    channels: format.channels,
+                    sample_rate: format.sample_rate,
+                    bits_per_sample: 32,
+                    sample_format: SampleFormat::Float,
+                }),
+                Some(duration as i32),
+            )
+        }
+        #[cfg(feature = "opus")]
+        (Container::Ogg, Codec::Opus) => encode::decode_ogg_opus(&audio.audio_bytes)?,
+        #[cfg(not(feature = "opus"))]
+        (Container::Ogg, Codec::Opus) => {
+            let operation = format!("decoding {} without the `opus` feature", audio.audio_format);
+            return Err(TtsError::not_supported(operation));
+        }
+        (Container::Webm, _) => {
+            let operation = format!("decoding WebM ({})", audio.audio_format);
+            return Err(TtsError::not_supported(operation));
+        }
+        _ => {
+            let operation = format!("decoding {}", audio.audio_format);
+            return Err(TtsError::not_supported(operation));
+        }
+    };
+
+    let spec = decoded.spec.layout()?;
+    return Ok(SynthesizedAudio::new(
+        decoded.data,
+        Spec::Synthesized(spec, audio.audio_format, audio.audio_metadata),
+        decoded.duration,
+    ));
+}
+
+/// The payload of the `data` chunk of a RIFF/WAVE file.
+fn riff_data(bytes: &[u8]) -> Result<&[u8], TtsError> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(TtsError::InvalidData("not a RIFF/WAVE file".to_string()));
+    }
+
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let len = u32::from_le_bytes([
+            bytes[pos + 4],
+            bytes[pos + 5],
+            bytes[pos + 6],
+            bytes[pos + 7],
+        ]) as usize;
+        let start = pos + 8;
+        if id == b"data" {
+            return Ok(&bytes[start..(start + len).min(bytes.len())]);
+        }
+        // Chunks are padded to an even length.
+        pos = start + len + (len & 1);
+    }
+
+    Err(TtsError::InvalidData(
+        "no data chunk in RIFF/WAVE file".to_string(),
+    ))
+}
+
+fn decode_raw(bytes: &[u8], codec: Codec, bits_per_sample: u16) -> Result<Vec<f32>, TtsError> {
+    Ok(match (codec, bits_per_sample) {
+        (Codec::Mulaw, _) => bytes.iter().map(|x| mulaw_to_f32(*x)).collect(),
+        (Codec::Alaw, _) => bytes.iter().map(|x| alaw_to_f32(*x)).collect(),
+        (Codec::Pcm, 8) => bytes.iter().map(|x| (*x as f32 - 128.0) / 128.0).collect(),
+        (Codec::Pcm, 16) => bytes
+            .chunks_exact(2)
+            .map(|x| i16::from_le_bytes([x[0], x[1]]) as f32 / 32768.0)
+            .collect(),
+        (Codec::Pcm, 24) => bytes
+            .chunks_exact(3)
+            .map(|x| i32::from_le_bytes([0, x[0], x[1], x[2]]) as f32 / 2147483648.0)
+            .collect(),
+        (codec, bits) => {
+            let operation = format!("decoding {}-bit {:?}", bits, codec);
+            return Err(TtsError::not_supported(operation));
+        }
+    })
+}
+
+/// G.711 mu-law expansion.
+fn mulaw_to_f32(byte: u8) -> f32 {
+    let byte = !byte;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = (byte & 0x0f) as i32;
+    let magnitude = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+    let sample = if byte & 0x80 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    };
+    sample as f32 / 32768.0
+}
+
+/// G.711 A-law expansion.
+fn alaw_to_f32(byte: u8) -> f32 {
+    let byte = byte ^ 0x55;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = (byte & 0x0f) as i32;
+    let magnitude = match exponent {
+        0 => (mantissa << 4) + 8,
+        _ => ((mantissa << 4) + 0x108) << (exponent - 1),
+    };
+    let sample = if byte & 0x80 != 0 {
+        magnitude
+    } else {
+        -magnitude
+    };
+    sample as f32 / 32768.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    /// A bare MP3 stream, e.g. `audio-24khz-48kbitrate-mono-mp3`.
+    Audio,
+    Riff,
+    Raw,
+    Ogg,
+    Webm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Mp3,
+    Pcm,
+    Mulaw,
+    Alaw,
+    Opus,
+    Other,
+}
+
+/// A parsed MSEdge output format such as `audio-24khz-48kbitrate-mono-mp3`
+/// or `riff-16khz-16bit-mono-pcm`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub container: Container,
+    pub sample_rate: u32,
+    pub bits_per_sample: Option<u16>,
+    pub bitrate_kbps: Option<u32>,
+    pub channels: u16,
+    pub codec: Codec,
+}
+
+impl FromStr for AudioFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('-');
+        let container = match parts.next() {
+            Some("audio") => Container::Audio,
+            Some("riff") => Container::Riff,
+            Some("raw") => Container::Raw,
+            Some("ogg") => Container::Ogg,
+            Some("webm") => Container::Webm,
+            _ => return Err(format!("Unknown audio format: {}", s)),
+        };
+
+        let mut format = AudioFormat {
+            container,
+            sample_rate: 0,
+            bits_per_sample: None,
+            bitrate_kbps: None,
+            channels: 1,
+            codec: Codec::Other,
+        };
+
+        for part in parts {
+            if let Some(rate) = part.strip_suffix("khz") {
+                format.sample_rate = rate.parse::<u32>().map_err(|e| e.to_string())? * 1000;
+            } else if let Some(rate) = part.strip_suffix("hz") {
+                format.sample_rate = rate.parse::<u32>().map_err(|e| e.to_string())?;
+            } else if let Some(bitrate) = part.strip_suffix("kbitrate") {
+                format.bitrate_kbps = Some(bitrate.parse::<u32>().map_err(|e| e.to_string())?);
+            } else if let Some(bits) = part.strip_suffix("bit") {
+                format.bits_per_sample = Some(bits.parse::<u16>().map_err(|e| e.to_string())?);
+            } else {
+                match part {
+                    "mono" => format.channels = 1,
+                    "stereo" => format.channels = 2,
+                    "mp3" => format.codec = Codec::Mp3,
+                    "pcm" => format.codec = Codec::Pcm,
+                    "mulaw" => format.codec = Codec::Mulaw,
+                    "alaw" => format.codec = Codec::Alaw,
+                    "opus" => format.codec = Codec::Opus,
+                    _ => {}
+                }
+            }
+        }
+
+        if format.sample_rate == 0 {
+            return Err(format!("No sample rate in audio format: {}", s));
+        }
+
+        Ok(format)
     }
 }
 
@@ -1,17 +1,38 @@
 pub mod model;
-use super::{did_save, NaturalModelTrait, SynthesizedAudio};
+use super::{did_save, AudioStream, NaturalModelTrait, Spec, SynthesizedAudio, Voice};
+use crate::{
+    audio::{
+        encode::{self, OutputFormat},
+        loudness::LoudnessStream,
+    },
+    TtsError,
+};
 use candle_core::{DType, Device, IndexOp, Tensor};
 use candle_nn::VarBuilder;
+use candle_transformers::generation::LogitsProcessor;
 use derive_builder::Builder;
 use hf_hub::api::sync::Api;
 use hound::WavSpec;
 use model::*;
-use std::{error::Error, path::PathBuf};
+use std::path::PathBuf;
 use tokenizers::Tokenizer;
 
 use super::meta::utils::*;
 
 const MODEL_NAME: &str = "parler-tts/parler-tts-mini-v1";
+const MAX_STEPS: usize = 512;
+/// Frames decoded ahead of each streamed chunk so the decoder has context at the seam.
+const CONTEXT_FRAMES: usize = 8;
+/// Frames decoded past the end of each streamed chunk, which the decoder looks
+/// ahead to as well, and decoded again at the start of the next one.
+const LOOKAHEAD_FRAMES: usize = 8;
+/// Speakers the v1 checkpoints were trained to keep consistent when named in the description.
+const SPEAKERS: &[&str] = &[
+    "Laura", "Gary", "Jon", "Lea", "Karen", "Rick", "Brenda", "David", "Eileen", "Jordan", "Mike",
+    "Yann", "Joy", "James", "Eric", "Lauren", "Rose", "Will", "Jason", "Aaron", "Naomie", "Alisa",
+    "Patrick", "Jerry", "Tina", "Jenna", "Bill", "Tom", "Carol", "Barbara", "Rebecca", "Anna",
+    "Bruce", "Emily",
+];
 
 #[derive(Builder, Clone, Default)]
 #[builder(setter(into))]
@@ -29,11 +50,15 @@ pub struct ParlerModelOptions {
     seed: u64,
     #[builder(default = "MODEL_NAME.to_string()")]
     model_name: String,
+    /// Frames generated before each chunk of [`ParlerModel::stream`] is decoded.
+    #[builder(default = "32")]
+    stream_chunk_frames: usize,
 }
 
 #[derive(Clone)]
 pub struct ParlerModel {
     device: Device,
+    model_name: String,
     config: Config,
     model: Model,
     description: String,
@@ -41,10 +66,11 @@ pub struct ParlerModel {
     top_p: Option<f64>,
     seed: u64,
     tokenizer: Tokenizer,
+    stream_chunk_frames: usize,
 }
 
 impl ParlerModel {
-    pub fn new(options: ParlerModelOptions) -> Result<Self, Box<dyn Error>> {
+    pub fn new(options: ParlerModelOptions) -> Result<Self, TtsError> {
         use tracing_chrome::ChromeLayerBuilder;
         use tracing_subscriber::prelude::*;
 
@@ -62,7 +88,7 @@ impl ParlerModel {
         let revision = "main".to_string();
 
         let repo = api.repo(hf_hub::Repo::with_revision(
-            options.model_name,
+            options.model_name.clone(),
             hf_hub::RepoType::Model,
             revision.clone(),
         ));
@@ -81,10 +107,11 @@ impl ParlerModel {
 
         let tokenizer = repo.get("tokenizer.json")?;
 
-        let tokenizer = Tokenizer::from_file(tokenizer).unwrap();
+        let tokenizer = Tokenizer::from_file(tokenizer).map_err(TtsError::Tokenizer)?;
 
         return Ok(Self {
             device,
+            model_name: options.model_name,
             config,
             top_p: options.top_p,
             description: options.description,
@@ -92,36 +119,49 @@ impl ParlerModel {
             tokenizer,
             seed: options.seed,
             temperature: options.temperature,
+            stream_chunk_frames: options.stream_chunk_frames.max(1),
         });
     }
 
-    pub fn generate(&mut self, message: String) -> Result<SynthesizedAudio<f32>, Box<dyn Error>> {
+    fn tokens(&self, message: String) -> Result<(Tensor, Tensor), TtsError> {
         let description_tokens = self
             .tokenizer
             .encode(self.description.clone(), true)
-            .unwrap()
+            .map_err(TtsError::Tokenizer)?
             .get_ids()
             .to_vec();
         let description_tokens = Tensor::new(description_tokens, &self.device)?.unsqueeze(0)?;
         let prompt_tokens = self
             .tokenizer
             .encode(message, true)
-            .unwrap()
+            .map_err(TtsError::Tokenizer)?
             .get_ids()
             .to_vec();
         let prompt_tokens = Tensor::new(prompt_tokens, &self.device)?.unsqueeze(0)?;
+        Ok((prompt_tokens, description_tokens))
+    }
 
-        let lp = candle_transformers::generation::LogitsProcessor::new(
-            self.seed,
-            Some(self.temperature),
-            self.top_p,
-        );
+    fn logits_processor(&self) -> LogitsProcessor {
+        LogitsProcessor::new(self.seed, Some(self.temperature), self.top_p)
+    }
+
+    fn spec(&self) -> WavSpec {
+        WavSpec {
+            sample_rate: self.config.audio_encoder.sampling_rate,
+            channels: 1,
+            sample_format: hound::SampleFormat::Float,
+            bits_per_sample: 32,
+        }
+    }
+
+    pub fn generate(&mut self, message: String) -> Result<SynthesizedAudio<f32>, TtsError> {
+        let (prompt_tokens, description_tokens) = self.tokens(message)?;
+        let lp = self.logits_processor();
 
         let codes = self
             .model
-            .generate(&prompt_tokens, &description_tokens, lp, 512)?;
+            .generate(&prompt_tokens, &description_tokens, lp, MAX_STEPS)?;
         let codes = codes.to_dtype(DType::I64)?;
-        codes.save_safetensors("codes", "out.safetensors")?;
         let codes = codes.unsqueeze(0)?;
 
         let pcm = self
@@ -129,23 +169,121 @@ impl ParlerModel {
             .audio_encoder
             .decode_codes(&codes.to_device(&self.device)?)?;
         let pcm = pcm.i((0, 0))?;
-        let pcm = normalize_loudness(&pcm, 24_000, true)?;
-        let pcm = pcm.to_vec1::<f32>()?;
+        let mut pcm = pcm.to_vec1::<f32>()?;
+        let spec = self.spec();
+        LOUDNESS.normalize(&mut pcm, spec.channels, spec.sample_rate);
+
+        return Ok(SynthesizedAudio::new(pcm, Spec::Wav(spec), None));
+    }
+
+    /// Generates `message`, decoding the codes in windows of
+    /// `stream_chunk_frames` while the decoder keeps running.
+    ///
+    /// Loudness is measured on what has been decoded so far, so the first
+    /// chunks can be a few dB off the level [`ParlerModel::generate`] gives,
+    /// see [`LoudnessStream`].
+    pub fn stream(&mut self, message: String) -> Result<ParlerStream<'_>, TtsError> {
+        let (prompt_tokens, description_tokens) = self.tokens(message)?;
+        let lp = self.logits_processor();
+        let spec = self.spec();
+        let generation =
+            self.model
+                .start_generation(&prompt_tokens, &description_tokens, lp, MAX_STEPS)?;
 
-        return Ok(SynthesizedAudio::new(
+        Ok(ParlerStream {
+            generation,
+            device: self.device.clone(),
+            spec,
+            chunk_frames: self.stream_chunk_frames,
+            emitted: 0,
+            loudness: LOUDNESS.stream(spec.channels, spec.sample_rate),
+            finished: false,
+        })
+    }
+}
+
+/// Chunks of audio decoded from a running [`Generation`], see [`ParlerModel::stream`].
+pub struct ParlerStream<'a> {
+    generation: Generation<'a>,
+    device: Device,
+    spec: WavSpec,
+    chunk_frames: usize,
+    emitted: usize,
+    loudness: LoudnessStream,
+    finished: bool,
+}
+
+impl ParlerStream<'_> {
+    /// Decodes the frames generated since the last chunk, holding back the
+    /// last few until the frames after them are in, unless `last`.
+    fn decode(&mut self, frames: usize, last: bool) -> Result<SynthesizedAudio<f32>, TtsError> {
+        let end = match last {
+            true => frames,
+            false => frames.saturating_sub(LOOKAHEAD_FRAMES),
+        };
+        let mut pcm = Vec::new();
+        if end > self.emitted {
+            let start = self.emitted.saturating_sub(CONTEXT_FRAMES);
+            let codes = self.generation.codes(start..frames)?;
+            let codes = codes.to_dtype(DType::I64)?.unsqueeze(0)?;
+            let decoded = self
+                .generation
+                .audio_encoder()
+                .decode_codes(&codes.to_device(&self.device)?)?;
+            let decoded = decoded.i((0, 0))?.to_vec1::<f32>()?;
+
+            // Keep the samples of the new frames, dropping those of the
+            // context before them and the lookahead after them.
+            let hop = decoded.len() / (frames - start);
+            pcm = decoded[(self.emitted - start) * hop..(end - start) * hop].to_vec();
+            self.emitted = end;
+        }
+
+        let mut pcm = self.loudness.push(&pcm);
+        if last {
+            pcm.extend(self.loudness.finish());
+        }
+        let duration = pcm.len() / self.spec.channels.max(1) as usize;
+        Ok(SynthesizedAudio::new(
             pcm,
-            super::Spec::Wav(WavSpec {
-                sample_rate: self.config.audio_encoder.sampling_rate,
-                channels: 1,
-                sample_format: hound::SampleFormat::Float,
-                bits_per_sample: self.config.audio_encoder.model_bitrate as u16,
-            }),
-            None,
-        ));
+            Spec::Wav(self.spec),
+            Some(duration as i32),
+        ))
+    }
+}
+
+impl Iterator for ParlerStream<'_> {
+    type Item = Result<SynthesizedAudio<f32>, TtsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let running = match self.generation.step() {
+                Ok(x) => x,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e.into()));
+                }
+            };
+            self.finished = !running;
+
+            let frames = self.generation.frames();
+            if self.finished {
+                return match self.decode(frames, true) {
+                    Ok(x) if x.data.is_empty() => None,
+                    chunk => Some(chunk),
+                };
+            }
+            if frames >= self.emitted + self.chunk_frames + LOOKAHEAD_FRAMES {
+                return Some(self.decode(frames, false));
+            }
+        }
+
+        None
     }
 }
 
 impl Default for ParlerModel {
+    /// Panics if the model can't be downloaded or loaded, see [`ParlerModel::new`].
     fn default() -> Self {
         let desc = "A female speaker in fast calming voice in a quiet environment".to_string();
         let model = "parler-tts/parler-tts-mini-expresso".to_string();
@@ -156,20 +294,57 @@ impl Default for ParlerModel {
                 .build()
                 .unwrap(),
         )
-        .unwrap();
+        .expect("failed to load parler-tts/parler-tts-mini-expresso");
     }
 }
 
 impl NaturalModelTrait for ParlerModel {
     type SynthesizeType = f32;
-    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        let data = self.synthesize(message, path)?;
-        let mut output = std::fs::File::create(&path)?;
-        write_pcm_as_wav(
-            &mut output,
-            &data.data,
-            self.config.audio_encoder.sampling_rate,
-        )?;
+    fn save(&mut self, message: String, path: &PathBuf) -> Result<(), TtsError> {
+        let data = self.synthesize_to_memory(message)?;
+        let format = OutputFormat::from_path(path).unwrap_or_default();
+        encode::save(&data, format, path)?;
         did_save(path)
     }
+
+    fn synthesize_to_memory(
+        &mut self,
+        message: String,
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
+        self.generate(message)
+    }
+
+    fn synthesize_stream(
+        &mut self,
+        message: String,
+    ) -> Result<AudioStream<'_, Self::SynthesizeType>, TtsError> {
+        Ok(Box::new(self.stream(message)?))
+    }
+
+    fn voices(&self) -> Result<Vec<Voice>, TtsError> {
+        Ok(SPEAKERS
+            .iter()
+            .map(|x| Voice::new(*x, crate::Model::Parler, "en"))
+            .collect())
+    }
+
+    fn language(&self) -> Option<String> {
+        Some("en".to_string())
+    }
+
+    fn configuration(&self) -> Option<String> {
+        Some(format!(
+            "{}\n{}\n{}\n{:?}\n{}",
+            self.model_name, self.description, self.temperature, self.top_p, self.seed
+        ))
+    }
+
+    /// Replaces the description with one naming the speaker.
+    fn set_voice(&mut self, voice: &Voice) -> Result<(), TtsError> {
+        self.description = format!(
+            "{}'s voice is clear and expressive, with a moderate speed and pitch. The recording is of very high quality, with the speaker's voice sounding clear and very close up.",
+            voice.name
+        );
+        Ok(())
+    }
 }
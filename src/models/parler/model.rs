@@ -5,6 +5,7 @@ use candle_transformers::{
     models::{dac, t5},
     utils::repeat_kv,
 };
+use std::ops::Range;
 
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct DecoderConfig {
@@ -360,9 +361,23 @@ impl Model {
         &mut self,
         prompt_tokens: &Tensor,
         description_tokens: &Tensor,
-        mut lp: LogitsProcessor,
+        lp: LogitsProcessor,
         max_steps: usize,
     ) -> Result<Tensor> {
+        let mut generation =
+            self.start_generation(prompt_tokens, description_tokens, lp, max_steps)?;
+        while generation.step()? {}
+        generation.codes(0..generation.frames())
+    }
+
+    /// Prepares a [`Generation`] that can be advanced one decoder step at a time.
+    pub fn start_generation(
+        &mut self,
+        prompt_tokens: &Tensor,
+        description_tokens: &Tensor,
+        lp: LogitsProcessor,
+        max_steps: usize,
+    ) -> Result<Generation<'_>> {
         self.decoder.clear_kv_cache();
         self.text_encoder.clear_kv_cache();
         let encoded = self.text_encoder.forward(description_tokens)?;
@@ -372,59 +387,19 @@ impl Model {
         };
         let prompt_hidden_states = prompt_tokens.apply(&self.embed_prompts)?;
         let num_codebooks = self.decoder.num_codebooks;
-        let mut audio_tokens = vec![self.decoder_start_token_id; num_codebooks];
-        let mut all_audio_tokens = vec![vec![]; num_codebooks];
         let prompt_len = prompt_hidden_states.dim(1)?;
-        for step in 0..max_steps {
-            let input_ids = Tensor::from_slice(
-                audio_tokens.as_slice(),
-                (1, num_codebooks, 1),
-                prompt_tokens.device(),
-            )?;
-            let (prompt_hidden_states, pos) = if step == 0 {
-                (Some(&prompt_hidden_states), 0)
-            } else {
-                (None, step + prompt_len)
-            };
-            let causal_mask = if pos == 0 {
-                self.prepare_causal_mask(prompt_len + 1, prompt_len + 1, input_ids.device())?
-            } else {
-                self.prepare_causal_mask(1, pos + 1, input_ids.device())?
-            };
-            let logits = self.decoder.forward(
-                &input_ids,
-                prompt_hidden_states,
-                Some(&causal_mask),
-                &encoded,
-                None,
-                pos,
-            )?;
-            for (logit_idx, logit) in logits.iter().enumerate() {
-                if logit_idx > step {
-                    break;
-                }
-                if audio_tokens[logit_idx] != self.pad_token_id {
-                    let logit = logit.i((0, logit.dim(1)? - 1))?;
-                    let token = lp.sample(&logit)?;
-                    audio_tokens[logit_idx] = token
-                }
-            }
-            if audio_tokens.iter().all(|v| v == &self.pad_token_id) {
-                break;
-            }
-            for (cb_idx, &token) in audio_tokens.iter().enumerate() {
-                if token != self.decoder_start_token_id && token != self.pad_token_id {
-                    all_audio_tokens[cb_idx].push(token)
-                }
-            }
-        }
-
-        let min_len = all_audio_tokens.iter().map(|v| v.len()).min().unwrap_or(0);
-        all_audio_tokens.iter_mut().for_each(|v| {
-            v.resize(min_len, 0);
-        });
-        let all_audio_tokens = Tensor::new(all_audio_tokens, &Device::Cpu)?;
-        Ok(all_audio_tokens)
+        Ok(Generation {
+            audio_tokens: vec![self.decoder_start_token_id; num_codebooks],
+            all_audio_tokens: vec![vec![]; num_codebooks],
+            device: prompt_tokens.device().clone(),
+            model: self,
+            encoded,
+            prompt_hidden_states,
+            prompt_len,
+            lp,
+            step: 0,
+            max_steps,
+        })
     }
 
     fn prepare_causal_mask(&self, q_len: usize, kv_len: usize, device: &Device) -> Result<Tensor> {
@@ -442,3 +417,107 @@ impl Model {
         Tensor::from_slice(&mask, (q_len, kv_len), device)
     }
 }
+
+/// An in-progress [`Model::generate`], advanced one decoder step at a time so
+/// that the codes produced so far can be decoded while generation continues.
+pub struct Generation<'a> {
+    model: &'a mut Model,
+    encoded: Tensor,
+    prompt_hidden_states: Tensor,
+    prompt_len: usize,
+    lp: LogitsProcessor,
+    audio_tokens: Vec<u32>,
+    all_audio_tokens: Vec<Vec<u32>>,
+    device: Device,
+    step: usize,
+    max_steps: usize,
+}
+
+impl Generation<'_> {
+    /// Runs a single decoder step, returning `false` once every codebook has
+    /// reached the pad token or `max_steps` is exhausted.
+    pub fn step(&mut self) -> Result<bool> {
+        if self.step >= self.max_steps {
+            return Ok(false);
+        }
+
+        let step = self.step;
+        let num_codebooks = self.audio_tokens.len();
+        let input_ids = Tensor::from_slice(
+            self.audio_tokens.as_slice(),
+            (1, num_codebooks, 1),
+            &self.device,
+        )?;
+        let (prompt_hidden_states, pos) = if step == 0 {
+            (Some(&self.prompt_hidden_states), 0)
+        } else {
+            (None, step + self.prompt_len)
+        };
+        let causal_mask = if pos == 0 {
+            self.model.prepare_causal_mask(
+                self.prompt_len + 1,
+                self.prompt_len + 1,
+                input_ids.device(),
+            )?
+        } else {
+            self.model
+                .prepare_causal_mask(1, pos + 1, input_ids.device())?
+        };
+        let logits = self.model.decoder.forward(
+            &input_ids,
+            prompt_hidden_states,
+            Some(&causal_mask),
+            &self.encoded,
+            None,
+            pos,
+        )?;
+        self.step += 1;
+        for (logit_idx, logit) in logits.iter().enumerate() {
+            if logit_idx > step {
+                break;
+            }
+            if self.audio_tokens[logit_idx] != self.model.pad_token_id {
+                let logit = logit.i((0, logit.dim(1)? - 1))?;
+                let token = self.lp.sample(&logit)?;
+                self.audio_tokens[logit_idx] = token
+            }
+        }
+        if self
+            .audio_tokens
+            .iter()
+            .all(|v| v == &self.model.pad_token_id)
+        {
+            self.step = self.max_steps;
+            return Ok(false);
+        }
+        for (cb_idx, &token) in self.audio_tokens.iter().enumerate() {
+            if token != self.model.decoder_start_token_id && token != self.model.pad_token_id {
+                self.all_audio_tokens[cb_idx].push(token)
+            }
+        }
+        Ok(self.step < self.max_steps)
+    }
+
+    /// The number of frames for which every codebook has produced a token.
+    pub fn frames(&self) -> usize {
+        self.all_audio_tokens
+            .iter()
+            .map(|v| v.len())
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// The codes for `frames`, shaped `(num_codebooks, frames.len())` on the CPU device.
+    pub fn codes(&self, frames: Range<usize>) -> Result<Tensor> {
+        let codes: Vec<Vec<u32>> = self
+            .all_audio_tokens
+            .iter()
+            .map(|v| v[frames.clone()].to_vec())
+            .collect();
+        Tensor::new(codes, &Device::Cpu)
+    }
+
+    pub fn audio_encoder(&self) -> &dac::Model {
+        &self.model.audio_encoder
+    }
+}
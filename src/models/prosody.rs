@@ -0,0 +1,59 @@
+use super::SynthesizedAudio;
+use crate::{audio, TtsError};
+
+/// How fast, how high and how loud to speak, in units every engine shares.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Prosody {
+    /// Speaking rate as a multiplier, 2.0 being twice as fast.
+    pub rate: f32,
+    /// Pitch shift in semitones.
+    pub pitch: f32,
+    /// Gain in dB.
+    pub volume: f32,
+}
+
+impl Default for Prosody {
+    fn default() -> Self {
+        return Self {
+            rate: 1.0,
+            pitch: 0.0,
+            volume: 0.0,
+        };
+    }
+}
+
+impl Prosody {
+    pub fn new(rate: f32, pitch: f32, volume: f32) -> Self {
+        return Self {
+            rate,
+            pitch,
+            volume,
+        };
+    }
+
+    /// Applies `other` on top of this one, as when prosody elements are nested.
+    pub fn combine(&self, other: &Prosody) -> Prosody {
+        return Self {
+            rate: self.rate * other.rate,
+            pitch: self.pitch + other.pitch,
+            volume: self.volume + other.volume,
+        };
+    }
+
+    /// Whether this leaves the audio untouched.
+    pub fn is_neutral(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Applies the prosody to already synthesized audio.
+    pub fn apply(&self, audio: &mut SynthesizedAudio<f32>) -> Result<(), TtsError> {
+        if self.is_neutral() {
+            return Ok(());
+        }
+
+        audio.pitch_and_tempo(self.pitch, self.rate)?;
+        audio::apply_gain(&mut audio.data, self.volume);
+
+        Ok(())
+    }
+}
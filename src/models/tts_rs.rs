@@ -1,48 +1,121 @@
-use super::{AudioHandler, NaturalModelTrait, SynthesizedAudio};
-use crate::TtsError;
-use std::{error::Error, path::PathBuf};
+use super::{AudioHandler, Gender, NaturalModelTrait, Prosody, SynthesizedAudio, Voice};
+use crate::{playback::Player, Model, TtsError};
+use std::path::PathBuf;
 use tts::Tts;
 
 #[derive(Clone)]
 pub struct TtsModel(pub Tts);
 
 impl TtsModel {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
+    pub fn new() -> Result<Self, TtsError> {
         let def = Tts::default()?;
         return Ok(Self(def));
     }
 }
 
+/// Maps `fraction` in `[-1, 1]` onto a platform range, 0 being `normal`.
+fn scale(fraction: f32, min: f32, normal: f32, max: f32) -> f32 {
+    let fraction = fraction.clamp(-1.0, 1.0);
+    if fraction >= 0.0 {
+        normal + fraction * (max - normal)
+    } else {
+        normal + fraction * (normal - min)
+    }
+}
+
 impl Default for TtsModel {
+    /// Panics without a platform speech API, see [`TtsModel::new`].
     fn default() -> Self {
-        return Self::new().unwrap();
+        return Self::new().expect("no platform speech API");
     }
 }
 
 impl NaturalModelTrait for TtsModel {
     type SynthesizeType = f32;
-    fn save(&mut self, _message: String, _path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        Err(TtsError::NotSupported.into())
+    fn save(&mut self, _message: String, _path: &PathBuf) -> Result<(), TtsError> {
+        Err(TtsError::not_supported("saving to a file"))
     }
 
-    fn start(&mut self, message: String, _path: &PathBuf) -> Result<AudioHandler, Box<dyn Error>> {
-        let is_speaking = self.0.is_speaking();
-
-        if let Ok(speaking) = is_speaking {
-            if speaking {
-                return Ok(AudioHandler::Tts);
-            }
-        }
-
-        let _ = self.0.speak(message, false);
-        Ok(AudioHandler::Tts)
+    /// Speaks through the platform rather than `_player`.
+    fn start(
+        &mut self,
+        message: String,
+        _path: &PathBuf,
+        _player: &Player,
+    ) -> Result<AudioHandler, TtsError> {
+        // Interrupts whatever the platform is saying, like the other engines.
+        self.0.speak(message, true)?;
+        Ok(AudioHandler::Tts(self.0.clone()))
     }
 
-    fn synthesize(
+    fn synthesize_to_memory(
         &mut self,
         _message: String,
-        _path: &PathBuf,
-    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, Box<dyn Error>> {
-        Err(TtsError::NotSupported.into())
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
+        Err(TtsError::not_supported("synthesizing to memory"))
+    }
+
+    fn voices(&self) -> Result<Vec<Voice>, TtsError> {
+        Ok(self
+            .0
+            .voices()?
+            .into_iter()
+            .map(|x| {
+                let mut voice = Voice::new(x.id(), Model::TTS, x.language().to_string());
+                voice.name = x.name();
+                voice.gender = x.gender().map(|gender| match gender {
+                    tts::Gender::Female => Gender::Female,
+                    tts::Gender::Male => Gender::Male,
+                });
+                voice
+            })
+            .collect())
+    }
+
+    fn language(&self) -> Option<String> {
+        let voice = self.0.voice().ok().flatten()?;
+        Some(voice.language().to_string())
+    }
+
+    fn set_voice(&mut self, voice: &Voice) -> Result<(), TtsError> {
+        let found = self.0.voices()?.into_iter().find(|x| x.id() == voice.id);
+        match found {
+            Some(x) => Ok(self.0.set_voice(&x)?),
+            None => Err(TtsError::UnknownVoice(voice.id.clone())),
+        }
+    }
+
+    /// Every platform has its own units, so a doubling of the rate and an
+    /// octave of pitch are mapped to the top of their range. The audio is
+    /// played by the platform, so nothing is left for DSP.
+    fn set_prosody(&mut self, prosody: &Prosody) -> Result<Prosody, TtsError> {
+        let features = self.0.supported_features();
+        let tts = &mut self.0;
+
+        if features.rate {
+            let rate = scale(
+                prosody.rate.max(f32::EPSILON).log2(),
+                tts.min_rate(),
+                tts.normal_rate(),
+                tts.max_rate(),
+            );
+            tts.set_rate(rate)?;
+        }
+        if features.pitch {
+            let pitch = scale(
+                prosody.pitch / 12.0,
+                tts.min_pitch(),
+                tts.normal_pitch(),
+                tts.max_pitch(),
+            );
+            tts.set_pitch(pitch)?;
+        }
+        if features.volume {
+            let volume = (tts.normal_volume() * crate::audio::db_to_gain(prosody.volume))
+                .clamp(tts.min_volume(), tts.max_volume());
+            tts.set_volume(volume)?;
+        }
+
+        Ok(Prosody::default())
     }
 }
@@ -0,0 +1,55 @@
+use crate::Model;
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Gender {
+    Female,
+    Male,
+    Neutral,
+}
+
+/// A voice one of the engines can speak with, as returned by
+/// [`NaturalTts::list_voices`](crate::NaturalTts::list_voices).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Voice {
+    /// Identifies the voice within its engine, e.g. `en-US-AriaNeural` for MSEdge.
+    pub id: String,
+    pub name: String,
+    pub engine: Model,
+    /// BCP-47 language tag, e.g. `en-GB`.
+    pub language: String,
+    pub gender: Option<Gender>,
+    /// Content categories and personalities such as `News` or `Friendly`.
+    pub styles: Vec<String>,
+}
+
+impl Voice {
+    pub fn new(id: impl Into<String>, engine: Model, language: impl Into<String>) -> Self {
+        let id = id.into();
+        return Self {
+            name: id.clone(),
+            id,
+            engine,
+            language: language.into(),
+            gender: None,
+            styles: Vec::new(),
+        };
+    }
+
+    /// The id prefixed with its engine, e.g. `msedge:en-US-AriaNeural`, which
+    /// stays unique when several engines are registered.
+    pub fn qualified_id(&self) -> String {
+        format!("{}:{}", self.engine, self.id)
+    }
+
+    /// Whether `id` names this voice, either plainly or qualified with its engine.
+    pub fn matches(&self, id: &str) -> bool {
+        self.id == id || self.qualified_id() == id
+    }
+}
+
+impl Display for Voice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.language)
+    }
+}
@@ -0,0 +1,277 @@
+//! Audio output.
+//!
+//! A [`Player`] opens the output stream, which has to outlive every sound
+//! played on it, and keeps it open for as long as the player or any
+//! [`Playback`] it handed out is alive. Both are cheap to clone and can be
+//! shared between threads.
+use crate::{
+    models::SynthesizedAudio,
+    text::timeline::{Boundary, Timeline},
+    TtsError,
+};
+use rodio::{buffer::SamplesBuffer, source::SeekError, OutputStream, OutputStreamHandle, Sink};
+use std::{
+    sync::{mpsc, Arc, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+#[derive(Clone)]
+pub struct Player {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    handle: OutputStreamHandle,
+    /// Sounds that haven't finished yet, kept so they play to the end even
+    /// when every handle to them is dropped.
+    sinks: Mutex<Vec<Arc<Sink>>>,
+    /// Dropped with the last clone of the player, which closes the stream.
+    _close: mpsc::Sender<()>,
+}
+
+impl Player {
+    /// Opens the default output device.
+    ///
+    /// The stream can't move between threads on every platform, so it lives
+    /// on a thread of its own until the player is dropped.
+    pub fn new() -> Result<Self, TtsError> {
+        let (opened, opening) = mpsc::channel();
+        let (close, closed) = mpsc::channel::<()>();
+        thread::Builder::new()
+            .name("natural-tts-output".to_string())
+            .spawn(move || match OutputStream::try_default() {
+                Ok((stream, handle)) => {
+                    let _ = opened.send(Ok(handle));
+                    // Returns once every sender, and so every player, is gone.
+                    let _ = closed.recv();
+                    drop(stream);
+                }
+                Err(e) => {
+                    let _ = opened.send(Err(e));
+                }
+            })?;
+
+        let handle = opening
+            .recv()
+            .map_err(|_| TtsError::AudioDevice("the output thread exited".into()))??;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                handle,
+                sinks: Mutex::new(Vec::new()),
+                _close: close,
+            }),
+        })
+    }
+
+    /// Starts playing `audio`, returning a handle to control it.
+    pub fn play(&self, audio: &SynthesizedAudio<f32>) -> Result<Playback, TtsError> {
+        let sink = Arc::new(Sink::try_new(&self.inner.handle)?);
+        let playback = Playback {
+            sink: sink.clone(),
+            chunks: Arc::new(Mutex::new(Vec::new())),
+            timeline: Arc::new(Mutex::new(Timeline::default())),
+            player: self.clone(),
+        };
+        playback.append(audio)?;
+
+        let mut sinks = self.sinks();
+        sinks.retain(|x| !x.empty());
+        sinks.push(sink);
+        Ok(playback)
+    }
+
+    /// Sounds still playing or paused.
+    pub fn active(&self) -> usize {
+        let mut sinks = self.sinks();
+        sinks.retain(|x| !x.empty());
+        sinks.len()
+    }
+
+    /// Stops every sound started on this player.
+    pub fn stop_all(&self) {
+        for sink in self.sinks().drain(..) {
+            sink.stop();
+        }
+    }
+
+    fn sinks(&self) -> MutexGuard<'_, Vec<Arc<Sink>>> {
+        lock(&self.inner.sinks)
+    }
+}
+
+/// A panic while holding these locks can't leave what they guard inconsistent.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A sound started by a [`Player`]. Clones control the same sound.
+#[derive(Clone)]
+pub struct Playback {
+    sink: Arc<Sink>,
+    /// The length of every chunk appended, as rodio only tracks the
+    /// position within the one playing.
+    chunks: Arc<Mutex<Vec<Duration>>>,
+    /// The boundaries of every chunk appended, shifted to where it starts.
+    timeline: Arc<Mutex<Timeline>>,
+    /// Keeps the stream open while the sound may still play.
+    player: Player,
+}
+
+impl Playback {
+    /// Queues `audio` after whatever is already playing.
+    pub fn append(&self, audio: &SynthesizedAudio<f32>) -> Result<(), TtsError> {
+        let spec = audio.spec.layout()?;
+        let frames = audio.data.len() / spec.channels.max(1) as usize;
+        let length = Duration::from_secs_f64(frames as f64 / spec.sample_rate.max(1) as f64);
+
+        let mut chunks = lock(&self.chunks);
+        lock(&self.timeline).extend(audio.timeline(), chunks.iter().sum());
+        chunks.push(length);
+        self.sink.append(SamplesBuffer::new(
+            spec.channels,
+            spec.sample_rate,
+            audio.data.clone(),
+        ));
+        Ok(())
+    }
+
+    pub fn pause(&self) {
+        self.sink.pause();
+    }
+
+    pub fn resume(&self) {
+        self.sink.play();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+
+    /// Linear gain, 1.0 being the volume the audio was synthesized at.
+    pub fn volume(&self) -> f32 {
+        self.sink.volume()
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.sink.set_volume(volume);
+    }
+
+    /// Playback speed, 1.0 being normal.
+    pub fn speed(&self) -> f32 {
+        self.sink.speed()
+    }
+
+    /// Plays faster or slower like a tape, so the pitch follows the speed.
+    /// [`Prosody::rate`](crate::models::Prosody::rate) changes the rate alone.
+    pub fn set_speed(&self, speed: f32) {
+        self.sink.set_speed(speed);
+    }
+
+    /// How far into the audio playback is, across every chunk appended.
+    pub fn position(&self) -> Duration {
+        let chunks = lock(&self.chunks);
+        let done = chunks.len().saturating_sub(self.sink.len());
+        let played: Duration = chunks[..done].iter().sum();
+        match done < chunks.len() {
+            true => played + self.sink.get_pos(),
+            false => played,
+        }
+    }
+
+    /// The length of everything appended so far.
+    pub fn duration(&self) -> Duration {
+        lock(&self.chunks).iter().sum()
+    }
+
+    /// Moves playback to `position`. Audio that was streamed in chunks can
+    /// only be sought within the chunk playing.
+    pub fn seek(&self, position: Duration) -> Result<(), TtsError> {
+        let start = {
+            let chunks = lock(&self.chunks);
+            let done = chunks.len().saturating_sub(self.sink.len());
+            let start: Duration = chunks[..done].iter().sum();
+            let end = start + chunks.get(done).copied().unwrap_or_default();
+            if position < start || position > end {
+                return Err(TtsError::not_supported("seeking outside the chunk playing"));
+            }
+            start
+        };
+
+        match self.sink.try_seek(position - start) {
+            Ok(()) => Ok(()),
+            Err(SeekError::NotSupported { .. }) => Err(TtsError::not_supported("seeking")),
+            Err(e) => Err(TtsError::AudioDevice(e.to_string().into())),
+        }
+    }
+
+    /// Blocks until everything queued has been played or stopped. A paused
+    /// sound is waited for until it is resumed and done.
+    pub fn wait_until_done(&self) {
+        while !self.sink.empty() {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// When each word and sentence appended so far is spoken, if the engine reports it.
+    pub fn timeline(&self) -> Timeline {
+        lock(&self.timeline).clone()
+    }
+
+    /// Calls `callback` with every boundary of [`Playback::timeline`] as
+    /// playback reaches it, from a thread that ends with the sound. Seeking
+    /// back replays the boundaries after the new position.
+    pub fn on_boundary<F>(&self, mut callback: F) -> thread::JoinHandle<()>
+    where
+        F: FnMut(&Boundary) + Send + 'static,
+    {
+        let playback = self.clone();
+        thread::spawn(move || {
+            let mut next = 0;
+            loop {
+                let finished = playback.is_finished();
+                let position = playback.position();
+                let due: Vec<Boundary> = {
+                    let timeline = lock(&playback.timeline);
+                    let boundaries = &timeline.boundaries;
+                    if next > 0 && boundaries[next - 1].offset > position {
+                        next = boundaries.partition_point(|x| x.offset < position);
+                    }
+                    let count = boundaries[next..]
+                        .iter()
+                        .take_while(|x| x.offset <= position)
+                        .count();
+                    next += count;
+                    boundaries[next - count..next].to_vec()
+                };
+                // Outside the lock, so the callback may look at the timeline.
+                due.iter().for_each(&mut callback);
+
+                if finished {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        })
+    }
+
+    /// Stops the sound and drops whatever is queued. It can't be resumed.
+    pub fn stop(&self) {
+        self.sink.stop();
+    }
+
+    /// Whether everything queued has been played or stopped.
+    pub fn is_finished(&self) -> bool {
+        self.sink.empty()
+    }
+
+    /// The player this sound was started on.
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
+    /// The underlying rodio sink, for anything not covered here.
+    pub fn sink(&self) -> &Sink {
+        &self.sink
+    }
+}
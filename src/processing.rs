@@ -0,0 +1,209 @@
+//! What [`NaturalTts`](crate::NaturalTts) does to an engine's audio after
+//! synthesis, for whatever the engine can't or won't do itself.
+use crate::{
+    audio::{
+        apply_gain,
+        effects::{EffectChain, EffectStream},
+        loudness::{Loudness, LoudnessStream},
+        resample::Resampler,
+    },
+    models::{AudioStream, Prosody, Spec, SynthesizedAudio},
+    TtsError,
+};
+use hound::WavSpec;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Processing {
+    /// The part of the prosody the engine couldn't apply natively.
+    pub prosody: Prosody,
+    pub effects: EffectChain,
+    pub sample_rate: Option<u32>,
+    /// Applied last, so it overrides the volume of the prosody.
+    pub loudness: Option<Loudness>,
+}
+
+impl Processing {
+    /// Whether this leaves the audio untouched.
+    pub fn is_empty(&self) -> bool {
+        self.prosody.is_neutral()
+            && self.effects.is_empty()
+            && self.sample_rate.is_none()
+            && self.loudness.is_none()
+    }
+
+    /// `configuration`, an engine's, extended with everything this changes,
+    /// for the [`Cache`](crate::cache::Cache) key of its audio.
+    pub fn configuration(&self, configuration: &str) -> String {
+        let mut configuration = format!("{}\n{:?}", configuration, self.prosody);
+        if !self.effects.is_empty() {
+            configuration.push_str(&format!("\n{:?}", self.effects));
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            configuration.push_str(&format!("\n{} Hz", sample_rate));
+        }
+        if let Some(loudness) = self.loudness {
+            configuration.push_str(&format!("\n{:?}", loudness));
+        }
+        configuration
+    }
+
+    pub fn apply(&self, audio: &mut SynthesizedAudio<f32>) -> Result<(), TtsError> {
+        self.prosody.apply(audio)?;
+        self.effects.apply(audio)?;
+        if let Some(sample_rate) = self.sample_rate {
+            audio.resample(sample_rate)?;
+        }
+        if let Some(loudness) = self.loudness {
+            loudness.apply(audio)?;
+        }
+        Ok(())
+    }
+
+    /// Processes the chunks of `stream` as they come, with every stage
+    /// carrying its state from chunk to chunk. Filters and the resampler run
+    /// on as if the audio were whole, trimming and fades only touch the ends
+    /// of the stream and the loudness follows everything so far, see
+    /// [`LoudnessStream`]. What a stage has to see first is held back, so the
+    /// chunks don't line up with the engine's, and they come out as plain
+    /// WAV without word boundaries.
+    ///
+    /// Prosody applied as DSP can't be streamed, as each chunk would be
+    /// stretched on its own.
+    pub fn stream<'a>(
+        self,
+        stream: AudioStream<'a, f32>,
+    ) -> Result<AudioStream<'a, f32>, TtsError> {
+        let shape = Prosody {
+            volume: 0.0,
+            ..self.prosody
+        };
+        if !shape.is_neutral() {
+            return Err(TtsError::not_supported(
+                "streaming with rate or pitch applied as DSP",
+            ));
+        }
+        Ok(Box::new(ProcessedStream {
+            stream,
+            processing: self,
+            stages: None,
+            finished: false,
+        }))
+    }
+}
+
+struct ProcessedStream<'a> {
+    stream: AudioStream<'a, f32>,
+    processing: Processing,
+    /// Made for the layout of the first chunk.
+    stages: Option<Stages>,
+    finished: bool,
+}
+
+struct Stages {
+    /// The layout of the engine's chunks.
+    input: WavSpec,
+    effects: EffectStream,
+    resampler: Option<Resampler>,
+    loudness: Option<LoudnessStream>,
+}
+
+impl Stages {
+    fn new(processing: &Processing, input: WavSpec) -> Result<Self, TtsError> {
+        let output = processing.sample_rate.unwrap_or(input.sample_rate);
+        if output == 0 {
+            return Err(TtsError::not_supported("resampling to 0 Hz"));
+        }
+        return Ok(Self {
+            input,
+            effects: processing.effects.stream(input.channels, input.sample_rate),
+            resampler: processing
+                .sample_rate
+                .map(|x| Resampler::new(input.channels, input.sample_rate, x)),
+            loudness: processing
+                .loudness
+                .map(|x| x.stream(input.channels, output)),
+        });
+    }
+
+    /// Runs `samples` through every stage, or what the earlier stages held
+    /// back through the rest when `last`.
+    fn push(&mut self, samples: Vec<f32>, last: bool) -> Vec<f32> {
+        let mut samples = self.effects.push(samples);
+        if last {
+            samples.extend(self.effects.finish());
+        }
+        if let Some(resampler) = &mut self.resampler {
+            samples = resampler.push(&samples);
+            if last {
+                samples.extend(resampler.finish());
+            }
+        }
+        if let Some(loudness) = &mut self.loudness {
+            samples = loudness.push(&samples);
+            if last {
+                samples.extend(loudness.finish());
+            }
+        }
+        samples
+    }
+}
+
+impl ProcessedStream<'_> {
+    fn process(&mut self, mut chunk: SynthesizedAudio<f32>) -> Result<Vec<f32>, TtsError> {
+        let layout = chunk.spec.layout()?;
+        let stages = match &mut self.stages {
+            Some(stages) => stages,
+            None => self.stages.insert(Stages::new(&self.processing, layout)?),
+        };
+        if (layout.channels, layout.sample_rate)
+            != (stages.input.channels, stages.input.sample_rate)
+        {
+            return Err(TtsError::InvalidData(
+                "stream chunks changed layout".to_string(),
+            ));
+        }
+        apply_gain(&mut chunk.data, self.processing.prosody.volume);
+        Ok(stages.push(chunk.data, false))
+    }
+
+    fn chunk(&self, samples: Vec<f32>) -> Option<SynthesizedAudio<f32>> {
+        let stages = self.stages.as_ref()?;
+        let spec = WavSpec {
+            sample_rate: self
+                .processing
+                .sample_rate
+                .unwrap_or(stages.input.sample_rate),
+            ..stages.input
+        };
+        let frames = samples.len() / spec.channels.max(1) as usize;
+        (frames > 0).then(|| SynthesizedAudio::new(samples, Spec::Wav(spec), Some(frames as i32)))
+    }
+}
+
+impl Iterator for ProcessedStream<'_> {
+    type Item = Result<SynthesizedAudio<f32>, TtsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let samples = match self.stream.next() {
+                Some(chunk) => chunk.and_then(|x| self.process(x)),
+                None => {
+                    self.finished = true;
+                    match &mut self.stages {
+                        Some(stages) => Ok(stages.push(Vec::new(), true)),
+                        None => return None,
+                    }
+                }
+            };
+            match samples {
+                Ok(samples) => {
+                    if let Some(chunk) = self.chunk(samples) {
+                        return Some(Ok(chunk));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,291 @@
+//! Speaks utterances one after another, most urgent first.
+//!
+//! A [`SpeechQueue`] owns a [`NaturalTts`] and is driven by the caller: engines
+//! can't always move between threads, so nothing happens between calls to
+//! [`SpeechQueue::poll`] or [`SpeechQueue::run`]. What happens to each
+//! utterance is reported as [`QueueEvent`]s to every subscriber.
+use crate::{models::AudioHandler, NaturalTts, TtsError};
+use derive_builder::Builder;
+use std::{
+    cmp::Reverse,
+    fmt,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+/// What an utterance does when it has a higher priority than the one speaking.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Preempt {
+    /// Goes first once the current utterance is done.
+    #[default]
+    Wait,
+    /// Cancels the current utterance.
+    Interrupt,
+    /// Plays over the current utterance, which goes on at
+    /// [`SpeechQueue::duck_volume`] and is restored afterwards. Only one
+    /// utterance is ducked at a time, and tts-rs can't play two sounds at once,
+    /// so otherwise this interrupts.
+    Duck,
+}
+
+#[derive(Builder, Clone, Debug, PartialEq)]
+#[builder(setter(into))]
+pub struct Utterance {
+    pub text: String,
+    #[builder(default)]
+    pub priority: Priority,
+    #[builder(default)]
+    pub preempt: Preempt,
+}
+
+impl Utterance {
+    pub fn new(text: impl Into<String>, priority: Priority) -> Self {
+        return Self {
+            text: text.into(),
+            priority,
+            preempt: Preempt::Wait,
+        };
+    }
+}
+
+/// Identifies an utterance for as long as its queue lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UtteranceId(u64);
+
+impl fmt::Display for UtteranceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueueEvent {
+    Started(UtteranceId),
+    /// Played to the end.
+    Finished(UtteranceId),
+    /// Cancelled or interrupted before it was done.
+    Cancelled(UtteranceId),
+    /// Couldn't be synthesized or played. [`SpeechQueue::poll`] returns the error.
+    Failed(UtteranceId),
+}
+
+struct Playing {
+    id: UtteranceId,
+    priority: Priority,
+    handler: AudioHandler,
+    /// The volume to restore once it is no longer ducked.
+    volume: f32,
+}
+
+pub struct SpeechQueue {
+    pub natural: NaturalTts,
+    /// Linear gain of an utterance while another plays over it.
+    pub duck_volume: f32,
+
+    pending: Vec<(UtteranceId, Utterance)>,
+    current: Option<Playing>,
+    ducked: Option<Playing>,
+    next_id: u64,
+    subscribers: Vec<Sender<QueueEvent>>,
+}
+
+impl SpeechQueue {
+    pub fn new(natural: NaturalTts) -> Self {
+        return Self {
+            natural,
+            duck_volume: 0.3,
+            pending: Vec::new(),
+            current: None,
+            ducked: None,
+            next_id: 0,
+            subscribers: Vec::new(),
+        };
+    }
+
+    /// Receives every event from now on.
+    pub fn subscribe(&mut self) -> Receiver<QueueEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Queues `utterance`. It starts on a later [`SpeechQueue::poll`].
+    pub fn enqueue(&mut self, utterance: Utterance) -> UtteranceId {
+        let id = UtteranceId(self.next_id);
+        self.next_id += 1;
+        self.pending.push((id, utterance));
+        id
+    }
+
+    /// Queues `text` at [`Priority::Normal`].
+    pub fn say(&mut self, text: impl Into<String>) -> UtteranceId {
+        self.enqueue(Utterance::new(text, Priority::Normal))
+    }
+
+    /// The utterances waiting, in the order they'll be spoken.
+    pub fn pending(&self) -> Vec<UtteranceId> {
+        let mut pending: Vec<_> = self.pending.iter().collect();
+        pending.sort_by_key(|x| order(x));
+        pending.into_iter().map(|(id, _)| *id).collect()
+    }
+
+    /// The utterance speaking, not counting one ducked under it.
+    pub fn current(&self) -> Option<UtteranceId> {
+        self.current.as_ref().map(|x| x.id)
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty() && self.current.is_none() && self.ducked.is_none()
+    }
+
+    /// Cancels `id` whether it is waiting or speaking, returning whether it was found.
+    pub fn cancel(&mut self, id: UtteranceId) -> Result<bool, TtsError> {
+        if let Some(index) = self.pending.iter().position(|x| x.0 == id) {
+            self.pending.remove(index);
+            self.emit(QueueEvent::Cancelled(id));
+            return Ok(true);
+        }
+
+        let playing = match (&self.current, &self.ducked) {
+            (Some(x), _) if x.id == id => self.current.take(),
+            (_, Some(x)) if x.id == id => self.ducked.take(),
+            _ => return Ok(false),
+        };
+        if let Some(mut playing) = playing {
+            playing.handler.stop()?;
+        }
+        self.emit(QueueEvent::Cancelled(id));
+        self.restore_ducked();
+        Ok(true)
+    }
+
+    /// Cancels everything, speaking or waiting.
+    pub fn clear(&mut self) -> Result<(), TtsError> {
+        let mut ids = self.pending();
+        ids.extend(self.ducked.as_ref().map(|x| x.id));
+        ids.extend(self.current.as_ref().map(|x| x.id));
+        for id in ids {
+            self.cancel(id)?;
+        }
+        Ok(())
+    }
+
+    /// Reaps finished utterances, lets a more urgent one preempt the current
+    /// one and starts the next one when nothing is speaking.
+    pub fn poll(&mut self) -> Result<(), TtsError> {
+        if let Some(ducked) = &self.ducked {
+            if ducked.handler.is_finished()? {
+                let id = ducked.id;
+                self.ducked = None;
+                self.emit(QueueEvent::Finished(id));
+            }
+        }
+        if let Some(current) = &self.current {
+            if current.handler.is_finished()? {
+                let id = current.id;
+                self.current = None;
+                self.emit(QueueEvent::Finished(id));
+                self.restore_ducked();
+            }
+        }
+
+        let Some(next) = self.next() else {
+            return Ok(());
+        };
+        if let Some(mut current) = self.current.take() {
+            let utterance = &self.pending[next].1;
+            if utterance.priority <= current.priority || utterance.preempt == Preempt::Wait {
+                self.current = Some(current);
+                return Ok(());
+            }
+
+            match (&current.handler, utterance.preempt, &self.ducked) {
+                (AudioHandler::Playback(x), Preempt::Duck, None) => {
+                    x.set_volume(self.duck_volume);
+                    self.ducked = Some(current);
+                }
+                _ => {
+                    current.handler.stop()?;
+                    self.emit(QueueEvent::Cancelled(current.id));
+                }
+            }
+        }
+
+        let (id, utterance) = self.pending.remove(next);
+        match self.natural.launch(utterance.text, &PathBuf::new()) {
+            Ok(handler) => {
+                let volume = match &handler {
+                    AudioHandler::Playback(x) => x.volume(),
+                    #[cfg(feature = "tts-rs")]
+                    AudioHandler::Tts(_) => 1.0,
+                };
+                self.current = Some(Playing {
+                    id,
+                    priority: utterance.priority,
+                    handler,
+                    volume,
+                });
+                self.emit(QueueEvent::Started(id));
+                Ok(())
+            }
+            Err(e) => {
+                self.emit(QueueEvent::Failed(id));
+                self.restore_ducked();
+                Err(e)
+            }
+        }
+    }
+
+    /// Polls until everything queued has been spoken.
+    pub fn run(&mut self) -> Result<(), TtsError> {
+        while !self.is_idle() {
+            self.poll()?;
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    /// The index in `pending` of the utterance to speak next.
+    fn next(&self) -> Option<usize> {
+        self.pending
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, x)| order(x))
+            .map(|(index, _)| index)
+    }
+
+    /// Brings a ducked utterance back to its volume once nothing plays over it.
+    #[cfg_attr(not(feature = "tts-rs"), allow(irrefutable_let_patterns))]
+    fn restore_ducked(&mut self) {
+        if self.current.is_some() {
+            return;
+        }
+        if let Some(ducked) = self.ducked.take() {
+            if let AudioHandler::Playback(x) = &ducked.handler {
+                x.set_volume(ducked.volume);
+            }
+            self.current = Some(ducked);
+        }
+    }
+
+    fn emit(&mut self, event: QueueEvent) {
+        // Subscribers that hung up are dropped.
+        self.subscribers.retain(|x| x.send(event).is_ok());
+    }
+}
+
+/// Most urgent first, then first come first served.
+fn order((id, utterance): &(UtteranceId, Utterance)) -> (Reverse<Priority>, UtteranceId) {
+    (Reverse(utterance.priority), *id)
+}
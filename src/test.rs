@@ -1,5 +1,4 @@
-#[cfg(test)]
-#[cfg(feature = "gtts")]
+#[cfg(feature = "coqui")]
 use crate::models::coqui::CoquiModel;
 #[cfg(feature = "gtts")]
 use crate::models::gtts::GttsModel;
@@ -12,8 +11,133 @@ use crate::models::parler::ParlerModel;
 #[cfg(feature = "tts-rs")]
 use crate::models::tts_rs::TtsModel;
 
-use crate::{Model, NaturalTtsBuilder};
-use std::path::PathBuf;
+use crate::{
+    models::{NaturalModelTrait, Prosody, Spec, SynthesizedAudio},
+    Model, NaturalTtsBuilder, TtsError,
+};
+use std::path::{Path, PathBuf};
+
+/// Synthesizes one frame of silence at 1kHz per byte of the message.
+struct SilenceModel;
+
+impl NaturalModelTrait for SilenceModel {
+    type SynthesizeType = f32;
+
+    fn save(&mut self, _message: String, _path: &PathBuf) -> Result<(), TtsError> {
+        Ok(())
+    }
+
+    fn synthesize_to_memory(
+        &mut self,
+        message: String,
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
+        Ok(SynthesizedAudio::new(
+            vec![0.0; message.len()],
+            Spec::Wav(hound::WavSpec {
+                channels: 1,
+                sample_rate: 1000,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            }),
+            Some(message.len() as i32),
+        ))
+    }
+}
+
+#[test]
+fn custom_engine_test() {
+    let id = Model::Custom("silence".to_string());
+    let mut natural = NaturalTtsBuilder::default()
+        .engine(id.clone(), SilenceModel)
+        .default_model(id.clone())
+        .build()
+        .unwrap();
+    assert!(natural.is_registered(&id));
+    let audio = natural
+        .synthesize("Hello".to_string(), &PathBuf::from("output.wav"))
+        .unwrap();
+    assert_eq!(audio.data.len(), 5);
+}
+
+#[test]
+fn unregistered_engine_test() {
+    let mut natural = NaturalTtsBuilder::default()
+        .engine(Model::Custom("silence".to_string()), SilenceModel)
+        .default_model(Model::Custom("missing".to_string()))
+        .build()
+        .unwrap();
+    let err = natural
+        .save("Hello".to_string(), &PathBuf::from("output.wav"))
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        TtsError::NotRegistered(Model::Custom(id)) if id == "missing"
+    ));
+}
+
+#[test]
+fn error_test() {
+    let silence = Model::Custom("silence".to_string());
+    let mut natural = NaturalTtsBuilder::default()
+        .engine(silence.clone(), SilenceModel)
+        .default_model(silence.clone())
+        .build()
+        .unwrap();
+
+    let voice = crate::models::Voice::new("whisper", silence.clone(), "en");
+    let engine = natural.engine_mut(&silence).unwrap();
+    let err = engine.set_voice(&voice).unwrap_err();
+    assert!(matches!(err, TtsError::NotSupported { engine: None, .. }));
+    let err = err.with_engine(&silence);
+    let message = format!("choosing a voice is not supported by {}", silence);
+    assert_eq!(err.to_string(), message);
+    assert!(!err.is_transient());
+
+    let err = TtsError::Network("timed out".into());
+    assert!(err.is_transient());
+    assert!(std::error::Error::source(&err).is_some());
+    // The cause is the source, not part of the message.
+    assert_eq!(err.to_string(), "Network error");
+
+    assert!(TtsError::http(503, "Service Unavailable").is_transient());
+    assert!(TtsError::http(429, "Too Many Requests").is_transient());
+    assert!(!TtsError::http(404, "Not Found").is_transient());
+    #[cfg(feature = "gtts")]
+    {
+        assert!(TtsError::from(minreq::Error::AddressNotFound).is_transient());
+        assert!(!TtsError::from(minreq::Error::HttpsFeatureNotEnabled).is_transient());
+    }
+}
+
+#[cfg(feature = "gtts")]
+#[test]
+fn voice_selection_test() {
+    let silence = Model::Custom("silence".to_string());
+    let mut natural = NaturalTtsBuilder::default()
+        .gtts_model(GttsModel::default())
+        .engine(silence.clone(), SilenceModel)
+        .default_model(silence)
+        .build()
+        .unwrap();
+
+    let voices = natural.list_voices().unwrap();
+    assert!(voices.iter().any(|x| x.qualified_id() == "gtts:en-GB"));
+    assert!(voices.iter().all(|x| x.engine == Model::Gtts));
+
+    let voice = natural.select_voice("gtts:en-AU").unwrap();
+    assert_eq!(voice.language, "en-AU");
+    assert_eq!(natural.default_model, Some(Model::Gtts));
+
+    let err = natural.select_voice("klingon").unwrap_err();
+    assert!(matches!(err, TtsError::UnknownVoice(_)));
+
+    assert_eq!(Model::default(), Model::Gtts);
+
+    // A custom engine named like a built-in one doesn't claim its voices.
+    let voice = crate::models::Voice::new("en-GB", Model::Custom("gtts".to_string()), "en-GB");
+    assert_eq!(voice.qualified_id(), "custom:gtts:en-GB");
+    assert!(!voice.matches("gtts:en-GB"));
+}
 
 #[cfg(feature = "gtts")]
 #[test]
@@ -80,3 +204,1093 @@ fn coqui_test() {
         .unwrap();
     let _ = natural.start("Hello, World!".to_string(), &PathBuf::from("output.wav"));
 }
+
+#[test]
+fn decode_audio_test() {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: 16_000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+    for _ in 0..320 {
+        writer.write_sample(i16::MAX / 2).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let audio = crate::utils::decode_audio(bytes.into_inner()).unwrap();
+    assert_eq!(audio.data.len(), 320);
+    assert_eq!(audio.duration, Some(160));
+    assert!(matches!(audio.spec, Spec::Wav(x) if x.sample_rate == 16_000 && x.channels == 2));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_blocking_engine_test() {
+    use crate::asynchronous::{AsyncNaturalTtsBuilder, Blocking};
+
+    let id = Model::Custom("silence".to_string());
+    let natural = AsyncNaturalTtsBuilder::default()
+        .engine(id.clone(), Blocking::new(SilenceModel))
+        .default_model(id)
+        .build()
+        .unwrap();
+    let audio = natural
+        .synthesize_to_memory("Hello".to_string())
+        .await
+        .unwrap();
+    assert_eq!(audio.data.len(), 5);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_pipeline_test() {
+    use crate::{
+        asynchronous::{AsyncNaturalTtsBuilder, Blocking},
+        cache::CacheBuilder,
+        text::normalize::Normalizer,
+    };
+    use std::sync::{atomic::Ordering, Arc};
+
+    let id = Model::Custom("counting".to_string());
+    let calls = Arc::new(Default::default());
+    let mut natural = AsyncNaturalTtsBuilder::default()
+        .engine(id.clone(), Blocking::new(CountingModel(Arc::clone(&calls))))
+        .default_model(id.clone())
+        .normalizer(Some(Normalizer::default()))
+        .cache(CacheBuilder::default().build().unwrap())
+        .build()
+        .unwrap();
+    natural.set_output_sample_rate(&id, Some(2000)).unwrap();
+
+    // "5" is read as "five", and the audio comes back at twice the rate.
+    let audio = natural.synthesize_to_memory("5".to_string()).await.unwrap();
+    assert!(matches!(audio.spec, Spec::Wav(x) if x.sample_rate == 2000));
+    assert_eq!(audio.data.len(), 8);
+    let cached = natural.synthesize_to_memory("5".to_string()).await.unwrap();
+    assert_eq!(cached.data, audio.data);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn sentences_test() {
+    assert_eq!(
+        crate::text::sentences("Hello, World! It costs 3.5 euros.\nBye?! \"Yes.\" Ok"),
+        vec![
+            "Hello, World!",
+            "It costs 3.5 euros.",
+            "Bye?!",
+            "\"Yes.\"",
+            "Ok"
+        ]
+    );
+}
+
+#[test]
+fn default_stream_test() {
+    let mut natural = NaturalTtsBuilder::default()
+        .engine(Model::Custom("silence".to_string()), SilenceModel)
+        .default_model(Model::Custom("silence".to_string()))
+        .build()
+        .unwrap();
+    let chunks: Vec<usize> = natural
+        .synthesize_stream("One. Three.".to_string())
+        .unwrap()
+        .map(|x| x.unwrap().data.len())
+        .collect();
+    assert_eq!(chunks, vec![4, 6]);
+}
+
+#[test]
+fn ssml_test() {
+    use crate::text::ssml::{Segment, Ssml};
+    use std::time::Duration;
+
+    let ssml = Ssml::parse(
+        "<speak xml:lang='en-GB'>Hi &amp; <break time=\"500ms\"/><prosody rate='fast'>there \
+         <emphasis level='strong'>you</emphasis></prosody> <foo>x</foo><mark name='a'/></speak>",
+    )
+    .unwrap();
+    assert_eq!(ssml.language.as_deref(), Some("en-GB"));
+    assert_eq!(ssml.warnings.len(), 2);
+
+    let segments = ssml.segments();
+    assert_eq!(segments.len(), 5);
+    assert!(matches!(&segments[0], Segment::Text { text, .. } if text == "Hi &"));
+    assert_eq!(segments[1], Segment::Break(Duration::from_millis(500)));
+    assert!(matches!(&segments[2], Segment::Text { prosody, .. } if prosody.rate == 1.25));
+    assert!(matches!(&segments[4], Segment::Text { text, .. } if text == "x"));
+    assert!(Ssml::parse("<speak><prosody>Hi</speak>").is_err());
+
+    // Times that can't be a duration fall back to the strength, with a warning.
+    for time in ["-5ms", "infs", "NaNms", "1e300s"] {
+        let ssml = Ssml::parse(&format!("<speak>a<break time='{}'/>b</speak>", time)).unwrap();
+        assert_eq!(ssml.warnings.len(), 1, "{}", time);
+        assert!(matches!(ssml.segments()[1], Segment::Break(_)));
+    }
+
+    // Prosody that isn't a finite number is dropped and extreme values are
+    // clamped, both with a warning.
+    for (attribute, rate, pitch, volume) in [
+        ("volume='infdB'", 1.0, 0.0, 0.0),
+        ("volume='NaNdB'", 1.0, 0.0, 0.0),
+        ("rate='inf'", 1.0, 0.0, 0.0),
+        ("rate='100'", 4.0, 0.0, 0.0),
+        ("pitch='1e30st'", 1.0, 24.0, 0.0),
+        ("volume='-1e9dB'", 1.0, 0.0, -96.0),
+    ] {
+        let text = format!("<speak><prosody {}>a</prosody></speak>", attribute);
+        let ssml = Ssml::parse(&text).unwrap();
+        assert_eq!(ssml.warnings.len(), 1, "{}", attribute);
+        let expected = Prosody::new(rate, pitch, volume);
+        assert!(
+            matches!(&ssml.segments()[0], Segment::Text { prosody, .. } if *prosody == expected),
+            "{}",
+            attribute
+        );
+    }
+
+    let mut natural = NaturalTtsBuilder::default()
+        .engine(Model::Custom("silence".to_string()), SilenceModel)
+        .default_model(Model::Custom("silence".to_string()))
+        .build()
+        .unwrap();
+    let mut ssml = Ssml::parse("<speak>Hi<break time='0.5s'/>there</speak>").unwrap();
+    let audio = natural.synthesize_ssml(&mut ssml).unwrap();
+    assert_eq!(audio.data.len(), 2 + 500 + 5);
+    assert!(ssml.warnings.is_empty());
+
+    let mut ssml =
+        Ssml::parse("<speak><voice name='a'>Hi</voice><voice name='a'>there</voice></speak>")
+            .unwrap();
+    natural.synthesize_ssml(&mut ssml).unwrap();
+    assert_eq!(ssml.warnings.len(), 1);
+
+    // Breaks alone are silence rather than an error.
+    let mut ssml = Ssml::parse("<speak><break time='0.5s'/></speak>").unwrap();
+    let audio = natural.synthesize_ssml(&mut ssml).unwrap();
+    assert_eq!(audio.data.len(), 12_000);
+    assert!(audio.data.iter().all(|x| *x == 0.0));
+}
+
+#[cfg(feature = "gtts")]
+#[test]
+fn gtts_prosody_test() {
+    let mut model = GttsModel::default();
+    model.volume = 0.5;
+    let quiet = model.configuration();
+    let rest = model
+        .set_prosody(&crate::models::Prosody::new(1.0, 0.0, -6.0))
+        .unwrap();
+    assert_eq!(rest.volume, 0.0);
+    // The prosody volume comes on top of the user's, instead of replacing it.
+    assert_eq!(model.volume, 0.5);
+    assert_ne!(model.configuration(), quiet);
+    model
+        .set_prosody(&crate::models::Prosody::default())
+        .unwrap();
+    assert_eq!(model.configuration(), quiet);
+}
+
+#[cfg(feature = "gtts")]
+#[test]
+fn gtts_tokenizer_test() {
+    use crate::models::gtts::tokenizer::tokenize;
+
+    let text = "Hello, World! This sentence is long enough that it has to be cut on a space somewhere before the limit is reached. Done.";
+    let pieces = tokenize(text, 40);
+    assert!(pieces.iter().all(|x| x.chars().count() <= 40));
+    assert_eq!(pieces.join(" "), text);
+    assert_eq!(pieces[0], "Hello, World!");
+
+    // Limits are in characters, not bytes.
+    let text = "日本語のテキストはバイトではなく文字で数えます";
+    assert_eq!(tokenize(text, 100), vec![text]);
+    assert_eq!(tokenize(text, 10).len(), 3);
+}
+
+#[test]
+fn write_wav_test() {
+    use crate::audio::encode::wav::{self, WavFormat};
+
+    let path = std::env::temp_dir().join("natural_tts_write_wav_test.wav");
+    let audio = SynthesizedAudio::new(
+        vec![0.0, 0.5, -0.5, 1.0],
+        Spec::Wav(hound::WavSpec {
+            channels: 1,
+            sample_rate: 24_000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        }),
+        Some(4),
+    );
+    wav::save(&audio, None, &path).unwrap();
+
+    let read = crate::utils::decode_audio(std::fs::read(&path).unwrap()).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(read.data.len(), audio.data.len());
+    assert!(read
+        .data
+        .iter()
+        .zip(&audio.data)
+        .all(|(a, b)| (a - b).abs() < 1e-3));
+    assert!(matches!(read.spec, Spec::Wav(x) if x.sample_rate == 24_000 && x.channels == 1));
+
+    // Every format in stereo, written to memory.
+    let stereo = SynthesizedAudio::new(
+        vec![0.0, 0.25, 0.5, -0.5, 1.0, -1.0],
+        Spec::Wav(hound::WavSpec {
+            channels: 2,
+            ..audio.spec.layout().unwrap()
+        }),
+        Some(3),
+    );
+    for format in [
+        WavFormat::Int16,
+        WavFormat::Int24,
+        WavFormat::Int32,
+        WavFormat::Float32,
+    ] {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        wav::write(&stereo, Some(format), &mut bytes).unwrap();
+        let reader = hound::WavReader::new(std::io::Cursor::new(bytes.into_inner())).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.bits_per_sample, format.bits_per_sample());
+        assert_eq!(spec.sample_format, format.sample_format());
+        assert_eq!(reader.len(), 6);
+    }
+}
+
+#[test]
+fn encode_test() {
+    use crate::audio::encode::{self, OutputFormat};
+
+    let flac = OutputFormat::from_path(Path::new("out.FLAC"));
+    assert_eq!(flac, Some(OutputFormat::Flac { compression: 5 }));
+    let mp3 = OutputFormat::from_path(Path::new("out.mp3"));
+    assert!(matches!(mp3, Some(OutputFormat::Mp3 { .. })));
+    assert_eq!(OutputFormat::from_path(Path::new("out")), None);
+
+    // A tone, then noise only verbatim subframes can hold, in stereo.
+    let mut audio = sine(440.0, 0.5);
+    let noise = (0..2000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 16) as f32 / 65_536.0 - 0.5);
+    audio.data.extend(noise);
+    audio.data = audio.data.iter().flat_map(|x| [*x, -*x]).collect();
+    audio.spec = Spec::Wav(hound::WavSpec {
+        channels: 2,
+        ..audio.spec.layout().unwrap()
+    });
+
+    let path = std::env::temp_dir().join("natural_tts_encode_test.flac");
+    encode::save(&audio, OutputFormat::Flac { compression: 8 }, &path).unwrap();
+    let size = std::fs::metadata(&path).unwrap().len();
+    let read = crate::utils::decode_audio(std::fs::read(&path).unwrap()).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert!(size < audio.data.len() as u64 * 2);
+    assert!(matches!(read.spec, Spec::Wav(x) if x.sample_rate == 24_000 && x.channels == 2));
+    assert_eq!(read.data.len(), audio.data.len());
+    assert!(read
+        .data
+        .iter()
+        .zip(&audio.data)
+        .all(|(a, b)| (a - b).abs() < 1e-4));
+
+    // Lossy formats come back about as long, once their encoders are enabled.
+    let lossy = [
+        (
+            OutputFormat::Mp3 { bitrate: 128 },
+            "mp3",
+            cfg!(feature = "mp3"),
+        ),
+        (
+            OutputFormat::OggVorbis { quality: 0.5 },
+            "ogg",
+            cfg!(feature = "vorbis"),
+        ),
+    ];
+    for (format, extension, enabled) in lossy {
+        let path = path.with_extension(extension);
+        let saved = encode::save(&audio, format, &path);
+        if !enabled {
+            assert!(matches!(saved, Err(TtsError::NotSupported { .. })));
+            continue;
+        }
+        saved.unwrap();
+        let read = crate::utils::decode_audio(std::fs::read(&path).unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(
+            matches!(read.spec, Spec::Wav(x) if x.channels == 2),
+            "{}",
+            extension
+        );
+        let frames = read.data.len() as f32 / audio.data.len() as f32;
+        assert!((0.95..1.15).contains(&frames), "{}: {}", extension, frames);
+    }
+
+    // Ogg Opus decodes at 48kHz, without the pre-skip or the padding.
+    #[cfg(feature = "opus")]
+    {
+        let path = path.with_extension("opus");
+        encode::save(&audio, OutputFormat::OggOpus { bitrate: 64 }, &path).unwrap();
+        let read = encode::decode_ogg_opus(&std::fs::read(&path).unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(read.spec, Spec::Wav(x) if x.sample_rate == 48_000 && x.channels == 2));
+        assert_eq!(read.data.len(), audio.data.len() * 2);
+    }
+
+    let mut natural = NaturalTtsBuilder::default()
+        .default_model(Model::Custom("silence".to_string()))
+        .engine(Model::Custom("silence".to_string()), SilenceModel)
+        .output_format(OutputFormat::Flac { compression: 0 })
+        .build()
+        .unwrap();
+    let path = std::env::temp_dir().join("natural_tts_encode_test_natural.wav");
+    natural.save("Hello".to_string(), &path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(&bytes[..4], b"fLaC");
+}
+
+#[test]
+fn resample_test() {
+    use crate::audio::resample;
+
+    // A tone well inside both bands comes through intact.
+    let mut audio = sine(1000.0, 0.5);
+    audio.resample(48_000).unwrap();
+    assert!(matches!(audio.spec, Spec::Wav(x) if x.sample_rate == 48_000));
+    assert_eq!(audio.data.len(), 24_000);
+    assert_eq!(audio.duration, Some(24_000));
+    let expected = sine(1000.0 / 2.0, 1.0);
+    assert!((1000..23_000).all(|i| (audio.data[i] - expected.data[i]).abs() < 1e-3));
+
+    // A tone above the new Nyquist frequency is filtered out rather than aliased.
+    let high = sine(10_000.0, 0.5);
+    let low = resample::sinc(&high.data, 1, 24_000, 8_000);
+    assert_eq!(low.len(), 4000);
+    let peak = low[500..3500].iter().fold(0f32, |a, b| a.max(b.abs()));
+    assert!(peak < 1e-3, "{}", peak);
+
+    let silence = Model::Custom("silence".to_string());
+    let mut natural = NaturalTtsBuilder::default()
+        .default_model(silence.clone())
+        .engine(silence.clone(), SilenceModel)
+        .build()
+        .unwrap();
+    natural
+        .set_output_sample_rate(&silence, Some(8_000))
+        .unwrap();
+    assert_eq!(natural.output_sample_rate(&silence), Some(8_000));
+    let audio = natural
+        .synthesize_to_memory("Hello there".to_string())
+        .unwrap();
+    assert!(matches!(audio.spec, Spec::Wav(x) if x.sample_rate == 8_000));
+    let missing = Model::Custom("missing".to_string());
+    let unknown = natural.set_output_sample_rate(&missing, Some(8_000));
+    assert!(matches!(unknown, Err(TtsError::NotRegistered(_))));
+}
+
+/// Streams a 440Hz tone, one second per byte of the message, in chunks of
+/// its frames.
+struct ToneModel(usize);
+
+impl NaturalModelTrait for ToneModel {
+    type SynthesizeType = f32;
+
+    fn save(&mut self, _message: String, _path: &PathBuf) -> Result<(), TtsError> {
+        Ok(())
+    }
+
+    fn synthesize_to_memory(
+        &mut self,
+        message: String,
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
+        Ok(sine(440.0, message.len() as f32))
+    }
+
+    fn synthesize_stream(
+        &mut self,
+        message: String,
+    ) -> Result<crate::models::AudioStream<'_, Self::SynthesizeType>, TtsError> {
+        let audio = self.synthesize_to_memory(message)?;
+        let spec = audio.spec.layout()?;
+        let chunks: Vec<Vec<f32>> = audio.data.chunks(self.0).map(|x| x.to_vec()).collect();
+        Ok(Box::new(chunks.into_iter().map(move |x| {
+            let frames = x.len() as i32;
+            Ok(SynthesizedAudio::new(x, Spec::Wav(spec), Some(frames)))
+        })))
+    }
+}
+
+#[test]
+fn stream_resample_test() {
+    use crate::audio::resample::{self, Resampler};
+
+    // Chunks of any size come out as the whole would.
+    let audio = sine(440.0, 0.5);
+    let whole = resample::sinc(&audio.data, 1, 24_000, 16_000);
+    let mut resampler = Resampler::new(1, 24_000, 16_000);
+    let mut chunked = Vec::new();
+    for chunk in audio.data.chunks(777) {
+        chunked.extend(resampler.push(chunk));
+    }
+    chunked.extend(resampler.finish());
+    assert_eq!(chunked.len(), whole.len());
+    assert!(chunked
+        .iter()
+        .zip(&whole)
+        .all(|(a, b)| (a - b).abs() < 1e-6));
+
+    let tone = Model::Custom("tone".to_string());
+    let mut natural = NaturalTtsBuilder::default()
+        .default_model(tone.clone())
+        .engine(tone.clone(), ToneModel(1000))
+        .build()
+        .unwrap();
+    natural.set_output_sample_rate(&tone, Some(16_000)).unwrap();
+    let mut streamed = Vec::new();
+    for chunk in natural.synthesize_stream("a".to_string()).unwrap() {
+        let chunk = chunk.unwrap();
+        assert!(matches!(chunk.spec, Spec::Wav(x) if x.sample_rate == 16_000));
+        streamed.extend(chunk.data);
+    }
+    let whole = natural.synthesize_to_memory("a".to_string()).unwrap();
+    assert_eq!(streamed.len(), whole.data.len());
+    assert!(streamed
+        .iter()
+        .zip(&whole.data)
+        .all(|(a, b)| (a - b).abs() < 1e-6));
+}
+
+#[test]
+fn loudness_test() {
+    use crate::audio::loudness::{integrated, limit, true_peak, Limiter, Loudness};
+
+    let mut audio = sine(1000.0, 2.0);
+    Loudness::ebu_r128().apply(&mut audio).unwrap();
+    let measured = integrated(&audio.data, 1, 24_000).unwrap();
+    assert!((measured + 23.0).abs() < 0.1, "{}", measured);
+
+    // Too loud to reach without the limiter stepping in.
+    let mut audio = sine(1000.0, 2.0);
+    Loudness::new(-3.0, -1.0).apply(&mut audio).unwrap();
+    let peak = true_peak(&audio.data, 1, 24_000);
+    assert!(peak <= -0.9, "{}", peak);
+    assert!(audio.data.iter().all(|x| x.abs() < 1.0));
+
+    // Chunk by chunk, the limiter catches the peaks at the start of each
+    // chunk as it does on the whole.
+    let mut loud = sine(1000.0, 0.5).data;
+    crate::audio::apply_gain(&mut loud, 6.0);
+    let mut whole = loud.clone();
+    limit(&mut whole, 1, 24_000, -1.0);
+    let mut limiter = Limiter::new(1, 24_000, -1.0);
+    let mut chunked: Vec<f32> = loud.chunks(500).flat_map(|x| limiter.push(x)).collect();
+    chunked.extend(limiter.finish());
+    assert_eq!(chunked.len(), whole.len());
+    assert!(chunked
+        .iter()
+        .zip(&whole)
+        .all(|(a, b)| (a - b).abs() < 1e-6));
+
+    // Silence can't be measured and is left alone.
+    let mut silent = vec![0.0; 24_000];
+    Loudness::default().normalize(&mut silent, 1, 24_000);
+    assert!(silent.iter().all(|x| *x == 0.0));
+    assert_eq!(integrated(&silent, 1, 24_000), None);
+
+    let silence = Model::Custom("silence".to_string());
+    let mut natural = NaturalTtsBuilder::default()
+        .default_model(silence.clone())
+        .engine(silence.clone(), SilenceModel)
+        .build()
+        .unwrap();
+    natural
+        .set_loudness(&silence, Some(Loudness::ebu_r128()))
+        .unwrap();
+    assert_eq!(natural.loudness(&silence), Some(Loudness::ebu_r128()));
+    natural.set_loudness(&silence, None).unwrap();
+    assert_eq!(natural.loudness(&silence), None);
+}
+
+#[test]
+fn effects_test() {
+    use crate::audio::effects::{Effect, EffectChain};
+    use std::time::Duration;
+
+    // 300ms of silence on either side of a tone.
+    let mut audio = sine(1000.0, 0.5);
+    let silence = vec![0.0; 7200];
+    audio.data = [silence.clone(), audio.data, silence].concat();
+
+    let chain = EffectChain::new()
+        .then(Effect::Trim {
+            threshold: -40.0,
+            padding: Duration::from_millis(10),
+        })
+        .then(Effect::FadeIn(Duration::from_millis(10)))
+        .then(Effect::FadeOut(Duration::from_millis(10)));
+    chain.apply(&mut audio).unwrap();
+    let length = audio.data.len();
+    assert!((12_000..13_000).contains(&length), "{}", length);
+    assert_eq!(audio.duration, Some(length as i32));
+    assert!(audio.data[0].abs() < 1e-3 && audio.data[length - 1].abs() < 1e-3);
+
+    let mut offset = sine(1000.0, 1.0);
+    offset.data.iter_mut().for_each(|x| *x += 0.1);
+    Effect::DcRemoval.apply(&mut offset).unwrap();
+    let settled = &offset.data[12_000..];
+    let mean = settled.iter().sum::<f32>() / settled.len() as f32;
+    assert!(mean.abs() < 0.01, "{}", mean);
+
+    // A 200Hz high-pass all but removes 50Hz and leaves 2kHz.
+    let peak = |audio: &SynthesizedAudio<f32>| {
+        let settled = &audio.data[12_000..];
+        settled.iter().fold(0f32, |a, b| a.max(b.abs()))
+    };
+    let high_pass = EffectChain::new().then(Effect::HighPass { cutoff: 200.0 });
+    let mut low = sine(50.0, 1.0);
+    high_pass.apply(&mut low).unwrap();
+    assert!(peak(&low) < 0.05, "{}", peak(&low));
+    let mut high = sine(2000.0, 1.0);
+    high_pass.apply(&mut high).unwrap();
+    assert!(peak(&high) > 0.49, "{}", peak(&high));
+
+    let mut louder = sine(1000.0, 0.1);
+    Effect::Gain(6.0).apply(&mut louder).unwrap();
+    assert!((louder.data.iter().fold(0f32, |a, b| a.max(b.abs())) - 1.0).abs() < 0.01);
+
+    let silence = Model::Custom("silence".to_string());
+    let mut natural = NaturalTtsBuilder::default()
+        .default_model(silence.clone())
+        .engine(silence.clone(), SilenceModel)
+        .build()
+        .unwrap();
+    natural
+        .set_effects(&silence, EffectChain::cleanup())
+        .unwrap();
+    assert_eq!(natural.effects(&silence), Some(&EffectChain::cleanup()));
+    // Silence all along isn't trimmed away.
+    let audio = natural
+        .synthesize_to_memory("Hello there".to_string())
+        .unwrap();
+    assert_eq!(audio.data.len(), 11);
+    natural.set_effects(&silence, EffectChain::new()).unwrap();
+    assert_eq!(natural.effects(&silence), None);
+}
+
+/// A mono 24kHz sine wave.
+fn sine(frequency: f32, seconds: f32) -> SynthesizedAudio<f32> {
+    let frames = (24_000.0 * seconds) as usize;
+    let data = (0..frames)
+        .map(|i| 0.5 * (2.0 * std::f32::consts::PI * frequency * i as f32 / 24_000.0).sin())
+        .collect();
+    SynthesizedAudio::new(
+        data,
+        Spec::Wav(hound::WavSpec {
+            channels: 1,
+            sample_rate: 24_000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        }),
+        Some(frames as i32),
+    )
+}
+
+#[test]
+fn stream_processing_test() {
+    use crate::{
+        audio::{effects::EffectChain, loudness::Loudness},
+        processing::Processing,
+    };
+
+    // Two sentences with a pause after each, split into chunks that don't
+    // line up with the windows of the trim.
+    let mut audio = sine(440.0, 0.3);
+    audio.data.extend(vec![0.0; 4800]);
+    audio.data = audio.data.repeat(2);
+    let chunks: Vec<Vec<f32>> = audio.data.chunks(1234).map(|x| x.to_vec()).collect();
+    let spec = audio.spec.layout().unwrap();
+    let stream = || -> crate::models::AudioStream<'static, f32> {
+        Box::new(chunks.clone().into_iter().map(move |x| {
+            let frames = x.len() as i32;
+            Ok(SynthesizedAudio::new(x, Spec::Wav(spec), Some(frames)))
+        }))
+    };
+    let collect = |processing: Processing| -> Vec<f32> {
+        processing
+            .stream(stream())
+            .unwrap()
+            .flat_map(|x| x.unwrap().data)
+            .collect()
+    };
+
+    // Effects and resampling give what they give on the whole, pauses and all.
+    let processing = Processing {
+        effects: EffectChain::cleanup(),
+        sample_rate: Some(16_000),
+        ..Default::default()
+    };
+    let streamed = collect(processing.clone());
+    let mut whole = SynthesizedAudio::new(audio.data.clone(), Spec::Wav(spec), None);
+    processing.apply(&mut whole).unwrap();
+    assert_eq!(streamed.len(), whole.data.len());
+    assert!(streamed
+        .iter()
+        .zip(&whole.data)
+        .all(|(a, b)| (a - b).abs() < 1e-5));
+
+    // The loudness moves smoothly from chunk to chunk and stays under the ceiling.
+    let processing = Processing {
+        loudness: Some(Loudness::default()),
+        ..Default::default()
+    };
+    let streamed = collect(processing);
+    assert_eq!(streamed.len(), audio.data.len());
+    let peak = streamed.iter().fold(0f32, |a, b| a.max(b.abs()));
+    let step = streamed
+        .windows(2)
+        .fold(0f32, |a, x| a.max((x[1] - x[0]).abs()));
+    assert!(peak <= crate::audio::db_to_gain(-1.0) + 1e-3, "{}", peak);
+    assert!(step < peak * 0.13, "{} {}", step, peak);
+
+    // Rate and pitch as DSP would stretch each chunk on its own.
+    let processing = Processing {
+        prosody: Prosody::new(1.5, 0.0, 0.0),
+        ..Default::default()
+    };
+    let refused = processing.stream(stream());
+    assert!(matches!(refused, Err(TtsError::NotSupported { .. })));
+}
+
+/// Estimates the frequency of a mono 24kHz signal from its zero crossings.
+fn frequency(data: &[f32]) -> f32 {
+    let crossings = data
+        .windows(2)
+        .filter(|x| (x[0] < 0.0) != (x[1] < 0.0))
+        .count();
+    crossings as f32 / 2.0 / (data.len() as f32 / 24_000.0)
+}
+
+#[test]
+fn prosody_test() {
+    let mut faster = sine(220.0, 1.0);
+    Prosody::new(2.0, 0.0, 0.0).apply(&mut faster).unwrap();
+    assert!((faster.data.len() as i32 - 12_000).abs() < 100);
+    assert!((frequency(&faster.data) - 220.0).abs() < 10.0);
+
+    let mut higher = sine(220.0, 1.0);
+    Prosody::new(1.0, 12.0, -6.0).apply(&mut higher).unwrap();
+    assert!((higher.data.len() as i32 - 24_000).abs() < 100);
+    assert!((frequency(&higher.data) - 440.0).abs() < 20.0);
+    let peak = higher.data.iter().fold(0f32, |a, b| a.max(b.abs()));
+    assert!((peak - 0.25).abs() < 0.05);
+}
+
+#[test]
+fn stretch_test() {
+    use std::time::Duration;
+
+    let mut slower = sine(220.0, 1.0);
+    slower.stretch(0.5).unwrap();
+    assert!((slower.data.len() as i32 - 48_000).abs() < 100);
+    assert_eq!(slower.duration, Some(slower.data.len() as i32));
+    assert!((frequency(&slower.data) - 220.0).abs() < 10.0);
+
+    let mut lower = sine(440.0, 1.0);
+    lower.shift_pitch(-12.0).unwrap();
+    assert!((lower.data.len() as i32 - 24_000).abs() < 100);
+    assert!((frequency(&lower.data) - 220.0).abs() < 10.0);
+
+    // Fits exactly, whichever way it has to go.
+    for target in [700, 1300] {
+        let mut audio = sine(220.0, 1.0);
+        audio
+            .fit_to_duration(Duration::from_millis(target))
+            .unwrap();
+        assert_eq!(audio.data.len(), target as usize * 24);
+        assert!((frequency(&audio.data) - 220.0).abs() < 10.0);
+    }
+    let mut empty = SynthesizedAudio::new(Vec::new(), sine(220.0, 0.0).spec, None);
+    empty.fit_to_duration(Duration::from_millis(10)).unwrap();
+    assert_eq!(empty.data, vec![0.0; 240]);
+
+    assert!(sine(220.0, 1.0).stretch(0.0).is_err());
+    assert!(sine(220.0, 1.0).shift_pitch(f32::NAN).is_err());
+}
+
+#[test]
+fn concat_test() {
+    use crate::audio::concat::{remix, Join};
+    use std::time::Duration;
+
+    // Half a second of mono at 24kHz, then half a second of stereo at 48kHz.
+    let stereo = || {
+        let mut audio = sine(440.0, 0.5);
+        audio.resample(48_000).unwrap();
+        let data = remix(&audio.data, 1, 2);
+        let spec = hound::WavSpec {
+            channels: 2,
+            ..audio.spec.layout().unwrap()
+        };
+        SynthesizedAudio::new(data, Spec::Wav(spec), None)
+    };
+
+    let gap = Join::Gap(Duration::from_millis(100));
+    let joined = SynthesizedAudio::concat([("Hello.", sine(220.0, 0.5)), ("Bye.", stereo())], gap);
+    let joined = joined.unwrap();
+    let spec = joined.audio.spec.layout().unwrap();
+    assert_eq!((spec.sample_rate, spec.channels), (48_000, 2));
+    assert_eq!(joined.audio.data.len(), (24_000 + 4_800 + 24_000) * 2);
+    assert_eq!(joined.segments[0].text, "Hello.");
+    assert_eq!(joined.segments[0].samples, 0..48_000);
+    assert_eq!(joined.segments[1].samples, 57_600..105_600);
+    assert_eq!(joined.segments[1].offset, Duration::from_millis(600));
+    assert!(joined.audio.data[48_000..57_600].iter().all(|x| *x == 0.0));
+
+    let fade = Join::Crossfade(Duration::from_millis(100));
+    let joined = SynthesizedAudio::concat([("Hello.", stereo()), ("Bye.", stereo())], fade);
+    let joined = joined.unwrap();
+    assert_eq!(joined.audio.data.len(), (48_000 - 4_800) * 2);
+    assert_eq!(joined.segments[1].offset, Duration::from_millis(400));
+    assert_eq!(
+        joined.segments[0].samples.end - joined.segments[1].samples.start,
+        9_600
+    );
+    // The same signal faded into itself at equal power peaks at most 3dB higher.
+    let peak = joined.audio.data.iter().fold(0f32, |a, b| a.max(b.abs()));
+    assert!(peak < 0.5 * 1.42, "{}", peak);
+
+    assert_eq!(remix(&[0.2, 0.4], 2, 1), vec![0.3]);
+    let none: [(&str, SynthesizedAudio<f32>); 0] = [];
+    assert!(SynthesizedAudio::concat(none, Join::default()).is_err());
+}
+
+#[test]
+fn mix_test() {
+    use crate::audio::{
+        loudness::{integrated, Loudness},
+        mix::{Ducking, Mixer, Role, Track},
+    };
+    use std::time::Duration;
+
+    // Two seconds of music, with half a second of speech a quarter of the way in.
+    let mixer = Mixer::new()
+        .track(Track::new(sine(110.0, 2.0), Role::Background))
+        .track(Track::new(sine(1000.0, 0.5), Role::Speech).at(Duration::from_millis(500)))
+        .ducking(Ducking::default());
+    let mix = mixer.render().unwrap();
+    assert_eq!(mix.data.len(), 48_000);
+    let peak = |from: usize, to: usize| {
+        let samples = &mix.data[from..to];
+        samples.iter().fold(0f32, |a, b| a.max(b.abs()))
+    };
+    assert!((peak(6_000, 7_200) - 0.5).abs() < 0.01);
+    // Still ducked right after the speech, and back up well after it.
+    assert!(peak(24_000, 24_500) < 0.2, "{}", peak(24_000, 24_500));
+    assert!(peak(46_000, 48_000) > 0.45, "{}", peak(46_000, 48_000));
+
+    let mixer = Mixer::new()
+        .track(Track::new(sine(220.0, 1.0), Role::Speech).gain(-6.0))
+        .track(Track::new(sine(440.0, 0.5), Role::Effect).at(Duration::from_millis(1_800)))
+        .master(Loudness::default());
+    let mix = mixer.render().unwrap();
+    assert_eq!(mix.data.len(), 55_200);
+    assert!(mix.data[24_000..43_200].iter().all(|x| *x == 0.0));
+    let loudness = integrated(&mix.data, 1, 24_000).unwrap();
+    assert!((loudness + 16.0).abs() < 0.5, "{}", loudness);
+
+    let path = std::env::temp_dir().join("natural_tts_mix_test.wav");
+    mixer.save(&path).unwrap();
+    assert_eq!(hound::WavReader::open(&path).unwrap().duration(), 55_200);
+    assert!(Mixer::new().render().is_err());
+}
+
+#[cfg(feature = "msedge")]
+#[test]
+fn msedge_audio_format_test() {
+    use crate::models::msedge::{AudioFormat, Codec, Container};
+
+    let format: AudioFormat = "audio-24khz-48kbitrate-mono-mp3".parse().unwrap();
+    assert_eq!(format.container, Container::Audio);
+    assert_eq!(format.sample_rate, 24_000);
+    assert_eq!(format.bitrate_kbps, Some(48));
+    assert_eq!(format.channels, 1);
+    assert_eq!(format.codec, Codec::Mp3);
+
+    let format: AudioFormat = "riff-22050hz-16bit-mono-pcm".parse().unwrap();
+    assert_eq!(format.container, Container::Riff);
+    assert_eq!(format.sample_rate, 22_050);
+    assert_eq!(format.bits_per_sample, Some(16));
+    assert_eq!(format.codec, Codec::Pcm);
+
+    assert!("mp3".parse::<AudioFormat>().is_err());
+}
+
+#[test]
+fn normalize_test() {
+    use crate::text::normalize::{Normalizer, NormalizerBuilder};
+
+    let normalizer = Normalizer::default();
+    let en = |text: &str| normalizer.normalize(text, Some("en-US"));
+    assert_eq!(en("$3.50"), "three dollars and fifty cents");
+    assert_eq!(en("12/05/2026"), "December fifth, twenty twenty-six");
+    assert_eq!(
+        en("Dr. Smith drove 80 km/h"),
+        "Doctor Smith drove eighty kilometers per hour"
+    );
+    assert_eq!(en("the 21st at 10:05"), "the twenty-first at ten oh five");
+    assert_eq!(
+        normalizer.normalize("12/05/2026", Some("en-GB")),
+        "the twelfth of May, twenty twenty-six"
+    );
+    assert_eq!(
+        normalizer.normalize("Es kostet 3,50 €", Some("de")),
+        "Es kostet drei Euro und fünfzig Cent"
+    );
+    assert_eq!(normalizer.normalize("12/05/2026", Some("ja")), "12/05/2026");
+
+    assert_eq!(en("In the 1990s"), "In the nineteen nineties");
+    assert_eq!(en("The 80s and the '60s"), "The eighties and the sixties");
+    assert_eq!(en("It took 5 s"), "It took five seconds");
+    assert_eq!(en("Main St."), "Main St.");
+    assert_eq!(en("St. Louis"), "Saint Louis");
+    assert_eq!(normalizer.normalize("St. Pauli", Some("de")), "Sankt Pauli");
+    assert_eq!(
+        normalizer.normalize("21 oder 34", Some("de")),
+        "einundzwanzig oder vierunddreißig"
+    );
+    // Fractions too long for a number are read digit by digit.
+    assert_eq!(
+        normalizer.normalize("1,0123456789012345678901", Some("de")),
+        "eins Komma null eins zwei drei vier fünf sechs sieben acht neun null eins zwei drei vier fünf sechs sieben acht neun null eins"
+    );
+    // Tokens no rule reads as a whole are left alone rather than half read.
+    assert_eq!(en("Version 2.0.1"), "Version 2.0.1");
+    assert_eq!(en("10:60"), "10:60");
+    assert_eq!(en("13/13/2020"), "13/13/2020");
+
+    let numbers_only = NormalizerBuilder::default()
+        .currency(false)
+        .abbreviations(false)
+        .build()
+        .unwrap();
+    assert_eq!(numbers_only.normalize("Dr. 5", None), "Dr. five");
+}
+
+/// Counts how often it is asked to synthesize, and can be cached.
+struct CountingModel(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+impl NaturalModelTrait for CountingModel {
+    type SynthesizeType = f32;
+
+    fn save(&mut self, _message: String, _path: &PathBuf) -> Result<(), TtsError> {
+        Ok(())
+    }
+
+    fn synthesize_to_memory(
+        &mut self,
+        message: String,
+    ) -> Result<SynthesizedAudio<Self::SynthesizeType>, TtsError> {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        SilenceModel.synthesize_to_memory(message)
+    }
+
+    fn configuration(&self) -> Option<String> {
+        Some("counting".to_string())
+    }
+}
+
+#[test]
+fn cache_test() {
+    use crate::cache::CacheBuilder;
+    use std::sync::{atomic::Ordering, Arc};
+
+    let directory = std::env::temp_dir().join("natural_tts_cache_test");
+    let _ = std::fs::remove_dir_all(&directory);
+    let id = Model::Custom("counting".to_string());
+    let calls = Arc::new(Default::default());
+    let natural = |calls: &Arc<_>| {
+        NaturalTtsBuilder::default()
+            .engine(id.clone(), CountingModel(Arc::clone(calls)))
+            .default_model(id.clone())
+            .cache(
+                CacheBuilder::default()
+                    .max_entries(1usize)
+                    .directory(Some(directory.clone()))
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+    };
+
+    let mut first = natural(&calls);
+    first.synthesize_to_memory("Hello".to_string()).unwrap();
+    let audio = first.synthesize_to_memory("Hello".to_string()).unwrap();
+    assert_eq!(audio.data.len(), 5);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Evicted from memory, but still on disk, also for a fresh instance.
+    first.synthesize_to_memory("World".to_string()).unwrap();
+    assert_eq!(first.cache.as_ref().unwrap().len(), 1);
+    let mut second = natural(&calls);
+    assert!(second.is_cached("Hello".to_string()));
+    let reloaded = second.synthesize_to_memory("Hello".to_string()).unwrap();
+    assert_eq!(reloaded.duration, audio.duration);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    assert!(second.uncache("Hello".to_string()).unwrap());
+    assert!(!second.is_cached("Hello".to_string()));
+    second
+        .cache
+        .as_mut()
+        .unwrap()
+        .invalidate_engine(&id)
+        .unwrap();
+    assert!(!second.is_cached("World".to_string()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    // A custom engine named like a built-in one keeps its own entries.
+    #[cfg(feature = "gtts")]
+    assert_ne!(
+        crate::cache::Cache::key(&Model::Gtts, "", "Hello"),
+        crate::cache::Cache::key(&Model::Custom("gtts".to_string()), "", "Hello")
+    );
+}
+
+#[test]
+fn playback_test() {
+    // Machines without an output device can't open a player at all.
+    let player = match crate::playback::Player::new() {
+        Ok(player) => player,
+        Err(e) => return assert!(matches!(e, TtsError::AudioDevice(_))),
+    };
+    let silence = Model::Custom("silence".to_string());
+    let mut natural = NaturalTtsBuilder::default()
+        .engine(silence.clone(), SilenceModel)
+        .default_model(silence)
+        .player(Some(player.clone()))
+        .build()
+        .unwrap();
+
+    natural
+        .start("Hello".to_string(), &PathBuf::from("output.wav"))
+        .unwrap();
+    let playback = natural.playback().unwrap();
+    let handle = playback.clone();
+    assert!(std::ptr::eq(playback.sink(), handle.sink()));
+
+    natural.pause().unwrap();
+    assert!(handle.is_paused());
+    natural.set_volume(0.5).unwrap();
+    assert_eq!(handle.volume(), 0.5);
+    // SilenceModel makes one frame per byte at 1kHz.
+    assert_eq!(
+        natural.duration().unwrap(),
+        std::time::Duration::from_millis(5)
+    );
+    assert!(natural.position().unwrap() <= natural.duration().unwrap());
+    natural.seek(std::time::Duration::from_millis(2)).unwrap();
+    assert!(natural.seek(std::time::Duration::from_secs(1)).is_err());
+
+    // Handles keep the stream open after everything else is dropped.
+    drop(natural);
+    drop(player);
+    handle.resume();
+    playback.stop();
+    handle.wait_until_done();
+    assert!(handle.is_finished());
+    handle.player().stop_all();
+}
+
+#[test]
+fn queue_test() {
+    use crate::queue::{Priority, QueueEvent, SpeechQueue, Utterance, UtteranceBuilder};
+
+    let silence = Model::Custom("silence".to_string());
+    let natural = NaturalTtsBuilder::default()
+        .engine(silence.clone(), SilenceModel)
+        .default_model(silence)
+        .build()
+        .unwrap();
+    let mut queue = SpeechQueue::new(natural);
+    let events = queue.subscribe();
+
+    let low = queue.enqueue(Utterance::new("later", Priority::Low));
+    let first = queue.say("first");
+    let second = queue.say("second");
+    let urgent = queue.enqueue(
+        UtteranceBuilder::default()
+            .text("now")
+            .priority(Priority::Urgent)
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(queue.pending(), vec![urgent, first, second, low]);
+
+    assert!(queue.cancel(second).unwrap());
+    assert!(!queue.cancel(second).unwrap());
+    assert_eq!(events.try_recv(), Ok(QueueEvent::Cancelled(second)));
+
+    // Without an output device the utterance fails, but the queue goes on.
+    match queue.poll() {
+        Ok(()) => assert_eq!(events.try_recv(), Ok(QueueEvent::Started(urgent))),
+        Err(_) => assert_eq!(events.try_recv(), Ok(QueueEvent::Failed(urgent))),
+    }
+    assert_eq!(queue.pending(), vec![first, low]);
+    queue.clear().unwrap();
+    assert!(queue.is_idle());
+}
+
+#[test]
+fn timeline_test() {
+    use crate::text::timeline::{Boundary, BoundaryKind, Timeline};
+    use std::time::Duration;
+
+    let ms = Duration::from_millis;
+    let word = |text: &str, offset, duration| {
+        Boundary::new(BoundaryKind::Word, text, ms(offset), ms(duration))
+    };
+    let timeline = Timeline::new(vec![
+        word("Hello", 50, 400),
+        word("world.", 500, 450),
+        word("Bye", 3_601_000, 300),
+        Boundary::new(BoundaryKind::Punctuation, "!", ms(3_601_300), ms(100)),
+    ]);
+
+    let sentences: Vec<_> = timeline.sentences().map(|x| x.text.as_str()).collect();
+    assert_eq!(sentences, vec!["Hello world.", "Bye!"]);
+    assert_eq!(timeline.boundaries[0].kind, BoundaryKind::Sentence);
+    assert_eq!(
+        timeline.at(BoundaryKind::Word, ms(600)).unwrap().text,
+        "world."
+    );
+    assert!(timeline.at(BoundaryKind::Word, ms(10)).is_none());
+
+    assert_eq!(
+        timeline.to_srt(BoundaryKind::Sentence),
+        "1\n00:00:00,050 --> 00:00:00,950\nHello world.\n\n\
+         2\n01:00:01,000 --> 01:00:01,400\nBye!\n\n"
+    );
+    assert!(timeline
+        .to_webvtt(BoundaryKind::Word)
+        .starts_with("WEBVTT\n\n00:00:00.050 --> 00:00:00.450\nHello\n\n"));
+
+    #[cfg(feature = "msedge")]
+    {
+        let metadata = msedge_tts::tts::AudioMetadata {
+            metadata_type: Some("WordBoundary".to_string()),
+            offset: 1_000_000,
+            duration: 2_000_000,
+            text: Some("Hi".to_string()),
+            length: 2,
+            boundary_type: Some("WordBoundary".to_string()),
+        };
+        let timeline = Timeline::from(std::slice::from_ref(&metadata));
+        assert_eq!(timeline.words().next(), Some(&word("Hi", 100, 200)));
+    }
+}
@@ -0,0 +1,53 @@
+pub mod normalize;
+pub mod ssml;
+pub mod timeline;
+
+/// Characters that end a sentence when followed by whitespace or the end of the text.
+const SENTENCE_ENDS: &[char] = &['.', '!', '?', ';', '。', '！', '？', '…'];
+
+/// Splits `text` into sentences, keeping the closing punctuation with each one.
+///
+/// A terminator only ends a sentence when followed by whitespace, so decimals
+/// like "3.5" and abbreviations like "e.g." stay intact. Newlines always end a
+/// sentence.
+pub fn sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            push_trimmed(&mut sentences, &mut current);
+            continue;
+        }
+
+        current.push(c);
+        if SENTENCE_ENDS.contains(&c) {
+            // Keep runs like "?!" and closing quotes with the sentence they end.
+            while let Some(&next) = chars.peek() {
+                if SENTENCE_ENDS.contains(&next) || matches!(next, '"' | '\'' | ')' | '”' | '’')
+                {
+                    current.push(next);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+
+            if chars.peek().is_none_or(|x| x.is_whitespace()) || !c.is_ascii() {
+                push_trimmed(&mut sentences, &mut current);
+            }
+        }
+    }
+
+    push_trimmed(&mut sentences, &mut current);
+    sentences
+}
+
+fn push_trimmed(sentences: &mut Vec<String>, current: &mut String) {
+    let trimmed = current.trim();
+    if !trimmed.is_empty() {
+        sentences.push(trimmed.to_string());
+    }
+    current.clear();
+}
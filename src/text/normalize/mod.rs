@@ -0,0 +1,105 @@
+//! Expands numbers, currencies, dates, units and abbreviations into the
+//! words they are read as, so neural engines don't have to guess.
+//!
+//! English, German, French and Spanish are supported. Text in any other
+//! language is left untouched.
+pub mod numbers;
+mod rules;
+
+use derive_builder::Builder;
+
+/// A language the normalizer has rules for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    /// `us` decides whether dates are read month first.
+    English {
+        us: bool,
+    },
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    /// Picks the rules for a BCP-47 tag such as `en-GB`.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.to_ascii_lowercase();
+        let mut parts = tag.split(['-', '_']);
+        match (parts.next()?, parts.next()) {
+            ("en", region) => Some(Language::English {
+                us: region.is_none_or(|x| x == "us"),
+            }),
+            ("de", _) => Some(Language::German),
+            ("fr", _) => Some(Language::French),
+            ("es", _) => Some(Language::Spanish),
+            _ => None,
+        }
+    }
+
+    /// Whether `,` rather than `.` separates the decimals.
+    fn decimal_comma(&self) -> bool {
+        !matches!(self, Language::English { .. })
+    }
+}
+
+/// Which rule families to apply. Every family is on by default.
+#[derive(Builder, Clone, Debug, PartialEq)]
+#[builder(setter(into))]
+pub struct Normalizer {
+    /// Used when the engine doesn't say which language it speaks.
+    #[builder(default = "\"en\".to_string()")]
+    pub language: String,
+    /// `Dr.`, `e.g.`, `No. 5` and the like.
+    #[builder(default = "true")]
+    pub abbreviations: bool,
+    /// `$3.50`, `10 €`.
+    #[builder(default = "true")]
+    pub currency: bool,
+    /// `12/05/2026`, `2026-05-12` and times like `10:30`.
+    #[builder(default = "true")]
+    pub dates: bool,
+    /// `5 km`, `km/h`, `20%`.
+    #[builder(default = "true")]
+    pub units: bool,
+    /// Cardinals, decimals, ordinals like `21st` and decades like `1990s`.
+    #[builder(default = "true")]
+    pub numbers: bool,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        return NormalizerBuilder::default().build().unwrap();
+    }
+}
+
+impl Normalizer {
+    /// Normalizes `text` in the language given by the BCP-47 tag `language`,
+    /// falling back to [`Normalizer::language`] when it is `None`.
+    pub fn normalize(&self, text: &str, language: Option<&str>) -> String {
+        let tag = language.unwrap_or(&self.language);
+        let Some(language) = Language::from_tag(tag) else {
+            return text.to_string();
+        };
+
+        let mut text = text.to_string();
+        if self.abbreviations {
+            text = rules::abbreviations(&text, language);
+        }
+        if self.currency {
+            text = rules::currency(&text, language);
+        }
+        if self.dates {
+            text = rules::dates(&text, language);
+            text = rules::times(&text, language);
+        }
+        if self.units {
+            text = rules::units(&text, language);
+        }
+        if self.numbers {
+            text = rules::decades(&text, language);
+            text = rules::ordinals(&text, language);
+            text = rules::numbers(&text, language);
+        }
+        text
+    }
+}
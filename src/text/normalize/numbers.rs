@@ -0,0 +1,503 @@
+//! Numbers spelled out in words.
+use super::Language;
+
+const EN_ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+const EN_TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+const DE_ONES: [&str; 20] = [
+    "null",
+    "eins",
+    "zwei",
+    "drei",
+    "vier",
+    "fünf",
+    "sechs",
+    "sieben",
+    "acht",
+    "neun",
+    "zehn",
+    "elf",
+    "zwölf",
+    "dreizehn",
+    "vierzehn",
+    "fünfzehn",
+    "sechzehn",
+    "siebzehn",
+    "achtzehn",
+    "neunzehn",
+];
+const DE_TENS: [&str; 10] = [
+    "", "", "zwanzig", "dreißig", "vierzig", "fünfzig", "sechzig", "siebzig", "achtzig", "neunzig",
+];
+
+const FR_ONES: [&str; 20] = [
+    "zéro", "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf", "dix", "onze",
+    "douze", "treize", "quatorze", "quinze", "seize", "dix-sept", "dix-huit", "dix-neuf",
+];
+const FR_TENS: [&str; 7] = [
+    "",
+    "",
+    "vingt",
+    "trente",
+    "quarante",
+    "cinquante",
+    "soixante",
+];
+
+const ES_ONES: [&str; 30] = [
+    "cero",
+    "uno",
+    "dos",
+    "tres",
+    "cuatro",
+    "cinco",
+    "seis",
+    "siete",
+    "ocho",
+    "nueve",
+    "diez",
+    "once",
+    "doce",
+    "trece",
+    "catorce",
+    "quince",
+    "dieciséis",
+    "diecisiete",
+    "dieciocho",
+    "diecinueve",
+    "veinte",
+    "veintiuno",
+    "veintidós",
+    "veintitrés",
+    "veinticuatro",
+    "veinticinco",
+    "veintiséis",
+    "veintisiete",
+    "veintiocho",
+    "veintinueve",
+];
+const ES_TENS: [&str; 10] = [
+    "",
+    "",
+    "",
+    "treinta",
+    "cuarenta",
+    "cincuenta",
+    "sesenta",
+    "setenta",
+    "ochenta",
+    "noventa",
+];
+const ES_HUNDREDS: [&str; 10] = [
+    "",
+    "ciento",
+    "doscientos",
+    "trescientos",
+    "cuatrocientos",
+    "quinientos",
+    "seiscientos",
+    "setecientos",
+    "ochocientos",
+    "novecientos",
+];
+
+pub fn cardinal(n: u64, language: Language) -> String {
+    match language {
+        Language::English { .. } => en_cardinal(n),
+        Language::German => de_cardinal(n),
+        Language::French => fr_cardinal(n),
+        Language::Spanish => es_cardinal(n),
+    }
+}
+
+/// The form used when counting something, e.g. "un" rather than "uno" in Spanish.
+pub fn count(n: u64, language: Language) -> String {
+    match (language, n) {
+        (Language::German, 1) => "ein".to_string(),
+        (Language::Spanish, _) => es_apocope(es_cardinal(n)),
+        _ => cardinal(n, language),
+    }
+}
+
+pub fn ordinal(n: u64, language: Language) -> String {
+    match language {
+        Language::English { .. } => en_ordinal(n),
+        Language::German => de_ordinal(n),
+        Language::French => match n {
+            1 => "premier".to_string(),
+            _ => fr_ordinal(n),
+        },
+        Language::Spanish => match n {
+            1..=10 => [
+                "primero", "segundo", "tercero", "cuarto", "quinto", "sexto", "séptimo", "octavo",
+                "noveno", "décimo",
+            ][n as usize - 1]
+                .to_string(),
+            _ => es_cardinal(n),
+        },
+    }
+}
+
+/// A year, read the way it usually is: "nineteen ninety" rather than
+/// "one thousand nine hundred ninety".
+pub fn year(n: u64, language: Language) -> String {
+    match language {
+        Language::English { .. } if (1100..=1999).contains(&n) || (2010..=2099).contains(&n) => {
+            let (high, low) = (n / 100, n % 100);
+            match low {
+                0 => format!("{} hundred", en_cardinal(high)),
+                1..=9 => format!("{} oh {}", en_cardinal(high), en_cardinal(low)),
+                _ => format!("{} {}", en_cardinal(high), en_cardinal(low)),
+            }
+        }
+        Language::German if (1100..=1999).contains(&n) => {
+            format!("{}hundert{}", de_below_100(n / 100), de_rest(n % 100))
+        }
+        _ => cardinal(n, language),
+    }
+}
+
+/// Reads `fraction` after the decimal separator: digit by digit in English,
+/// as a number elsewhere.
+pub fn decimal(whole: u64, fraction: &str, language: Language) -> String {
+    let separator = match language {
+        Language::English { .. } => "point",
+        Language::German => "Komma",
+        Language::French => "virgule",
+        Language::Spanish => "coma",
+    };
+
+    let fraction = match language {
+        Language::English { .. } => fraction
+            .chars()
+            .filter_map(|x| x.to_digit(10))
+            .map(|x| en_cardinal(x as u64))
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => {
+            // Leading zeros are read out, the rest as a number.
+            let digits = fraction.trim_start_matches('0');
+            let zeros = fraction.len() - digits.len();
+            let mut words = vec![cardinal(0, language); zeros];
+            match digits.parse() {
+                Ok(x) => words.push(cardinal(x, language)),
+                // Too long for a number, so digit by digit after all.
+                Err(_) => words.extend(
+                    digits
+                        .chars()
+                        .filter_map(|x| x.to_digit(10))
+                        .map(|x| cardinal(x as u64, language)),
+                ),
+            }
+            words.join(" ")
+        }
+    };
+
+    format!("{} {} {}", cardinal(whole, language), separator, fraction)
+}
+
+fn en_below_1000(n: u64) -> String {
+    let (hundreds, rest) = (n / 100, n % 100);
+    let rest = match rest {
+        0 => String::new(),
+        1..=19 => EN_ONES[rest as usize].to_string(),
+        _ if rest % 10 == 0 => EN_TENS[rest as usize / 10].to_string(),
+        _ => format!(
+            "{}-{}",
+            EN_TENS[rest as usize / 10],
+            EN_ONES[rest as usize % 10]
+        ),
+    };
+    match (hundreds, rest.is_empty()) {
+        (0, _) => rest,
+        (h, true) => format!("{} hundred", EN_ONES[h as usize]),
+        (h, false) => format!("{} hundred {}", EN_ONES[h as usize], rest),
+    }
+}
+
+fn en_cardinal(n: u64) -> String {
+    if n == 0 {
+        return EN_ONES[0].to_string();
+    }
+
+    let mut words = Vec::new();
+    let mut rest = n;
+    for (scale, name) in [
+        (1_000_000_000_000, "trillion"),
+        (1_000_000_000, "billion"),
+        (1_000_000, "million"),
+        (1_000, "thousand"),
+    ] {
+        if rest >= scale {
+            words.push(format!("{} {}", en_cardinal(rest / scale), name));
+            rest %= scale;
+        }
+    }
+    if rest > 0 {
+        words.push(en_below_1000(rest));
+    }
+    words.join(" ")
+}
+
+fn en_ordinal(n: u64) -> String {
+    let cardinal = en_cardinal(n);
+    let split = cardinal.rfind([' ', '-']).map_or(0, |x| x + 1);
+    let (head, last) = cardinal.split_at(split);
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        x if x.ends_with('y') => format!("{}ieth", &x[..x.len() - 1]),
+        x => format!("{}th", x),
+    };
+    format!("{}{}", head, last)
+}
+
+fn de_below_100(n: u64) -> String {
+    let (tens, units) = (n as usize / 10, n % 10);
+    match (n, units) {
+        (0..=19, _) => DE_ONES[n as usize].to_string(),
+        (_, 0) => DE_TENS[tens].to_string(),
+        // "eins" loses its s inside a compound: einundzwanzig.
+        (_, 1) => format!("einund{}", DE_TENS[tens]),
+        (_, x) => format!("{}und{}", DE_ONES[x as usize], DE_TENS[tens]),
+    }
+}
+
+/// The tail of a compound, empty for zero.
+fn de_rest(n: u64) -> String {
+    match n {
+        0 => String::new(),
+        _ => de_below_1000(n),
+    }
+}
+
+fn de_below_1000(n: u64) -> String {
+    match n / 100 {
+        0 => de_below_100(n),
+        1 => format!("einhundert{}", de_rest(n % 100)),
+        h => format!("{}hundert{}", DE_ONES[h as usize], de_rest(n % 100)),
+    }
+}
+
+fn de_cardinal(n: u64) -> String {
+    if n == 0 {
+        return DE_ONES[0].to_string();
+    }
+
+    let mut words = Vec::new();
+    let mut rest = n;
+    for (scale, singular, plural) in [
+        (1_000_000_000_000, "eine Billion", "Billionen"),
+        (1_000_000_000, "eine Milliarde", "Milliarden"),
+        (1_000_000, "eine Million", "Millionen"),
+    ] {
+        if rest >= scale {
+            let count = rest / scale;
+            words.push(match count {
+                1 => singular.to_string(),
+                _ => format!("{} {}", de_cardinal(count), plural),
+            });
+            rest %= scale;
+        }
+    }
+
+    if rest > 0 {
+        let (thousands, below) = (rest / 1000, rest % 1000);
+        let thousands = match thousands {
+            0 => String::new(),
+            // "hunderteins" + "tausend" is "hunderteintausend".
+            x => match de_below_1000(x).strip_suffix("eins") {
+                Some(head) => format!("{}eintausend", head),
+                None => format!("{}tausend", de_below_1000(x)),
+            },
+        };
+        words.push(format!("{}{}", thousands, de_rest(below)));
+    }
+    words.join(" ")
+}
+
+fn de_ordinal(n: u64) -> String {
+    let rest = n % 100;
+    if rest == 0 || rest >= 20 {
+        return format!("{}ster", de_cardinal(n));
+    }
+
+    let head = match n - rest {
+        0 => String::new(),
+        x => de_cardinal(x),
+    };
+    let tail = match rest {
+        1 => "erster".to_string(),
+        3 => "dritter".to_string(),
+        7 => "siebter".to_string(),
+        8 => "achter".to_string(),
+        x => format!("{}ter", DE_ONES[x as usize]),
+    };
+    format!("{}{}", head, tail)
+}
+
+fn fr_below_100(n: u64) -> String {
+    match n {
+        0..=19 => FR_ONES[n as usize].to_string(),
+        20..=69 => match n % 10 {
+            0 => FR_TENS[n as usize / 10].to_string(),
+            1 => format!("{} et un", FR_TENS[n as usize / 10]),
+            x => format!("{}-{}", FR_TENS[n as usize / 10], FR_ONES[x as usize]),
+        },
+        71 => "soixante et onze".to_string(),
+        70..=79 => format!("soixante-{}", FR_ONES[n as usize - 60]),
+        80 => "quatre-vingts".to_string(),
+        _ => format!("quatre-vingt-{}", FR_ONES[n as usize - 80]),
+    }
+}
+
+fn fr_below_1000(n: u64) -> String {
+    let (hundreds, rest) = (n / 100, n % 100);
+    let head = match (hundreds, rest) {
+        (0, _) => return fr_below_100(rest),
+        (1, _) => "cent".to_string(),
+        (h, 0) => format!("{} cents", FR_ONES[h as usize]),
+        (h, _) => format!("{} cent", FR_ONES[h as usize]),
+    };
+    match rest {
+        0 => head,
+        _ => format!("{} {}", head, fr_below_100(rest)),
+    }
+}
+
+fn fr_cardinal(n: u64) -> String {
+    if n == 0 {
+        return FR_ONES[0].to_string();
+    }
+
+    let mut words = Vec::new();
+    let mut rest = n;
+    for (scale, name) in [
+        (1_000_000_000_000, "billion"),
+        (1_000_000_000, "milliard"),
+        (1_000_000, "million"),
+    ] {
+        if rest >= scale {
+            let count = rest / scale;
+            let plural = if count > 1 { "s" } else { "" };
+            words.push(format!("{} {}{}", fr_cardinal(count), name, plural));
+            rest %= scale;
+        }
+    }
+    if rest >= 1000 {
+        words.push(match rest / 1000 {
+            1 => "mille".to_string(),
+            // Mille is invariable and takes the plural off "cents" and "vingts" before it.
+            x => format!("{} mille", fr_below_1000(x).trim_end_matches('s')),
+        });
+        rest %= 1000;
+    }
+    if rest > 0 {
+        words.push(fr_below_1000(rest));
+    }
+    words.join(" ")
+}
+
+fn fr_ordinal(n: u64) -> String {
+    let cardinal = fr_cardinal(n);
+    let stem = match cardinal.as_str() {
+        x if x.ends_with("cinq") => format!("{}u", x),
+        x if x.ends_with("neuf") => format!("{}v", &x[..x.len() - 1]),
+        x if x.ends_with('e') => x[..x.len() - 1].to_string(),
+        x if x.ends_with('s') => x[..x.len() - 1].to_string(),
+        x => x.to_string(),
+    };
+    format!("{}ième", stem)
+}
+
+fn es_below_1000(n: u64) -> String {
+    let (hundreds, rest) = (n / 100, n % 100);
+    let rest = match rest {
+        0 => String::new(),
+        1..=29 => ES_ONES[rest as usize].to_string(),
+        _ if rest % 10 == 0 => ES_TENS[rest as usize / 10].to_string(),
+        _ => format!(
+            "{} y {}",
+            ES_TENS[rest as usize / 10],
+            ES_ONES[rest as usize % 10]
+        ),
+    };
+    match (hundreds, rest.is_empty()) {
+        (0, _) => rest,
+        (1, true) => "cien".to_string(),
+        (h, true) => ES_HUNDREDS[h as usize].to_string(),
+        (h, false) => format!("{} {}", ES_HUNDREDS[h as usize], rest),
+    }
+}
+
+/// "uno" becomes "un" before a noun or a scale word.
+fn es_apocope(words: String) -> String {
+    if let Some(x) = words.strip_suffix("veintiuno") {
+        format!("{}veintiún", x)
+    } else if let Some(x) = words.strip_suffix("uno") {
+        format!("{}un", x)
+    } else {
+        words
+    }
+}
+
+fn es_cardinal(n: u64) -> String {
+    if n == 0 {
+        return ES_ONES[0].to_string();
+    }
+
+    let mut words = Vec::new();
+    let mut rest = n;
+    for (scale, singular, plural) in [
+        (1_000_000_000_000, "un billón", "billones"),
+        (1_000_000, "un millón", "millones"),
+    ] {
+        if rest >= scale {
+            let count = rest / scale;
+            words.push(match count {
+                1 => singular.to_string(),
+                _ => format!("{} {}", es_apocope(es_cardinal(count)), plural),
+            });
+            rest %= scale;
+        }
+    }
+    if rest >= 1000 {
+        words.push(match rest / 1000 {
+            1 => "mil".to_string(),
+            x => format!("{} mil", es_apocope(es_below_1000(x))),
+        });
+        rest %= 1000;
+    }
+    if rest > 0 {
+        words.push(es_below_1000(rest));
+    }
+    words.join(" ")
+}
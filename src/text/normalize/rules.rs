@@ -0,0 +1,728 @@
+use super::{
+    numbers::{cardinal, count, decimal, ordinal, year},
+    Language,
+};
+use regex::{Captures, Regex};
+use std::sync::LazyLock;
+
+const EN_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("Dr.", "Doctor"),
+    ("Mr.", "Mister"),
+    ("Mrs.", "Missus"),
+    ("Ms.", "Miz"),
+    ("Prof.", "Professor"),
+    ("St.", "Saint"),
+    ("Jr.", "Junior"),
+    ("Sr.", "Senior"),
+    ("Mt.", "Mount"),
+    ("Ave.", "Avenue"),
+    ("etc.", "et cetera"),
+    ("e.g.", "for example"),
+    ("i.e.", "that is"),
+    ("vs.", "versus"),
+    ("approx.", "approximately"),
+];
+const DE_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("Dr.", "Doktor"),
+    ("Prof.", "Professor"),
+    ("Hr.", "Herr"),
+    ("Fr.", "Frau"),
+    ("St.", "Sankt"),
+    ("z.B.", "zum Beispiel"),
+    ("z. B.", "zum Beispiel"),
+    ("d.h.", "das heißt"),
+    ("d. h.", "das heißt"),
+    ("usw.", "und so weiter"),
+    ("bzw.", "beziehungsweise"),
+    ("ca.", "circa"),
+    ("evtl.", "eventuell"),
+    ("ggf.", "gegebenenfalls"),
+];
+const FR_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("M.", "Monsieur"),
+    ("MM.", "Messieurs"),
+    ("Mme", "Madame"),
+    ("Mlle", "Mademoiselle"),
+    ("Dr", "Docteur"),
+    ("Dr.", "Docteur"),
+    ("Pr", "Professeur"),
+    ("etc.", "et cetera"),
+    ("p. ex.", "par exemple"),
+    ("c.-à-d.", "c'est-à-dire"),
+];
+const ES_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("Sr.", "señor"),
+    ("Sra.", "señora"),
+    ("Srta.", "señorita"),
+    ("Dr.", "doctor"),
+    ("Dra.", "doctora"),
+    ("Ud.", "usted"),
+    ("Uds.", "ustedes"),
+    ("etc.", "etcétera"),
+    ("p. ej.", "por ejemplo"),
+    ("aprox.", "aproximadamente"),
+];
+
+/// Abbreviations only expanded before a capitalised name.
+const BEFORE_NAMES: &[&str] = &["St."];
+
+/// `(symbol, one major, many major, one minor, many minor)`. Currencies
+/// without a minor unit have empty minor names.
+type Currency = (char, &'static str, &'static str, &'static str, &'static str);
+
+const EN_CURRENCIES: &[Currency] = &[
+    ('$', "dollar", "dollars", "cent", "cents"),
+    ('€', "euro", "euros", "cent", "cents"),
+    ('£', "pound", "pounds", "penny", "pence"),
+    ('¥', "yen", "yen", "", ""),
+];
+const DE_CURRENCIES: &[Currency] = &[
+    ('$', "Dollar", "Dollar", "Cent", "Cent"),
+    ('€', "Euro", "Euro", "Cent", "Cent"),
+    ('£', "Pfund", "Pfund", "Penny", "Pence"),
+    ('¥', "Yen", "Yen", "", ""),
+];
+const FR_CURRENCIES: &[Currency] = &[
+    ('$', "dollar", "dollars", "cent", "cents"),
+    ('€', "euro", "euros", "centime", "centimes"),
+    ('£', "livre", "livres", "penny", "pence"),
+    ('¥', "yen", "yens", "", ""),
+];
+const ES_CURRENCIES: &[Currency] = &[
+    ('$', "dólar", "dólares", "centavo", "centavos"),
+    ('€', "euro", "euros", "céntimo", "céntimos"),
+    ('£', "libra", "libras", "penique", "peniques"),
+    ('¥', "yen", "yenes", "", ""),
+];
+
+/// `(abbreviation, said with a count of one, said with any other count)`.
+type Unit = (&'static str, &'static str, &'static str);
+
+const EN_UNITS: &[Unit] = &[
+    ("km/h", "one kilometer per hour", "kilometers per hour"),
+    ("mph", "one mile per hour", "miles per hour"),
+    ("m/s", "one meter per second", "meters per second"),
+    ("km", "one kilometer", "kilometers"),
+    ("cm", "one centimeter", "centimeters"),
+    ("mm", "one millimeter", "millimeters"),
+    ("m", "one meter", "meters"),
+    ("kg", "one kilogram", "kilograms"),
+    ("mg", "one milligram", "milligrams"),
+    ("g", "one gram", "grams"),
+    ("ml", "one milliliter", "milliliters"),
+    ("l", "one liter", "liters"),
+    ("°C", "one degree Celsius", "degrees Celsius"),
+    ("°F", "one degree Fahrenheit", "degrees Fahrenheit"),
+    ("%", "one percent", "percent"),
+    ("kWh", "one kilowatt hour", "kilowatt hours"),
+    ("kW", "one kilowatt", "kilowatts"),
+    ("GB", "one gigabyte", "gigabytes"),
+    ("MB", "one megabyte", "megabytes"),
+    ("ms", "one millisecond", "milliseconds"),
+    ("min", "one minute", "minutes"),
+    ("h", "one hour", "hours"),
+    ("s", "one second", "seconds"),
+];
+const DE_UNITS: &[Unit] = &[
+    ("km/h", "ein Kilometer pro Stunde", "Kilometer pro Stunde"),
+    ("mph", "eine Meile pro Stunde", "Meilen pro Stunde"),
+    ("m/s", "ein Meter pro Sekunde", "Meter pro Sekunde"),
+    ("km", "ein Kilometer", "Kilometer"),
+    ("cm", "ein Zentimeter", "Zentimeter"),
+    ("mm", "ein Millimeter", "Millimeter"),
+    ("m", "ein Meter", "Meter"),
+    ("kg", "ein Kilogramm", "Kilogramm"),
+    ("mg", "ein Milligramm", "Milligramm"),
+    ("g", "ein Gramm", "Gramm"),
+    ("ml", "ein Milliliter", "Milliliter"),
+    ("l", "ein Liter", "Liter"),
+    ("°C", "ein Grad Celsius", "Grad Celsius"),
+    ("°F", "ein Grad Fahrenheit", "Grad Fahrenheit"),
+    ("%", "ein Prozent", "Prozent"),
+    ("kWh", "eine Kilowattstunde", "Kilowattstunden"),
+    ("kW", "ein Kilowatt", "Kilowatt"),
+    ("GB", "ein Gigabyte", "Gigabyte"),
+    ("MB", "ein Megabyte", "Megabyte"),
+    ("ms", "eine Millisekunde", "Millisekunden"),
+    ("min", "eine Minute", "Minuten"),
+    ("h", "eine Stunde", "Stunden"),
+    ("s", "eine Sekunde", "Sekunden"),
+];
+const FR_UNITS: &[Unit] = &[
+    ("km/h", "un kilomètre par heure", "kilomètres par heure"),
+    ("mph", "un mile par heure", "miles par heure"),
+    ("m/s", "un mètre par seconde", "mètres par seconde"),
+    ("km", "un kilomètre", "kilomètres"),
+    ("cm", "un centimètre", "centimètres"),
+    ("mm", "un millimètre", "millimètres"),
+    ("m", "un mètre", "mètres"),
+    ("kg", "un kilogramme", "kilogrammes"),
+    ("mg", "un milligramme", "milligrammes"),
+    ("g", "un gramme", "grammes"),
+    ("ml", "un millilitre", "millilitres"),
+    ("l", "un litre", "litres"),
+    ("°C", "un degré Celsius", "degrés Celsius"),
+    ("°F", "un degré Fahrenheit", "degrés Fahrenheit"),
+    ("%", "un pour cent", "pour cent"),
+    ("kWh", "un kilowattheure", "kilowattheures"),
+    ("kW", "un kilowatt", "kilowatts"),
+    ("GB", "un gigaoctet", "gigaoctets"),
+    ("MB", "un mégaoctet", "mégaoctets"),
+    ("ms", "une milliseconde", "millisecondes"),
+    ("min", "une minute", "minutes"),
+    ("h", "une heure", "heures"),
+    ("s", "une seconde", "secondes"),
+];
+const ES_UNITS: &[Unit] = &[
+    ("km/h", "un kilómetro por hora", "kilómetros por hora"),
+    ("mph", "una milla por hora", "millas por hora"),
+    ("m/s", "un metro por segundo", "metros por segundo"),
+    ("km", "un kilómetro", "kilómetros"),
+    ("cm", "un centímetro", "centímetros"),
+    ("mm", "un milímetro", "milímetros"),
+    ("m", "un metro", "metros"),
+    ("kg", "un kilogramo", "kilogramos"),
+    ("mg", "un miligramo", "miligramos"),
+    ("g", "un gramo", "gramos"),
+    ("ml", "un mililitro", "mililitros"),
+    ("l", "un litro", "litros"),
+    ("°C", "un grado Celsius", "grados Celsius"),
+    ("°F", "un grado Fahrenheit", "grados Fahrenheit"),
+    ("%", "un por ciento", "por ciento"),
+    ("kWh", "un kilovatio hora", "kilovatios hora"),
+    ("kW", "un kilovatio", "kilovatios"),
+    ("GB", "un gigabyte", "gigabytes"),
+    ("MB", "un megabyte", "megabytes"),
+    ("ms", "un milisegundo", "milisegundos"),
+    ("min", "un minuto", "minutos"),
+    ("h", "una hora", "horas"),
+    ("s", "un segundo", "segundos"),
+];
+
+const EN_MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const DE_MONTHS: [&str; 12] = [
+    "Januar",
+    "Februar",
+    "März",
+    "April",
+    "Mai",
+    "Juni",
+    "Juli",
+    "August",
+    "September",
+    "Oktober",
+    "November",
+    "Dezember",
+];
+const FR_MONTHS: [&str; 12] = [
+    "janvier",
+    "février",
+    "mars",
+    "avril",
+    "mai",
+    "juin",
+    "juillet",
+    "août",
+    "septembre",
+    "octobre",
+    "novembre",
+    "décembre",
+];
+const ES_MONTHS: [&str; 12] = [
+    "enero",
+    "febrero",
+    "marzo",
+    "abril",
+    "mayo",
+    "junio",
+    "julio",
+    "agosto",
+    "septiembre",
+    "octubre",
+    "noviembre",
+    "diciembre",
+];
+
+/// A whole part with optional thousands separators and decimals.
+const NUMBER_POINT: &str = r"(\d{1,3}(?:,\d{3})+|\d+)(?:\.(\d+))?";
+const NUMBER_COMMA: &str = r"(\d{1,3}(?:\.\d{3})+|\d+)(?:,(\d+))?";
+
+static EN_ABBREVIATION_RE: LazyLock<Regex> = LazyLock::new(|| alternation(EN_ABBREVIATIONS));
+static DE_ABBREVIATION_RE: LazyLock<Regex> = LazyLock::new(|| alternation(DE_ABBREVIATIONS));
+static FR_ABBREVIATION_RE: LazyLock<Regex> = LazyLock::new(|| alternation(FR_ABBREVIATIONS));
+static ES_ABBREVIATION_RE: LazyLock<Regex> = LazyLock::new(|| alternation(ES_ABBREVIATIONS));
+static NUMBER_SIGN_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(No\.|Nr\.|[nN]°|n\.º|núm\.)\s*(\d)").unwrap());
+
+static CURRENCY_PREFIX_POINT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"([$€£¥])\s?{}", NUMBER_POINT)).unwrap());
+static CURRENCY_PREFIX_COMMA_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"([$€£¥])\s?{}", NUMBER_COMMA)).unwrap());
+static CURRENCY_SUFFIX_POINT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"\b{}\s?([$€£¥])", NUMBER_POINT)).unwrap());
+static CURRENCY_SUFFIX_COMMA_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"\b{}\s?([$€£¥])", NUMBER_COMMA)).unwrap());
+
+static ISO_DATE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d{4})-(\d{1,2})-(\d{1,2})\b").unwrap());
+static DATE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d{1,2})([./-])(\d{1,2})([./-])(\d{4}|\d{2})\b").unwrap());
+static DE_DATE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"\b(\d{{1,2}})\.\s?({})", DE_MONTHS.join("|"))).unwrap());
+static TIME_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b([01]?\d|2[0-3]):([0-5]\d)\b").unwrap());
+
+static EN_UNIT_RE: LazyLock<Regex> = LazyLock::new(|| units_regex(EN_UNITS, NUMBER_POINT));
+static DE_UNIT_RE: LazyLock<Regex> = LazyLock::new(|| units_regex(DE_UNITS, NUMBER_COMMA));
+static FR_UNIT_RE: LazyLock<Regex> = LazyLock::new(|| units_regex(FR_UNITS, NUMBER_COMMA));
+static ES_UNIT_RE: LazyLock<Regex> = LazyLock::new(|| units_regex(ES_UNITS, NUMBER_COMMA));
+static RATE_UNIT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(km/h|m/s|mph)\b").unwrap());
+
+static EN_ORDINAL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d+)(?:st|nd|rd|th)\b").unwrap());
+static FR_ORDINAL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d+)(er|re|e|ème)\b").unwrap());
+static ES_ORDINAL_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(\d+)\.?[ºª]").unwrap());
+
+static EN_DECADE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:\B['’]|\b)(\d{1,3}0)s\b").unwrap());
+
+static NUMBER_POINT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!("(-?){}", NUMBER_POINT)).unwrap());
+static NUMBER_COMMA_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!("(-?){}", NUMBER_COMMA)).unwrap());
+
+/// Matches any of `table`'s abbreviations as a whole word, longest first.
+fn alternation(table: &[(&str, &str)]) -> Regex {
+    let mut keys: Vec<&str> = table.iter().map(|x| x.0).collect();
+    keys.sort_by_key(|x| std::cmp::Reverse(x.len()));
+    let keys: Vec<String> = keys
+        .into_iter()
+        .map(|x| match x.ends_with(char::is_alphabetic) {
+            true => format!(r"{}\b", regex::escape(x)),
+            false => regex::escape(x),
+        })
+        .collect();
+    Regex::new(&format!(r"\b(?:{})", keys.join("|"))).unwrap()
+}
+
+fn units_regex(table: &[Unit], number: &str) -> Regex {
+    let mut keys: Vec<&str> = table.iter().map(|x| x.0).collect();
+    keys.sort_by_key(|x| std::cmp::Reverse(x.len()));
+    let keys: Vec<String> = keys.into_iter().map(regex::escape).collect();
+    Regex::new(&format!(r"(-?){}\s?({})", number, keys.join("|"))).unwrap()
+}
+
+/// Replaces every match of `re` with `f`, which may return `None` to keep the match.
+fn replace(text: &str, re: &Regex, mut f: impl FnMut(&Captures) -> Option<String>) -> String {
+    re.replace_all(text, |caps: &Captures| {
+        f(caps).unwrap_or_else(|| caps[0].to_string())
+    })
+    .into_owned()
+}
+
+fn char_before(text: &str, index: usize) -> Option<char> {
+    text[..index].chars().next_back()
+}
+
+fn char_after(text: &str, index: usize) -> Option<char> {
+    text[index..].chars().next()
+}
+
+fn minus(language: Language) -> &'static str {
+    match language {
+        Language::English { .. } | Language::German => "minus",
+        Language::French => "moins",
+        Language::Spanish => "menos",
+    }
+}
+
+/// Spells out a numeral as matched by [`NUMBER_POINT`] or [`NUMBER_COMMA`].
+fn spell(whole: &str, fraction: Option<&str>, language: Language) -> String {
+    let digits: String = whole.chars().filter(char::is_ascii_digit).collect();
+    match digits.parse::<u64>() {
+        Ok(n) if digits.len() == 1 || !digits.starts_with('0') => match fraction {
+            Some(fraction) => decimal(n, fraction, language),
+            None => cardinal(n, language),
+        },
+        // Too long, or with leading zeros like a code: read digit by digit.
+        _ => digits
+            .chars()
+            .map(|x| cardinal(x.to_digit(10).unwrap_or(0) as u64, language))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+pub fn abbreviations(text: &str, language: Language) -> String {
+    let (re, table): (&Regex, _) = match language {
+        Language::English { .. } => (&EN_ABBREVIATION_RE, EN_ABBREVIATIONS),
+        Language::German => (&DE_ABBREVIATION_RE, DE_ABBREVIATIONS),
+        Language::French => (&FR_ABBREVIATION_RE, FR_ABBREVIATIONS),
+        Language::Spanish => (&ES_ABBREVIATION_RE, ES_ABBREVIATIONS),
+    };
+    let text = replace(text, re, |caps| {
+        let whole = caps.get(0)?;
+        // "St. Louis" is a saint, "Main St." a street.
+        if BEFORE_NAMES.contains(&whole.as_str()) {
+            let rest = text[whole.end()..].trim_start();
+            if rest.len() == text.len() - whole.end() || !rest.starts_with(char::is_uppercase) {
+                return None;
+            }
+        }
+        table
+            .iter()
+            .find(|x| x.0 == whole.as_str())
+            .map(|x| x.1.to_string())
+    });
+
+    let number = match language {
+        Language::English { .. } => "number",
+        Language::German => "Nummer",
+        Language::French => "numéro",
+        Language::Spanish => "número",
+    };
+    replace(&text, &NUMBER_SIGN_RE, |caps| {
+        Some(format!("{} {}", number, &caps[2]))
+    })
+}
+
+pub fn currency(text: &str, language: Language) -> String {
+    let (table, and) = match language {
+        Language::English { .. } => (EN_CURRENCIES, "and"),
+        Language::German => (DE_CURRENCIES, "und"),
+        Language::French => (FR_CURRENCIES, "et"),
+        Language::Spanish => (ES_CURRENCIES, "con"),
+    };
+    let (prefix, suffix): (&Regex, &Regex) = match language.decimal_comma() {
+        false => (&CURRENCY_PREFIX_POINT_RE, &CURRENCY_SUFFIX_POINT_RE),
+        true => (&CURRENCY_PREFIX_COMMA_RE, &CURRENCY_SUFFIX_COMMA_RE),
+    };
+
+    let say = |symbol: &str, whole: &str, fraction: Option<&str>| -> Option<String> {
+        let currency = table.iter().find(|x| symbol.starts_with(x.0))?;
+        let (_, one, many, one_minor, many_minor) = *currency;
+        let whole: u64 = whole
+            .chars()
+            .filter(char::is_ascii_digit)
+            .collect::<String>()
+            .parse()
+            .ok()?;
+
+        // Without a minor unit, or with more than two decimals, read it as a number.
+        if fraction.is_some_and(|x| x.len() > 2 || one_minor.is_empty()) {
+            return Some(format!("{} {}", decimal(whole, fraction?, language), many));
+        }
+
+        let minor: u64 = match fraction {
+            Some(x) if x.len() == 1 => x.parse::<u64>().ok()? * 10,
+            Some(x) => x.parse().ok()?,
+            None => 0,
+        };
+
+        let mut parts = Vec::new();
+        if whole > 0 || minor == 0 {
+            let noun = if whole == 1 { one } else { many };
+            parts.push(format!("{} {}", count(whole, language), noun));
+        }
+        if minor > 0 {
+            let noun = if minor == 1 { one_minor } else { many_minor };
+            parts.push(format!("{} {}", count(minor, language), noun));
+        }
+        Some(parts.join(&format!(" {} ", and)))
+    };
+
+    let text = replace(text, prefix, |caps| {
+        say(&caps[1], &caps[2], caps.get(3).map(|x| x.as_str()))
+    });
+    replace(&text, suffix, |caps| {
+        say(&caps[3], &caps[1], caps.get(2).map(|x| x.as_str()))
+    })
+}
+
+fn month_name(month: u64, language: Language) -> &'static str {
+    let months = match language {
+        Language::English { .. } => &EN_MONTHS,
+        Language::German => &DE_MONTHS,
+        Language::French => &FR_MONTHS,
+        Language::Spanish => &ES_MONTHS,
+    };
+    months[month as usize - 1]
+}
+
+fn say_date(day: u64, month: u64, year_: u64, language: Language) -> Option<String> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let month = month_name(month, language);
+    let year_ = year(year_, language);
+    Some(match language {
+        Language::English { us: true } => {
+            format!("{} {}, {}", month, ordinal(day, language), year_)
+        }
+        Language::English { us: false } => {
+            format!("the {} of {}, {}", ordinal(day, language), month, year_)
+        }
+        Language::German => format!("{} {} {}", ordinal(day, language), month, year_),
+        Language::French => {
+            let day = match day {
+                1 => ordinal(day, language),
+                _ => cardinal(day, language),
+            };
+            format!("{} {} {}", day, month, year_)
+        }
+        Language::Spanish => {
+            let day = match day {
+                1 => ordinal(day, language),
+                _ => cardinal(day, language),
+            };
+            format!("{} de {} de {}", day, month, year_)
+        }
+    })
+}
+
+pub fn dates(text: &str, language: Language) -> String {
+    let text = replace(text, &ISO_DATE_RE, |caps| {
+        say_date(
+            caps[3].parse().ok()?,
+            caps[2].parse().ok()?,
+            caps[1].parse().ok()?,
+            language,
+        )
+    });
+
+    let text = replace(&text, &DATE_RE, |caps| {
+        let separator = &caps[2];
+        // Two digit years are only taken as dates with slashes, so versions like 1.2.34 survive.
+        if separator != &caps[4] || (caps[5].len() == 2 && separator != "/") {
+            return None;
+        }
+
+        let first: u64 = caps[1].parse().ok()?;
+        let second: u64 = caps[3].parse().ok()?;
+        let mut year_: u64 = caps[5].parse().ok()?;
+        if caps[5].len() == 2 {
+            year_ += if year_ < 50 { 2000 } else { 1900 };
+        }
+
+        // Month first in the US, unless that can't be a date.
+        let month_first = language == Language::English { us: true } && first <= 12 || second > 12;
+        match month_first {
+            true => say_date(second, first, year_, language),
+            false => say_date(first, second, year_, language),
+        }
+    });
+
+    match language {
+        Language::German => replace(&text, &DE_DATE_RE, |caps| {
+            Some(format!(
+                "{} {}",
+                ordinal(caps[1].parse().ok()?, language),
+                &caps[2]
+            ))
+        }),
+        _ => text,
+    }
+}
+
+pub fn times(text: &str, language: Language) -> String {
+    replace(text, &TIME_RE, |caps| {
+        // Leave times with seconds and ratios like 1:2:3 alone.
+        let end = caps.get(0)?.end();
+        if char_after(text, end) == Some(':') {
+            return None;
+        }
+
+        let hours: u64 = caps[1].parse().ok()?;
+        let minutes: u64 = caps[2].parse().ok()?;
+        let h = cardinal(hours, language);
+        let m = cardinal(minutes, language);
+        Some(match language {
+            Language::English { .. } => match minutes {
+                0 => format!("{} o'clock", h),
+                1..=9 => format!("{} oh {}", h, m),
+                _ => format!("{} {}", h, m),
+            },
+            Language::German => {
+                let h = count(hours, language);
+                match minutes {
+                    0 => format!("{} Uhr", h),
+                    _ => format!("{} Uhr {}", h, m),
+                }
+            }
+            Language::French => {
+                let h = match hours {
+                    1 => "une heure".to_string(),
+                    0 => format!("{} heure", h),
+                    _ => format!("{} heures", h),
+                };
+                match minutes {
+                    0 => h,
+                    _ => format!("{} {}", h, m),
+                }
+            }
+            Language::Spanish => {
+                let h = match hours {
+                    1 => "una".to_string(),
+                    _ => h,
+                };
+                match minutes {
+                    0 => format!("{} en punto", h),
+                    _ => format!("{} y {}", h, m),
+                }
+            }
+        })
+    })
+}
+
+pub fn units(text: &str, language: Language) -> String {
+    let (re, table): (&Regex, _) = match language {
+        Language::English { .. } => (&EN_UNIT_RE, EN_UNITS),
+        Language::German => (&DE_UNIT_RE, DE_UNITS),
+        Language::French => (&FR_UNIT_RE, FR_UNITS),
+        Language::Spanish => (&ES_UNIT_RE, ES_UNITS),
+    };
+
+    let text = replace(text, re, |caps| {
+        let whole = caps.get(0)?;
+        // "5 min" is a unit, "5 minutes" and "3 mice" are not.
+        if char_after(text, whole.end()).is_some_and(char::is_alphanumeric)
+            || char_before(text, whole.start()).is_some_and(char::is_alphanumeric)
+        {
+            return None;
+        }
+
+        // "5 s" is seconds, "1990s" and "80s" are decades.
+        let unit_start = caps.get(4)?.start();
+        if &caps[4] == "s" && !char_before(text, unit_start).is_some_and(char::is_whitespace) {
+            return None;
+        }
+
+        let unit = table.iter().find(|x| x.0 == &caps[4])?;
+        let negative = !caps[1].is_empty();
+        if !negative && &caps[2] == "1" && caps.get(3).is_none() {
+            return Some(unit.1.to_string());
+        }
+
+        let number = match caps.get(3) {
+            Some(fraction) => spell(&caps[2], Some(fraction.as_str()), language),
+            None => match caps[2].replace([',', '.'], "").parse() {
+                Ok(n) if !caps[2].starts_with('0') => count(n, language),
+                _ => spell(&caps[2], None, language),
+            },
+        };
+        Some(match negative {
+            true => format!("{} {} {}", minus(language), number, unit.2),
+            false => format!("{} {}", number, unit.2),
+        })
+    });
+
+    replace(&text, &RATE_UNIT_RE, |caps| {
+        table
+            .iter()
+            .find(|x| x.0 == &caps[1])
+            .map(|x| x.2.to_string())
+    })
+}
+
+pub fn ordinals(text: &str, language: Language) -> String {
+    let re: &Regex = match language {
+        Language::English { .. } => &EN_ORDINAL_RE,
+        Language::French => &FR_ORDINAL_RE,
+        Language::Spanish => &ES_ORDINAL_RE,
+        // A German ordinal is a number followed by a full stop, which can't be
+        // told apart from the end of a sentence.
+        Language::German => return text.to_string(),
+    };
+
+    replace(text, re, |caps| {
+        let n: u64 = caps[1].parse().ok()?;
+        match (language, caps.get(2).map(|x| x.as_str())) {
+            (Language::French, Some("re")) if n == 1 => Some("première".to_string()),
+            _ => Some(ordinal(n, language)),
+        }
+    })
+}
+
+/// Decades such as `1990s` and `'80s`, in English.
+pub fn decades(text: &str, language: Language) -> String {
+    if !matches!(language, Language::English { .. }) {
+        return text.to_string();
+    }
+
+    replace(text, &EN_DECADE_RE, |caps| {
+        let n: u64 = caps[1].parse().ok()?;
+        let words = match caps[1].len() {
+            2 => cardinal(n, language),
+            4 => year(n, language),
+            _ => return None,
+        };
+        Some(match words.strip_suffix('y') {
+            Some(stem) => format!("{}ies", stem),
+            None => format!("{}s", words),
+        })
+    })
+}
+
+/// Whether the number at `start..end` of `text` is only part of a token
+/// like `2.0.1`, `10:60` or `13/13/2020` that no rule could read as a whole.
+fn partial_token(text: &str, start: usize, end: usize) -> bool {
+    let separator = |x: Option<char>| x.is_some_and(|x| ".,:/".contains(x));
+    let digit = |x: Option<char>| x.is_some_and(|x| x.is_ascii_digit());
+    let before = char_before(text, start);
+    let after = char_after(text, end);
+    (separator(before) && digit(before.and_then(|x| char_before(text, start - x.len_utf8()))))
+        || (separator(after) && digit(after.and_then(|x| char_after(text, end + x.len_utf8()))))
+}
+
+pub fn numbers(text: &str, language: Language) -> String {
+    let re: &Regex = match language.decimal_comma() {
+        false => &NUMBER_POINT_RE,
+        true => &NUMBER_COMMA_RE,
+    };
+
+    replace(text, re, |caps| {
+        let whole = caps.get(0)?;
+        if partial_token(text, whole.start(), whole.end()) {
+            return None;
+        }
+        let before = char_before(text, whole.start());
+        let after = char_after(text, whole.end());
+
+        // A minus straight after a word is a hyphen, as in "COVID-19".
+        let hyphen = before.is_some_and(char::is_alphanumeric);
+        let sign = match (caps[1].is_empty(), hyphen) {
+            (true, _) => String::new(),
+            (false, true) => "-".to_string(),
+            (false, false) => format!("{} ", minus(language)),
+        };
+
+        let mut words = format!(
+            "{}{}",
+            sign,
+            spell(&caps[2], caps.get(3).map(|x| x.as_str()), language)
+        );
+        // Keep numbers glued to letters, as in "MP3", apart from them.
+        if caps[1].is_empty() && before.is_some_and(char::is_alphabetic) {
+            words.insert(0, ' ');
+        }
+        if after.is_some_and(char::is_alphabetic) {
+            words.push(' ');
+        }
+        Some(words)
+    })
+}
@@ -0,0 +1,575 @@
+//! A parser for the subset of SSML the engines can render.
+//!
+//! `<speak>`, `<break>`, `<prosody>`, `<emphasis>`, `<say-as>`, `<voice>`,
+//! `<p>` and `<s>` are understood. Any other element is dropped with a
+//! warning, keeping its text unless it is one of [`DROPPED`].
+use crate::{models::Prosody, text::normalize::Normalizer, TtsError};
+use std::{ops::RangeInclusive, time::Duration};
+
+/// Elements whose content should never be read out.
+const DROPPED: &[&str] = &["audio", "desc", "mark", "lexicon", "meta", "metadata"];
+/// What `<prosody>` attributes are clamped to, past which speech is no longer
+/// intelligible anyway.
+const RATE_RANGE: RangeInclusive<f32> = 0.25..=4.0;
+/// In semitones.
+const PITCH_RANGE: RangeInclusive<f32> = -24.0..=24.0;
+/// In dB.
+const VOLUME_RANGE: RangeInclusive<f32> = -96.0..=24.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emphasis {
+    Strong,
+    Moderate,
+    None,
+    Reduced,
+}
+
+impl Emphasis {
+    /// How the emphasis is rendered by engines without SSML support.
+    pub fn prosody(&self) -> Prosody {
+        match self {
+            Emphasis::Strong => Prosody::new(0.9, 1.0, 4.0),
+            Emphasis::Moderate => Prosody::new(0.95, 0.5, 2.0),
+            Emphasis::None => Prosody::default(),
+            Emphasis::Reduced => Prosody::new(1.1, -0.5, -4.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Text(String),
+    Break(Duration),
+    Prosody(Prosody, Vec<Node>),
+    Emphasis(Emphasis, Vec<Node>),
+    SayAs {
+        interpret_as: String,
+        format: Option<String>,
+        text: String,
+    },
+    /// Children spoken with the named voice.
+    Voice(String, Vec<Node>),
+}
+
+/// A piece of the document ready to be synthesized on its own.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    Text {
+        text: String,
+        prosody: Prosody,
+        voice: Option<String>,
+    },
+    Break(Duration),
+}
+
+/// A parsed SSML document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ssml {
+    /// The `xml:lang` of the root element, if any.
+    pub language: Option<String>,
+    pub nodes: Vec<Node>,
+    /// Everything that was ignored while parsing.
+    pub warnings: Vec<String>,
+}
+
+impl Ssml {
+    pub fn parse(input: &str) -> Result<Self, TtsError> {
+        Parser::new(input).parse()
+    }
+
+    /// Flattens the document into text and breaks, each text carrying the
+    /// prosody and voice it should be spoken with.
+    pub fn segments(&self) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        flatten(&self.nodes, Prosody::default(), None, &mut segments);
+
+        segments
+            .into_iter()
+            .filter_map(|segment| match segment {
+                Segment::Text {
+                    text,
+                    prosody,
+                    voice,
+                } => {
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    (!text.is_empty()).then_some(Segment::Text {
+                        text,
+                        prosody,
+                        voice,
+                    })
+                }
+                x => Some(x),
+            })
+            .collect()
+    }
+
+    /// The text of the document with all markup removed.
+    pub fn text(&self) -> String {
+        self.segments()
+            .into_iter()
+            .filter_map(|x| match x {
+                Segment::Text { text, .. } => Some(text),
+                Segment::Break(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// A copy with all text run through `normalizer`, in the document's
+    /// `xml:lang` when it has one and in `language` otherwise.
+    pub fn normalized(&self, normalizer: &Normalizer, language: Option<&str>) -> Ssml {
+        let language = self.language.as_deref().or(language);
+        let mut ssml = self.clone();
+        normalize_nodes(&mut ssml.nodes, &|text| {
+            normalizer.normalize(text, language)
+        });
+        ssml
+    }
+}
+
+fn normalize_nodes(nodes: &mut [Node], f: &dyn Fn(&str) -> String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) | Node::SayAs { text, .. } => *text = f(text),
+            Node::Break(_) => {}
+            Node::Prosody(_, children) | Node::Emphasis(_, children) | Node::Voice(_, children) => {
+                normalize_nodes(children, f)
+            }
+        }
+    }
+}
+
+fn flatten(nodes: &[Node], prosody: Prosody, voice: Option<&str>, out: &mut Vec<Segment>) {
+    for node in nodes {
+        match node {
+            Node::Text(text) | Node::SayAs { text, .. } => match out.last_mut() {
+                Some(Segment::Text {
+                    text: last,
+                    prosody: p,
+                    voice: v,
+                }) if *p == prosody && v.as_deref() == voice => last.push_str(text),
+                _ => out.push(Segment::Text {
+                    text: text.clone(),
+                    prosody,
+                    voice: voice.map(str::to_string),
+                }),
+            },
+            Node::Break(duration) => match out.last_mut() {
+                Some(Segment::Break(last)) => *last += *duration,
+                _ => out.push(Segment::Break(*duration)),
+            },
+            Node::Prosody(p, children) => flatten(children, prosody.combine(p), voice, out),
+            Node::Emphasis(level, children) => {
+                flatten(children, prosody.combine(&level.prosody()), voice, out)
+            }
+            Node::Voice(name, children) => flatten(children, prosody, Some(name), out),
+        }
+    }
+}
+
+/// Renders segments back into markup for engines that take SSML, leaving out
+/// the voices, which such engines set per request.
+pub fn to_markup(segments: &[Segment]) -> String {
+    let mut markup = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Text { text, prosody, .. } if prosody.is_neutral() => {
+                markup.push_str(&escape(text))
+            }
+            Segment::Text { text, prosody, .. } => markup.push_str(&format!(
+                "<prosody rate='{:+}%' pitch='{:+}st' volume='{:+}%'>{}</prosody>",
+                ((prosody.rate - 1.0) * 100.0).round() as i32,
+                prosody.pitch,
+                ((crate::audio::db_to_gain(prosody.volume) - 1.0) * 100.0).round() as i32,
+                escape(text)
+            )),
+            Segment::Break(duration) => {
+                markup.push_str(&format!("<break time='{}ms'/>", duration.as_millis()))
+            }
+        }
+        markup.push(' ');
+    }
+    markup.trim_end().to_string()
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|x| u32::from_str_radix(x, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|x| x.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    warnings: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        return Self {
+            input,
+            pos: 0,
+            warnings: Vec::new(),
+        };
+    }
+
+    fn parse(mut self) -> Result<Ssml, TtsError> {
+        let mut stack = vec![Element {
+            name: String::new(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }];
+        let mut language = None;
+
+        while self.pos < self.input.len() {
+            let rest = &self.input[self.pos..];
+            if !rest.starts_with('<') {
+                let end = rest.find('<').unwrap_or(rest.len());
+                let text = unescape(&rest[..end]);
+                stack.last_mut().unwrap().children.push(Node::Text(text));
+                self.pos += end;
+                continue;
+            }
+
+            if let Some(skip) = [("<!--", "-->"), ("<?", "?>"), ("<!", ">")]
+                .iter()
+                .find(|(open, _)| rest.starts_with(open))
+            {
+                let end = rest
+                    .find(skip.1)
+                    .ok_or_else(|| self.error("Unclosed markup"))?;
+                self.pos += end + skip.1.len();
+                continue;
+            }
+
+            let end = rest.find('>').ok_or_else(|| self.error("Unclosed tag"))?;
+            let tag = &rest[1..end];
+            self.pos += end + 1;
+
+            if let Some(name) = tag.strip_prefix('/') {
+                let element = stack.pop().filter(|_| !stack.is_empty());
+                let element = match element {
+                    Some(x) if x.name == name.trim() => x,
+                    _ => return Err(self.error(&format!("Unexpected </{}>", name.trim()))),
+                };
+                let nodes = self.element(element);
+                stack.last_mut().unwrap().children.extend(nodes);
+                continue;
+            }
+
+            let self_closing = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let (name, attributes) = self.tag(tag)?;
+            let element = Element {
+                name,
+                attributes,
+                children: Vec::new(),
+            };
+
+            if element.name == "speak" && stack.len() == 1 {
+                language = element.attribute("xml:lang").map(str::to_string);
+            }
+            if self_closing {
+                let nodes = self.element(element);
+                stack.last_mut().unwrap().children.extend(nodes);
+            } else {
+                stack.push(element);
+            }
+        }
+
+        if stack.len() > 1 {
+            let name = &stack.last().unwrap().name;
+            return Err(self.error(&format!("Unclosed <{}>", name)));
+        }
+
+        Ok(Ssml {
+            language,
+            nodes: stack.pop().unwrap().children,
+            warnings: self.warnings,
+        })
+    }
+
+    fn error(&self, message: &str) -> TtsError {
+        TtsError::InvalidSsml(format!("{} at byte {}", message, self.pos))
+    }
+
+    fn tag(&self, tag: &str) -> Result<(String, Vec<(String, String)>), TtsError> {
+        let tag = tag.trim();
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let name = tag[..name_end].to_string();
+
+        let mut attributes = Vec::new();
+        let mut rest = tag[name_end..].trim_start();
+        while !rest.is_empty() {
+            let eq = rest
+                .find('=')
+                .ok_or_else(|| self.error(&format!("Malformed attribute in <{}>", name)))?;
+            let key = rest[..eq].trim().to_string();
+            let value = rest[eq + 1..].trim_start();
+            let quote = value
+                .chars()
+                .next()
+                .filter(|x| *x == '"' || *x == '\'')
+                .ok_or_else(|| self.error(&format!("Unquoted attribute in <{}>", name)))?;
+            let close = value[1..]
+                .find(quote)
+                .ok_or_else(|| self.error(&format!("Unclosed attribute in <{}>", name)))?;
+            attributes.push((key, unescape(&value[1..close + 1])));
+            rest = value[close + 2..].trim_start();
+        }
+
+        Ok((name, attributes))
+    }
+
+    /// Turns a closed element into the nodes it stands for.
+    fn element(&mut self, element: Element) -> Vec<Node> {
+        match element.name.as_str() {
+            "speak" => element.children,
+            "p" | "s" => {
+                let mut nodes = element.children;
+                nodes.push(Node::Text(" ".to_string()));
+                nodes
+            }
+            "break" => vec![Node::Break(self.break_duration(&element))],
+            "prosody" => {
+                let prosody = self.prosody(&element);
+                vec![Node::Prosody(prosody, element.children)]
+            }
+            "emphasis" => {
+                let level = match element.attribute("level").unwrap_or("moderate") {
+                    "strong" => Emphasis::Strong,
+                    "moderate" => Emphasis::Moderate,
+                    "none" => Emphasis::None,
+                    "reduced" => Emphasis::Reduced,
+                    x => {
+                        self.warn(format!("Unknown emphasis level \"{}\"", x));
+                        Emphasis::Moderate
+                    }
+                };
+                vec![Node::Emphasis(level, element.children)]
+            }
+            "say-as" => {
+                let interpret_as = element.attribute("interpret-as").unwrap_or("").to_string();
+                let text = plain_text(&element.children);
+                let text = match interpret_as.as_str() {
+                    "characters" | "spell-out" | "verbatim" => text
+                        .chars()
+                        .filter(|x| !x.is_whitespace())
+                        .map(String::from)
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    _ => text,
+                };
+                vec![Node::SayAs {
+                    format: element.attribute("format").map(str::to_string),
+                    interpret_as,
+                    text,
+                }]
+            }
+            "voice" => match element.attribute("name") {
+                Some(name) => vec![Node::Voice(name.to_string(), element.children)],
+                None => {
+                    self.warn("<voice> without a name is ignored".to_string());
+                    element.children
+                }
+            },
+            name if DROPPED.contains(&name) => {
+                self.warn(format!("Unsupported element <{}> dropped", name));
+                Vec::new()
+            }
+            name => {
+                self.warn(format!("Unsupported element <{}> ignored", name));
+                element.children
+            }
+        }
+    }
+
+    fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    fn break_duration(&mut self, element: &Element) -> Duration {
+        if let Some(time) = element.attribute("time") {
+            match parse_time(time) {
+                Some(x) => return x,
+                None => self.warn(format!("Invalid break time \"{}\"", time)),
+            }
+        }
+
+        let millis = match element.attribute("strength").unwrap_or("medium") {
+            "none" => 0,
+            "x-weak" => 250,
+            "weak" => 500,
+            "medium" => 750,
+            "strong" => 1000,
+            "x-strong" => 1250,
+            x => {
+                self.warn(format!("Unknown break strength \"{}\"", x));
+                750
+            }
+        };
+        Duration::from_millis(millis)
+    }
+
+    fn prosody(&mut self, element: &Element) -> Prosody {
+        let mut prosody = Prosody::default();
+        for (key, value) in &element.attributes {
+            let (parsed, range, field) = match key.as_str() {
+                "rate" => (parse_rate(value), RATE_RANGE, &mut prosody.rate),
+                "pitch" => (parse_pitch(value), PITCH_RANGE, &mut prosody.pitch),
+                "volume" => (parse_volume(value), VOLUME_RANGE, &mut prosody.volume),
+                _ => {
+                    self.warn(format!("Unsupported prosody {}=\"{}\"", key, value));
+                    continue;
+                }
+            };
+            match parsed {
+                Some(x) if range.contains(&x) => *field = x,
+                Some(x) => {
+                    *field = x.clamp(*range.start(), *range.end());
+                    self.warn(format!(
+                        "Prosody {}=\"{}\" out of range, clamped to {}",
+                        key, value, field
+                    ));
+                }
+                None => self.warn(format!("Unsupported prosody {}=\"{}\"", key, value)),
+            }
+        }
+        prosody
+    }
+}
+
+fn plain_text(nodes: &[Node]) -> String {
+    let mut segments = Vec::new();
+    flatten(nodes, Prosody::default(), None, &mut segments);
+    segments
+        .into_iter()
+        .filter_map(|x| match x {
+            Segment::Text { text, .. } => Some(text),
+            Segment::Break(_) => None,
+        })
+        .collect()
+}
+
+fn parse_time(time: &str) -> Option<Duration> {
+    let time = time.trim();
+    let (number, scale) = match time.strip_suffix("ms") {
+        Some(ms) => (ms, 1000.0),
+        None => (time.strip_suffix('s')?, 1.0),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|x| x.is_finite() && *x >= 0.0)
+        .and_then(|x| Duration::try_from_secs_f64(x / scale).ok())
+}
+
+/// A relative percentage such as `+20%`, as a multiplier.
+fn parse_percent(value: &str) -> Option<f32> {
+    let percent: f32 = value.strip_suffix('%')?.parse().ok()?;
+    Some(if value.starts_with(['+', '-']) {
+        1.0 + percent / 100.0
+    } else {
+        percent / 100.0
+    })
+}
+
+fn parse_rate(value: &str) -> Option<f32> {
+    match value {
+        "x-slow" => Some(0.5),
+        "slow" => Some(0.75),
+        "medium" | "default" => Some(1.0),
+        "fast" => Some(1.25),
+        "x-fast" => Some(1.5),
+        x if x.ends_with('%') => parse_percent(x),
+        x => x.parse().ok(),
+    }
+    .filter(|x| x.is_finite() && *x > 0.0)
+}
+
+fn parse_pitch(value: &str) -> Option<f32> {
+    match value {
+        "x-low" => Some(-6.0),
+        "low" => Some(-3.0),
+        "medium" | "default" => Some(0.0),
+        "high" => Some(3.0),
+        "x-high" => Some(6.0),
+        x if x.ends_with("st") => x.trim_end_matches("st").parse().ok(),
+        x if x.ends_with('%') => parse_percent(x)
+            .filter(|x| *x > 0.0)
+            .map(|x| 12.0 * x.log2()),
+        _ => None,
+    }
+    .filter(|x| x.is_finite())
+}
+
+fn parse_volume(value: &str) -> Option<f32> {
+    match value {
+        "silent" => Some(-96.0),
+        "x-soft" => Some(-12.0),
+        "soft" => Some(-6.0),
+        "medium" | "default" => Some(0.0),
+        "loud" => Some(6.0),
+        "x-loud" => Some(12.0),
+        x if x.ends_with("dB") => x.trim_end_matches("dB").parse().ok(),
+        x if x.ends_with('%') => parse_percent(x)
+            .filter(|x| *x > 0.0)
+            .map(crate::audio::gain_to_db),
+        _ => None,
+    }
+    .filter(|x| x.is_finite())
+}
@@ -0,0 +1,208 @@
+//! When each word and sentence of synthesized speech is spoken, for
+//! highlighting text along with playback or exporting subtitles.
+use super::SENTENCE_ENDS;
+#[cfg(feature = "msedge")]
+use msedge_tts::tts::AudioMetadata;
+use std::{fmt::Write, time::Duration};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BoundaryKind {
+    Word,
+    /// Punctuation the engine reported on its own, such as a comma.
+    Punctuation,
+    Sentence,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Boundary {
+    pub kind: BoundaryKind,
+    pub text: String,
+    /// From the start of the audio.
+    pub offset: Duration,
+    pub duration: Duration,
+}
+
+impl Boundary {
+    pub fn new(
+        kind: BoundaryKind,
+        text: impl Into<String>,
+        offset: Duration,
+        duration: Duration,
+    ) -> Self {
+        return Self {
+            kind,
+            text: text.into(),
+            offset,
+            duration,
+        };
+    }
+
+    pub fn end(&self) -> Duration {
+        self.offset + self.duration
+    }
+}
+
+/// Boundaries ordered by offset, a sentence coming before its first word.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timeline {
+    pub boundaries: Vec<Boundary>,
+}
+
+impl Timeline {
+    /// Orders `boundaries` and, when there are words but no sentences,
+    /// groups the words into sentences at their closing punctuation.
+    pub fn new(mut boundaries: Vec<Boundary>) -> Self {
+        if !boundaries.iter().any(|x| x.kind == BoundaryKind::Sentence) {
+            let sentences = sentences(&boundaries);
+            boundaries.extend(sentences);
+        }
+        boundaries.sort_by_key(|x| (x.offset, x.kind != BoundaryKind::Sentence));
+        return Self { boundaries };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boundaries.is_empty()
+    }
+
+    pub fn of_kind(&self, kind: BoundaryKind) -> impl Iterator<Item = &Boundary> {
+        self.boundaries.iter().filter(move |x| x.kind == kind)
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &Boundary> {
+        self.of_kind(BoundaryKind::Word)
+    }
+
+    pub fn sentences(&self) -> impl Iterator<Item = &Boundary> {
+        self.of_kind(BoundaryKind::Sentence)
+    }
+
+    /// The last boundary of `kind` to start by `position`.
+    pub fn at(&self, kind: BoundaryKind, position: Duration) -> Option<&Boundary> {
+        self.of_kind(kind)
+            .take_while(|x| x.offset <= position)
+            .last()
+    }
+
+    /// Appends the boundaries of `other`, which starts `offset` into this audio.
+    pub fn extend(&mut self, other: Timeline, offset: Duration) {
+        self.boundaries
+            .extend(other.boundaries.into_iter().map(|mut x| {
+                x.offset += offset;
+                x
+            }));
+    }
+
+    /// Stretches every offset and duration by `factor`, as when the audio is
+    /// slowed down to `factor` times its length.
+    pub fn scale(&mut self, factor: f64) {
+        for boundary in &mut self.boundaries {
+            boundary.offset = boundary.offset.mul_f64(factor);
+            boundary.duration = boundary.duration.mul_f64(factor);
+        }
+    }
+
+    /// One SubRip cue per boundary of `kind`.
+    pub fn to_srt(&self, kind: BoundaryKind) -> String {
+        let mut srt = String::new();
+        for (index, boundary) in self.of_kind(kind).enumerate() {
+            let _ = write!(
+                srt,
+                "{}\n{} --> {}\n{}\n\n",
+                index + 1,
+                timestamp(boundary.offset, ','),
+                timestamp(boundary.end(), ','),
+                boundary.text
+            );
+        }
+        srt
+    }
+
+    /// One WebVTT cue per boundary of `kind`.
+    pub fn to_webvtt(&self, kind: BoundaryKind) -> String {
+        let mut vtt = String::from("WEBVTT\n\n");
+        for boundary in self.of_kind(kind) {
+            let text = boundary
+                .text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            let _ = write!(
+                vtt,
+                "{} --> {}\n{}\n\n",
+                timestamp(boundary.offset, '.'),
+                timestamp(boundary.end(), '.'),
+                text
+            );
+        }
+        vtt
+    }
+}
+
+/// Converts MSEdge's word boundaries, whose offsets are in ticks of 100ns.
+/// The service only reports words and punctuation, so sentences are derived.
+#[cfg(feature = "msedge")]
+impl From<&[AudioMetadata]> for Timeline {
+    fn from(metadata: &[AudioMetadata]) -> Self {
+        let boundaries = metadata
+            .iter()
+            .filter_map(|x| {
+                let kind = match (x.metadata_type.as_deref(), x.boundary_type.as_deref()) {
+                    (Some("SentenceBoundary"), _) | (_, Some("SentenceBoundary")) => {
+                        BoundaryKind::Sentence
+                    }
+                    (_, Some("PunctuationBoundary")) => BoundaryKind::Punctuation,
+                    (Some("WordBoundary"), _) => BoundaryKind::Word,
+                    _ => return None,
+                };
+                Some(Boundary::new(
+                    kind,
+                    x.text.clone()?,
+                    Duration::from_nanos(x.offset * 100),
+                    Duration::from_nanos(x.duration * 100),
+                ))
+            })
+            .collect();
+        Timeline::new(boundaries)
+    }
+}
+
+/// Groups words and punctuation into sentences.
+fn sentences(boundaries: &[Boundary]) -> Vec<Boundary> {
+    let mut sentences = Vec::new();
+    let mut current: Option<Boundary> = None;
+    for boundary in boundaries
+        .iter()
+        .filter(|x| x.kind != BoundaryKind::Sentence)
+    {
+        let sentence = current.get_or_insert_with(|| {
+            Boundary::new(BoundaryKind::Sentence, "", boundary.offset, Duration::ZERO)
+        });
+        let attached = boundary.kind == BoundaryKind::Punctuation
+            || boundary.text.starts_with(|c: char| !c.is_alphanumeric());
+        if !sentence.text.is_empty() && !attached {
+            sentence.text.push(' ');
+        }
+        sentence.text.push_str(&boundary.text);
+        sentence.duration = boundary.end().saturating_sub(sentence.offset);
+
+        if boundary.text.ends_with(SENTENCE_ENDS) {
+            sentences.extend(current.take());
+        }
+    }
+
+    sentences.extend(current);
+    sentences
+}
+
+/// `HH:MM:SS,mmm`, with `separator` before the milliseconds.
+fn timestamp(time: Duration, separator: char) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
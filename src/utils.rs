@@ -17,27 +17,32 @@
 // LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
-use crate::models::{Spec::Wav, SynthesizedAudio};
-use hound::{Sample, WavReader};
-use rodio::{buffer::SamplesBuffer, cpal::FromSample, Decoder, OutputStream, Sink};
-use std::{error::Error, io::Write, path::PathBuf};
-
-pub fn read_wav_file<T: Sample + Send + rodio::Sample>(
-    path: &PathBuf,
-) -> Result<SynthesizedAudio<T>, Box<dyn Error>> {
-    let mut reader = WavReader::open(path)?;
-    let mut samples: Vec<T> = Vec::new();
-
-    reader.samples::<T>().for_each(|s| {
-        if let Ok(sample) = s {
-            samples.push(sample as T);
-        }
-    });
+use crate::{
+    models::{Spec, SynthesizedAudio},
+    TtsError,
+};
+use hound::{SampleFormat, WavSpec};
+use rodio::{Decoder, Source};
+use std::io::Cursor;
+
+/// Decodes an encoded audio file (MP3, WAV, FLAC or Vorbis) held in memory.
+#[cfg_attr(not(any(feature = "gtts", feature = "msedge")), allow(dead_code))]
+pub fn decode_audio(bytes: Vec<u8>) -> Result<SynthesizedAudio<f32>, TtsError> {
+    let decoder = Decoder::new(Cursor::new(bytes))?;
+    let channels = decoder.channels();
+    let sample_rate = decoder.sample_rate();
+    let samples: Vec<f32> = decoder.convert_samples().collect();
+    let duration = samples.len() / channels.max(1) as usize;
 
     Ok(SynthesizedAudio::new(
         samples,
-        Wav(reader.spec()),
-        Some(reader.duration() as i32),
+        Spec::Wav(WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        }),
+        Some(duration as i32),
     ))
 }
 
@@ -47,94 +52,3 @@ pub fn get_path(path: String) -> String {
     new_path.push_str(&format!("/src/{}", path));
     return new_path;
 }
-
-pub fn play_audio<T>(data: &[T], rate: u32) -> Result<Sink, Box<dyn Error>>
-where
-    T: rodio::Sample + Send + 'static,
-    f32: FromSample<T>,
-{
-    let (_stream, handle) = rodio::OutputStream::try_default().unwrap();
-    let source = SamplesBuffer::new(1, rate, data);
-    let sink = rodio::Sink::try_new(&handle).unwrap();
-
-    sink.append(source);
-
-    Ok(sink)
-}
-
-pub fn play_wav_file(path: &PathBuf) -> Result<Sink, Box<dyn Error>> {
-    let file = std::fs::File::open(path)?;
-    let decoder = Decoder::new(file)?;
-    let (_stream, stream_handle) = OutputStream::try_default()?;
-    let sink = Sink::try_new(&stream_handle)?;
-
-    sink.append(decoder);
-    Ok(sink)
-}
-
-pub fn save_wav(data: &[f32], filename: &PathBuf, sample_rate: u32) -> Result<(), std::io::Error> {
-    let mut file = std::fs::File::create(filename)?;
-
-    // Write WAV header
-    let (chunk_size, bits_per_sample) = (44 + data.len() * 4, 32);
-    let pcm = 1; // PCM format
-
-    let header = [
-        // RIFF chunk
-        b'R',
-        b'I',
-        b'F',
-        b'F',
-        (chunk_size & 0xff) as u8,
-        (chunk_size >> 8 & 0xff) as u8,
-        (chunk_size >> 16 & 0xff) as u8,
-        (chunk_size >> 24 & 0xff) as u8,
-        // WAVE chunk
-        b'W',
-        b'A',
-        b'V',
-        b'E',
-        // fmt subchunk
-        b'f',
-        b'm',
-        b't',
-        b' ',
-        16,
-        0,
-        0,
-        0, // Subchunk size (16 for PCM format)
-        pcm as u8,
-        0, // PCM format
-        1,
-        0, // Mono channel
-        (sample_rate & 0xff) as u8,
-        (sample_rate >> 8 & 0xff) as u8,
-        (sample_rate >> 16 & 0xff) as u8,
-        (sample_rate >> 24 & 0xff) as u8,
-        4,
-        0, // Average bytes per second (4 for 32-bit samples)
-        4,
-        0, // Block align (4 for 32-bit samples, mono)
-        bits_per_sample as u8,
-        0, // Bits per sample
-        // data subchunk
-        b'd',
-        b'a',
-        b't',
-        b'a',
-        (data.len() * 4) as u8,
-        (data.len() * 4 >> 8 & 0xff) as u8,
-        (data.len() * 4 >> 16 & 0xff) as u8,
-        (data.len() * 4 >> 24 & 0xff) as u8,
-    ];
-    file.write_all(&header)?;
-
-    // Write audio data (assuming f32 samples between -1.0 and 1.0)
-    for sample in data {
-        let i32_sample = (sample * 2147483647.0) as i32; // Convert to 32-bit signed integer
-        let bytes = i32_sample.to_le_bytes();
-        file.write_all(&bytes)?;
-    }
-
-    Ok(())
-}